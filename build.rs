@@ -0,0 +1,56 @@
+//! Captures build metadata for the `version` and `about` commands.
+
+use std::{
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The stdout of `program args`, if it ran successfully.
+fn output_of(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The version of the `name` package locked in `Cargo.lock`.
+fn locked_version(name: &str) -> Option<String> {
+    let lock = std::fs::read_to_string("Cargo.lock").ok()?;
+    let mut lines = lock.lines();
+    lines.find(|line| *line == format!("name = \"{name}\""))?;
+    let version = lines.next()?.strip_prefix("version = ")?;
+    Some(version.trim_matches('"').to_string())
+}
+
+fn main() {
+    let git_hash = output_of("git", &["rev-parse", "--short", "HEAD"]);
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output_of(&rustc, &["--version"]);
+    let grammers_version = locked_version("grammers-client");
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    let unknown = || "unknown".to_string();
+    println!(
+        "cargo:rustc-env=TOMORIN_GIT_HASH={}",
+        git_hash.unwrap_or_else(unknown)
+    );
+    println!(
+        "cargo:rustc-env=TOMORIN_RUSTC_VERSION={}",
+        rustc_version.unwrap_or_else(unknown)
+    );
+    println!(
+        "cargo:rustc-env=TOMORIN_GRAMMERS_VERSION={}",
+        grammers_version.unwrap_or_else(unknown)
+    );
+    println!("cargo:rustc-env=TOMORIN_BUILD_TIMESTAMP={timestamp}");
+
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    if Path::new(".git").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs");
+    }
+}
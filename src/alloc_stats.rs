@@ -0,0 +1,92 @@
+//! A global allocator counting allocations, enabled by the `alloc-stats` feature, for
+//! diagnosing leaks with `,memprofile`.
+
+/// Allocation counters since the process started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocStats {
+    pub live_bytes: usize,
+    pub peak_bytes: usize,
+    pub allocations: usize,
+    pub deallocations: usize,
+}
+
+#[cfg(feature = "alloc-stats")]
+mod counting {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::atomic::{AtomicUsize, Ordering::Relaxed},
+    };
+
+    use super::AllocStats;
+
+    static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+    static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+    static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    static DEALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counting;
+
+    fn allocated(size: usize) {
+        let live = LIVE_BYTES.fetch_add(size, Relaxed) + size;
+        PEAK_BYTES.fetch_max(live, Relaxed);
+        ALLOCATIONS.fetch_add(1, Relaxed);
+    }
+
+    fn deallocated(size: usize) {
+        LIVE_BYTES.fetch_sub(size, Relaxed);
+        DEALLOCATIONS.fetch_add(1, Relaxed);
+    }
+
+    // SAFETY: every call is forwarded to `System`, only counting around it.
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                allocated(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc_zeroed(layout) };
+            if !ptr.is_null() {
+                allocated(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            deallocated(layout.size());
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new = unsafe { System.realloc(ptr, layout, new_size) };
+            if !new.is_null() {
+                deallocated(layout.size());
+                allocated(new_size);
+            }
+            new
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: Counting = Counting;
+
+    pub fn stats() -> AllocStats {
+        AllocStats {
+            live_bytes: LIVE_BYTES.load(Relaxed),
+            peak_bytes: PEAK_BYTES.load(Relaxed),
+            allocations: ALLOCATIONS.load(Relaxed),
+            deallocations: DEALLOCATIONS.load(Relaxed),
+        }
+    }
+}
+
+/// The allocation counters, if built with the `alloc-stats` feature.
+pub fn stats() -> Option<AllocStats> {
+    #[cfg(feature = "alloc-stats")]
+    return Some(counting::stats());
+    #[cfg(not(feature = "alloc-stats"))]
+    None
+}
@@ -1,6 +1,31 @@
+use std::{fs::OpenOptions, path::Path, sync::Mutex};
+
 use clap::Parser;
 
-use tracing_subscriber::fmt::time::ChronoLocal;
+use tracing_subscriber::{
+    Layer, Registry, filter::LevelFilter, fmt::time::ChronoLocal, layer::SubscriberExt, reload,
+    util::SubscriberInitExt,
+};
+
+type FileLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Adds the log file once the config naming it is loaded, logging starts before that.
+pub struct LogFile(reload::Handle<Option<FileLayer>, Registry>);
+
+impl LogFile {
+    /// Also write the log to `path`, appending to it.
+    pub fn open(&self, path: &Path) -> anyhow::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let layer = tracing_subscriber::fmt::layer()
+            .with_timer(ChronoLocal::rfc_3339())
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .boxed();
+        self.0.reload(Some(layer))?;
+        tracing::info!("Logging to {path:?}");
+        Ok(())
+    }
+}
 
 #[derive(Debug, Parser)]
 #[command(version, about)]
@@ -10,20 +35,25 @@ pub struct Args {
 }
 
 impl Args {
-    pub fn init_debug(&self) {
-        let mut builder = tracing_subscriber::fmt().with_timer(ChronoLocal::rfc_3339());
-
-        if self.debug {
-            builder = builder.with_max_level(tracing::Level::DEBUG);
+    pub fn init_debug(&self) -> LogFile {
+        let level = if self.debug {
+            LevelFilter::DEBUG
         } else {
-            builder = builder.with_max_level(tracing::Level::INFO);
-        }
+            LevelFilter::INFO
+        };
+        let (file, handle) = reload::Layer::new(None);
 
-        builder.init();
+        tracing_subscriber::registry()
+            .with(file)
+            .with(tracing_subscriber::fmt::layer().with_timer(ChronoLocal::rfc_3339()))
+            .with(level)
+            .init();
+        LogFile(handle)
     }
 
-    pub fn init(&self) {
-        self.init_debug();
+    pub fn init(&self) -> LogFile {
+        let log_file = self.init_debug();
         tracing::info!("Args initialized: {:#?}", self);
+        log_file
     }
 }
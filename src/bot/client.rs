@@ -1,4 +1,11 @@
-use std::{env, process::Stdio, time::Duration};
+use std::{
+    collections::HashSet,
+    env,
+    path::Path,
+    process::Stdio,
+    sync::{Arc, Mutex, atomic::AtomicBool},
+    time::Duration,
+};
 
 use grammers_client::{
     Client, InputMessage,
@@ -8,6 +15,7 @@ use grammers_client::{
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::{
     process::Command,
+    sync::mpsc,
     time::{Instant, interval_at},
 };
 
@@ -16,11 +24,109 @@ pub struct TomorinClient {
     pub client: Client,
     pub me: User,
     pub start_time: std::time::Instant,
+    conf: Arc<Conf>,
+    uptime: uptime::PersistedUptime,
+    handled: Arc<Mutex<dedup::HandledCache>>,
+    owned: Arc<Mutex<dedup::OwnedMessages>>,
+    history: Arc<Mutex<history::History>>,
+    replies: Arc<Mutex<respond::Replies>>,
+    api_stats: Arc<Mutex<apistats::ApiStats>>,
+    prefixes: Arc<Mutex<prefixes::ChatPrefixes>>,
+    quiet: Arc<Mutex<HashSet<dedup::MessageKey>>>,
+    truncation: Arc<Mutex<Truncation>>,
+    watches: Arc<Mutex<watch::Watches>>,
+    error_mute: Arc<Mutex<mute::ErrorMute>>,
+    outbound: Arc<Mutex<ratelimit::Outbound>>,
+    notebooks: Arc<Mutex<notebook::Notebooks>>,
+    confirms: Arc<Mutex<confirm::Confirms>>,
+    output_modes: Arc<Mutex<outmode::ChatOutputModes>>,
+    long_outputs: Arc<Mutex<outmode::LongOutputs>>,
+    eval_settings: Arc<Mutex<evalset::ChatEvalSettings>>,
+    /// Name of the account logged in, whose session is kept in `<account>.session`.
+    account: Arc<str>,
+    /// Hands a client logged into another account over to the update loop.
+    switched: mpsc::UnboundedSender<Arc<TomorinClient>>,
+    /// Set by `,drain`: only commands ending or reporting on the drain, or stopping or
+    /// settling work already started, are handled.
+    draining: Arc<AtomicBool>,
+    drains: mpsc::UnboundedSender<DrainRequest>,
 }
 
-use crate::conf::Conf;
+use crate::conf::{Conf, EvalRender, Truncation};
+use crate::error::{BotError, Result};
+use crate::retry::{RetryPolicy, flood_wait, retry};
+use action::ChatAction;
+pub use drain::DrainRequest;
+use evalmode::EvalMode;
 use grammers_client::Update::{MessageEdited, NewMessage};
 use grammers_client::{Config, SignInError, session::Session};
+use message::{MessageOps, Outgoing};
+use outfmt::OutputFormat;
+use route::{RoutedCommand, Triggers, route};
+
+mod accounts;
+mod action;
+mod album;
+mod apistats;
+mod applyfmt;
+mod cache;
+mod clock;
+mod config;
+mod confirm;
+mod dedup;
+mod diff;
+mod draft;
+mod drain;
+mod entities;
+mod evalinfo;
+mod evalmode;
+mod evalping;
+mod evalset;
+mod fence;
+mod history;
+mod inline;
+mod limits;
+mod log;
+mod macros;
+mod membership;
+mod memprofile;
+mod message;
+mod monitor;
+mod mute;
+mod netinfo;
+mod notebook;
+mod outfmt;
+mod outmode;
+mod panic;
+mod poll;
+mod prefixes;
+mod profile;
+mod ratelimit;
+mod rawtl;
+mod react;
+mod read;
+mod redirect;
+mod resolve;
+mod respond;
+mod route;
+mod schedule;
+mod script;
+mod share;
+mod shellwords;
+mod shot;
+mod showconf;
+mod startup;
+mod state;
+mod stickerset;
+
+mod unshorten;
+mod upload;
+mod uptime;
+mod version;
+mod vs;
+mod watch;
+mod wc;
+mod wrap;
 
 mod reader {
     use std::io::{self, BufRead as _, Write as _};
@@ -28,7 +134,7 @@ mod reader {
     pub struct StdinReader;
 
     impl StdinReader {
-        pub fn read(prompt: &str) -> anyhow::Result<String> {
+        pub fn read(prompt: &str) -> io::Result<String> {
             let stdout = io::stdout();
             let mut stdout = stdout.lock();
             stdout.write_all(prompt.as_bytes())?;
@@ -45,18 +151,30 @@ mod reader {
 }
 
 impl TomorinClient {
-    const SESSION: &'static str = "tomorin.session";
+    const STATE: &'static str = "tomorin.state";
+    const PREFIXES: &'static str = "tomorin.prefixes";
 
-    pub async fn new(conf: Conf) -> anyhow::Result<Self> {
-        let client = Client::connect(Config {
-            session: Session::load_file_or_create(Self::SESSION)?,
+    async fn connect(conf: &Conf, session: &str) -> anyhow::Result<Client> {
+        Ok(Client::connect(Config {
+            session: Session::load_file_or_create(session)?,
             api_id: conf.api_id,
-            api_hash: conf.api_hash,
+            api_hash: conf.api_hash.clone(),
             params: Default::default(),
         })
-        .await?;
+        .await?)
+    }
+
+    pub async fn new(
+        conf: Conf,
+        switched: mpsc::UnboundedSender<Arc<TomorinClient>>,
+        drains: mpsc::UnboundedSender<DrainRequest>,
+    ) -> anyhow::Result<Self> {
+        let account = accounts::DEFAULT_ACCOUNT;
+        let session = accounts::session_file(account);
+        let client = Self::connect(&conf, &session).await?;
 
-        if !client.is_authorized().await? {
+        let logged_in = !client.is_authorized().await?;
+        if logged_in {
             let token = client.request_login_code(&conf.phone).await?;
             let code = reader::StdinReader::read("Code: ")?;
             if let Err(SignInError::PasswordRequired(password_token)) =
@@ -70,103 +188,240 @@ impl TomorinClient {
             }
         }
 
-        client.session().save_to_file(Self::SESSION)?;
+        client.session().save_to_file(&session)?;
 
         let me = client.get_me().await?;
 
         let start_time = std::time::Instant::now();
+        let truncation = Arc::new(Mutex::new(conf.output_truncation));
+        let outbound = ratelimit::Outbound::new(conf.outbound_global_rate, conf.outbound_chat_rate);
+        let uptime = uptime::PersistedUptime::load(Path::new(Self::STATE));
 
-        Ok(Self {
+        let this = Self {
             client,
             me,
             start_time,
+            conf: Arc::new(conf),
+            uptime,
+            handled: Default::default(),
+            owned: Default::default(),
+            history: Default::default(),
+            replies: Default::default(),
+            api_stats: Default::default(),
+            prefixes: Arc::new(Mutex::new(prefixes::ChatPrefixes::load(Path::new(
+                Self::PREFIXES,
+            )))),
+            quiet: Default::default(),
+            truncation,
+            watches: Default::default(),
+            error_mute: Default::default(),
+            outbound: Arc::new(Mutex::new(outbound)),
+            notebooks: Default::default(),
+            confirms: Default::default(),
+            output_modes: Default::default(),
+            long_outputs: Default::default(),
+            eval_settings: Default::default(),
+            account: account.into(),
+            switched,
+            draining: Default::default(),
+            drains,
+        };
+        if logged_in {
+            this.send_login_banner().await;
+        }
+        Ok(this)
+    }
+
+    /// A copy of this client on a fresh connection, resumed from the saved session.
+    /// Everything but the connection is shared with `self`.
+    pub async fn reconnect(&self) -> anyhow::Result<Self> {
+        let session = self.session_file();
+        self.client.session().save_to_file(&session)?;
+        let client = Self::connect(&self.conf, &session).await?;
+        Ok(Self {
+            client,
+            ..self.clone()
         })
     }
 
-    pub async fn next_update(&self) -> anyhow::Result<grammers_client::Update> {
+    /// Persist the accumulated uptime, to be called on graceful shutdown.
+    pub fn save_state(&self) {
+        let session = self.start_time.elapsed();
+        if let Err(e) = self.uptime.save(Path::new(Self::STATE), session) {
+            tracing::warn!("Failed to save state to {}: {e}", Self::STATE);
+        }
+    }
+
+    /// Exit code telling an operator a new login is needed, rather than a transient failure.
+    pub const EXIT_SESSION_REVOKED: i32 = 3;
+
+    /// Stop the bot for good after Telegram rejected the session with `reason`, which
+    /// no retry or reconnect can fix. The stale session file is removed if configured.
+    pub fn session_revoked(&self, reason: &str) -> ! {
+        tracing::error!(
+            "Telegram no longer accepts the session ({reason}), log in again to continue"
+        );
+        if self.conf.delete_revoked_session {
+            let session = self.session_file();
+            match std::fs::remove_file(&session) {
+                Ok(()) => tracing::info!("Deleted the stale session file {session}"),
+                Err(e) => tracing::warn!("Failed to delete {session}: {e}"),
+            }
+        }
+        self.save_state();
+        std::process::exit(Self::EXIT_SESSION_REVOKED)
+    }
+
+    pub async fn next_update(&self) -> Result<grammers_client::Update> {
         self.client.next_update().await.map_err(Into::into)
     }
 
-    pub async fn update(&self, update: grammers_client::Update) -> anyhow::Result<()> {
+    pub async fn update(&self, update: grammers_client::Update) -> Result<()> {
         match update {
+            // Output the bot is rendering into a command still being handled.
+            NewMessage(m) | MessageEdited(m) if self.is_owned(&m) => (),
             NewMessage(m) | MessageEdited(m) => {
                 if let Some(a) = m.sender()
                     && a.id() == self.me.id()
                 {
-                    const CMD_PREFIXES: [&str; 4] = [",", "，", ".", "。"];
-                    const REPEAT: &str = "+";
-                    const EVAL: &str = "r#";
-                    const HELP: &str = "h#";
-                    const STATUS: &str = "s#";
-
                     let text = m.text();
 
-                    if text == REPEAT {
-                        self.handle_repeat(&m).await?;
+                    // Edits that leave the text untouched were already handled, which includes
+                    // the ones echoing output the bot wrote.
+                    let key = (m.chat().id(), m.id());
+                    if !self.handled.lock().unwrap().insert(key, text) {
                         return Ok(());
                     }
 
-                    if text.starts_with(EVAL) {
-                        let code = text.trim_start_matches(EVAL);
-                        self.handle_eval(code, &m).await?;
+                    let routed = self.route(text, key.0);
+                    let _owned = (routed != RoutedCommand::None).then(|| self.own(&m));
+                    if self.refuse_while_draining(&routed, &m).await? {
                         return Ok(());
                     }
-
-                    for prefix in CMD_PREFIXES {
-                        if text.starts_with(prefix) {
-                            let cmd = text.trim_start_matches(prefix);
-                            self.handle_cmd(cmd, &m).await?;
-                            return Ok(());
-                        }
-                    }
-
-                    if text.starts_with(HELP) {
-                        self.handle_help(&m).await?;
-                        return Ok(());
+                    if routed != RoutedCommand::None {
+                        return self.dispatch(routed, &m).await;
                     }
 
-                    if text.starts_with(STATUS) {
-                        self.handle_status(&m).await?;
-                        return Ok(());
+                    if let Some(code) = self.auto_eval_code(&m) {
+                        let _owned = self.own(&m);
+                        self.handle_auto_eval(&code, &m).await?;
+                        return self.deliver_full_output(&m).await;
                     }
                 }
             }
+            grammers_client::Update::InlineQuery(query) if self.me.is_bot() => {
+                return self.handle_inline_query(query).await;
+            }
             _ => (),
         };
         Ok(())
     }
 
-    async fn handle_eval(&self, code: &str, m: &Message) -> anyhow::Result<()> {
-        use crate::eval::EvalClient;
-        m.edit("少女祈祷中......").await?;
+    /// Handle the command `routed` from `m`, then deliver its output in full if the chat's
+    /// output mode asks for it.
+    async fn dispatch(&self, routed: RoutedCommand<'_>, m: &Message) -> Result<()> {
+        let result = match routed {
+            RoutedCommand::Repeat => self.handle_repeat(m).await,
+            RoutedCommand::Eval(code) => self.handle_eval(code, m).await,
+            RoutedCommand::Notebook(code) => self.handle_notebook(code, m).await,
+            RoutedCommand::Script(language, code) => self.handle_script(language, code, m).await,
+            RoutedCommand::Cmd(cmd) => self.handle_cmd(cmd, m).await,
+            RoutedCommand::Help => Self::handle_help(&self.responder(m)).await,
+            RoutedCommand::Status => self.handle_status(m).await,
+            RoutedCommand::None => Ok(()),
+        };
+        let delivered = self.deliver_full_output(m).await;
+        result.and(delivered)
+    }
 
-        let resp = EvalClient::intance().eval(code).await?;
+    /// Route `text`, sent by the account itself in `chat`, with the chat's prefixes.
+    fn route<'t>(&self, text: &'t str, chat: i64) -> RoutedCommand<'t> {
+        let chat_prefixes = self.prefixes.lock().unwrap().get(chat);
+        let cmd_prefixes = chat_prefixes.iter().map(String::as_str).collect::<Vec<_>>();
+        let triggers = Triggers {
+            cmd_prefixes: &cmd_prefixes,
+            ..Triggers::DEFAULT
+        };
+        route(text, &triggers)
+    }
 
-        self.edit_eval_msg(m, code, &resp).await
+    /// Mark `m` as taken over by the bot until the returned guard is dropped, so edits
+    /// made while rendering output into it are not mistaken for new commands.
+    fn own(&self, m: &Message) -> dedup::Owned {
+        dedup::Owned::new(&self.owned, (m.chat().id(), m.id()))
     }
 
-    async fn edit_eval_msg(&self, m: &Message, code: &str, resp: &str) -> anyhow::Result<()> {
-        let code = code.trim();
-        let resp = resp.trim();
-        let code_entity = MessageEntity::Pre(MessageEntityPre {
-            offset: 0,
-            length: code.chars().count() as i32,
-            language: "Rust".to_string(),
-        });
+    /// Remember `text` as written by the bot into `m`, so the update echoing it is not
+    /// handled as a command once `m` is no longer owned.
+    fn mark_written(&self, m: &Message, text: &str) {
+        self.handled
+            .lock()
+            .unwrap()
+            .insert((m.chat().id(), m.id()), text);
+    }
 
-        let resp = format!("\n{resp}");
+    fn is_owned(&self, m: &Message) -> bool {
+        self.owned
+            .lock()
+            .unwrap()
+            .contains(&(m.chat().id(), m.id()))
+    }
 
-        let resp_entity = MessageEntity::Pre(MessageEntityPre {
-            offset: code_entity.length(),
-            length: resp.chars().count() as i32,
-            language: "Output".to_string(),
-        });
+    async fn handle_eval(&self, code: &str, m: &Message) -> Result<()> {
+        use crate::eval::EvalClient;
+
+        let (mode, code) = match evalmode::split_eval_mode(code) {
+            Ok(split) => split,
+            Err(e) => {
+                self.respond(m, e).await?;
+                return Ok(());
+            }
+        };
+        if mode == EvalMode::Show {
+            return self.handle_eval_show(code, m).await;
+        }
+        if let Err(e) = check_code_len(code, self.conf.max_code_chars) {
+            self.respond(m, e).await?;
+            return Ok(());
+        }
+        if let EvalMode::SendTo(target) = mode {
+            return self.handle_eval_send(target, code, m).await;
+        }
+        if matches!(mode, EvalMode::Bench(_)) && crate::eval::defines_main(code) {
+            self.respond(m, "Can't bench a snippet defining its own `fn main`")
+                .await?;
+            return Ok(());
+        }
+        self.respond(m, "少女祈祷中......").await?;
 
-        let text = format!("{code}{resp}");
+        let settings = self.eval_settings(m.chat().id());
+        let eval = async {
+            let client = EvalClient::intance();
+            match mode {
+                EvalMode::Bench(iterations) => client.bench(code, iterations, settings).await,
+                EvalMode::Check => client.check(code, settings).await,
+                EvalMode::Warn => client.eval_with_warnings(code, settings).await,
+                EvalMode::Timing => client.eval_timed(code, settings).await,
+                _ => client.eval(code, settings).await,
+            }
+        };
+        let resp = self.with_action(m, ChatAction::Typing, eval).await;
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e @ (BotError::Http(_) | BotError::HttpTimeout | BotError::HttpRateLimited)) => {
+                format!("笨！\n{e}")
+            }
+            Err(e) => return Err(e),
+        };
 
-        let msg = InputMessage::text(&text).fmt_entities(vec![code_entity, resp_entity]);
+        self.edit_eval_msg(m, code, &resp).await
+    }
 
-        match m.edit(msg).await {
+    async fn edit_eval_msg(&self, m: &Message, code: &str, resp: &str) -> Result<()> {
+        match self
+            .respond(m, eval_msg(self.conf.eval_render, code, resp))
+            .await
+        {
             Err(grammers_client::InvocationError::Rpc(e)) if e.name == "MESSAGE_NOT_MODIFIED" => {
                 Ok(())
             }
@@ -175,28 +430,50 @@ impl TomorinClient {
         }
     }
 
-    async fn edit_pre_msg(&self, m: &Message, resp: &str, lang: &str) -> anyhow::Result<()> {
-        const MAX_LINES: usize = 30;
+    async fn edit_pre_msg(m: &impl MessageOps, resp: &str, lang: &str) -> Result<()> {
         const TRIMMED_HINT: &str = "以上行数被杜叔叔吃掉了！\n";
+        const TRIMMED_HINT_HEAD: &str = "\n以下行数被杜叔叔吃掉了！";
 
-        let trimmed = resp.trim();
+        let full = resp.trim();
+        let trimmed = match m.wrap_columns() {
+            Some(columns) => wrap::wrap_lines(full, columns),
+            None => full.into(),
+        };
         let line_count = trimmed.lines().count();
 
-        let trimmed = if line_count > MAX_LINES {
-            let mut lines = trimmed.lines().rev().take(MAX_LINES).collect::<Vec<&str>>();
-            lines.push(TRIMMED_HINT);
-            lines.into_iter().rev().collect::<Vec<&str>>().join("\n")
+        let trimmed = if line_count > MAX_OUTPUT_LINES {
+            match m.truncation() {
+                Truncation::Tail => {
+                    let mut lines = trimmed
+                        .lines()
+                        .rev()
+                        .take(MAX_OUTPUT_LINES)
+                        .collect::<Vec<&str>>();
+                    lines.push(TRIMMED_HINT);
+                    lines.into_iter().rev().collect::<Vec<&str>>().join("\n")
+                }
+                Truncation::Head => {
+                    let mut lines = trimmed
+                        .lines()
+                        .take(MAX_OUTPUT_LINES)
+                        .collect::<Vec<&str>>();
+                    lines.push(TRIMMED_HINT_HEAD);
+                    lines.join("\n")
+                }
+            }
         } else {
             trimmed.to_string()
         };
 
-        let msg =
-            InputMessage::text(&trimmed).fmt_entities(vec![MessageEntity::Pre(MessageEntityPre {
+        let length = trimmed.encode_utf16().count() as i32;
+        let msg = Outgoing::text(trimmed)
+            .fmt_entities(vec![MessageEntity::Pre(MessageEntityPre {
                 offset: 0,
-                length: trimmed.chars().count() as i32,
+                length,
                 language: lang.to_string(),
-            })]);
-        match m.edit(msg).await {
+            })])
+            .full_output(full);
+        match retry(&EDIT_RETRY, "edit", || m.edit(msg.clone()), flood_wait).await {
             Err(grammers_client::InvocationError::Rpc(e)) if e.name == "MESSAGE_NOT_MODIFIED" => {
                 Ok(())
             }
@@ -205,43 +482,58 @@ impl TomorinClient {
         }
     }
 
+    /// Read both streams into `msg`, passing it to `f` on every tick.
+    ///
+    /// Returns `true` if `msg` reached the output size limit, in which case reading
+    /// stops early if the command is to be killed.
     async fn read_buffer_per_tick<F>(
         &self,
         stdout_reader: &mut tokio::io::Lines<BufReader<tokio::process::ChildStdout>>,
         stderr_reader: &mut tokio::io::Lines<BufReader<tokio::process::ChildStderr>>,
         msg: &mut String,
         f: &mut F,
-    ) -> anyhow::Result<()>
+    ) -> Result<bool>
     where
-        for<'a> F: AsyncFnMut(&'a str) -> anyhow::Result<()> + Send + 'static,
+        for<'a> F: AsyncFnMut(&'a str) -> Result<()> + Send + 'static,
         for<'a> <F as AsyncFnMut<(&'a str,)>>::CallRefFuture<'a>:
-            Future<Output = anyhow::Result<()>> + Send + 'a,
+            Future<Output = Result<()>> + Send + 'a,
     {
         let mut ticker = interval_at(
             Instant::now()
-                .checked_add(Duration::from_millis(800))
+                .checked_add(Duration::from_millis(self.conf.output_initial_delay_ms))
                 .unwrap(),
-            Duration::from_secs(1),
+            Duration::from_millis(self.conf.output_tick_ms.max(1)),
         );
         let mut stdout_done = false;
         let mut stderr_done = false;
+        let mut truncated = false;
+        let mut push_line = |msg: &mut String, line: &str| {
+            if !truncated && !push_capped(msg, line, self.conf.output_max_bytes) {
+                truncated = true;
+                msg.push_str(&format!(
+                    "\n[output truncated at {} bytes]",
+                    self.conf.output_max_bytes
+                ));
+            }
+            truncated
+        };
 
         loop {
-            tokio::select! {
+            let full = tokio::select! {
                 res = stdout_reader.next_line(), if !stdout_done => match res {
-                    Ok(Some(line)) => {
-                        msg.push_str(&line);
-                        msg.push('\n');
+                    Ok(Some(line)) => push_line(msg, &line),
+                    Ok(None) => {
+                        stdout_done = true;
+                        false
                     }
-                    Ok(None) => stdout_done = true,
                     Err(e) => return Err(e.into()),
                 },
                 res = stderr_reader.next_line(), if !stderr_done => match res {
-                    Ok(Some(line)) => {
-                        msg.push_str(&line);
-                        msg.push('\n');
+                    Ok(Some(line)) => push_line(msg, &line),
+                    Ok(None) => {
+                        stderr_done = true;
+                        false
                     }
-                    Ok(None) => stderr_done = true,
                     Err(e) => return Err(e.into()),
                 },
                 _ = ticker.tick() => {
@@ -250,27 +542,154 @@ impl TomorinClient {
                     if stdout_done && stderr_done {
                         break;
                     }
+                    false
                 }
                 else => break,
+            };
+            if full && self.conf.output_overflow_kill {
+                f(msg).await?;
+                break;
             }
         }
 
-        Ok(())
+        Ok(truncated)
     }
 
-    pub async fn handle_cmd(&self, cmd: &str, m: &Message) -> anyhow::Result<()> {
-        let mut parts = cmd.split_whitespace();
-        let program = match parts.next() {
+    pub async fn handle_cmd(&self, cmd: &str, m: &Message) -> Result<()> {
+        let program = match cmd.split_whitespace().next() {
             Some(p) => p,
             None => {
-                m.edit("No command given").await?;
+                self.respond(m, "No command given").await?;
                 return Ok(());
             }
         };
-        let args = parts;
+        let rest = cmd.trim_start()[program.len()..].trim();
+
+        match program {
+            "setbio" => self.handle_setbio(rest, m).await,
+            "setname" => self.handle_setname(rest, m).await,
+            "join" => self.handle_join(rest, m).await,
+            "leave" if !self.confirmed(m).await? => Ok(()),
+            "leave" => self.handle_leave(m).await,
+            "diff" => Self::handle_diff(rest, &self.responder(m)).await,
+            "wc" => self.handle_wc(m).await,
+            "time" => self.handle_time(rest, m).await,
+            "history" => self.handle_history(&self.responder(m)).await,
+            "df" => self.handle_df(m).await,
+            "top" => self.handle_top(rest, m).await,
+            "shot" => self.handle_shot(rest, m).await,
+            "share" => self.handle_share(m).await,
+            "vs" => self.handle_vs(rest, m).await,
+            "tl" => self.handle_tl(rest, m).await,
+            "netinfo" => self.handle_netinfo(m).await,
+            "cache" => self.handle_cache(rest, m).await,
+            "apistats" => self.handle_apistats(rest, m).await,
+            "showconf" => self.handle_showconf(m).await,
+            "resolve" => self.handle_resolve(rest, m).await,
+            "prefix" => self.handle_prefix(rest, m).await,
+            "version" => self.handle_version(m).await,
+            "about" => self.handle_about(m).await,
+            "quiet" => self.handle_quiet(rest, m).await,
+            "truncate" => self.handle_truncate(rest, m).await,
+            "watch" => self.handle_watch(rest, m).await,
+            "entities" => self.handle_entities(m).await,
+            "bench-net" => self.handle_bench_net(rest, m).await,
+            "unshorten" => self.handle_unshorten(rest, m).await,
+            "account" => self.handle_account(rest, m).await,
+            "config" => self.handle_config(rest, m).await,
+            "mute-errors" => self.handle_mute_errors(rest, m).await,
+            "react" => self.handle_react(rest, m).await,
+            "link" => self.handle_link(m).await,
+            "upload" => self.handle_upload(rest, m).await,
+            "cleardraft" => self.handle_cleardraft(m).await,
+            "read" => self.handle_read(m).await,
+            "schedule" => self.handle_schedule(rest, m).await,
+            "run" => self.handle_run(rest, m).await,
+            "yes" => self.handle_confirm(true, m).await,
+            "no" => self.handle_confirm(false, m).await,
+            "log" => self.handle_log(rest, m).await,
+            "memprofile" => self.handle_memprofile(m).await,
+            "stickerset" => self.handle_stickerset(m).await,
+            "poll" => self.handle_poll(rest, m).await,
+            "applyfmt" => self.handle_applyfmt(rest, m).await,
+            "limits" => self.handle_limits(m).await,
+            "outmode" => self.handle_outmode(rest, m).await,
+            "evalset" => self.handle_evalset(rest, m).await,
+            "evalping" => self.handle_evalping(m).await,
+            "evalinfo" => self.handle_evalinfo(m).await,
+            "drain" => self.handle_drain(m).await,
+            "resume" => self.handle_resume(m).await,
+            "state" => self.handle_state(rest, m).await,
+            _ if let Some(index) = program.strip_prefix('!') => {
+                self.handle_history_rerun(index, m).await
+            }
+            _ => self.run_shell(cmd, m).await,
+        }
+    }
+
+    /// Whether `program` may be spawned for the command in `m`: it is not on `cmd-denylist`,
+    /// and when on `confirm-commands` the command was confirmed. Otherwise tells the chat
+    /// why not, or asks for confirmation.
+    async fn may_spawn(&self, program: &str, m: &Message) -> Result<bool> {
+        if matches_program(&self.conf.cmd_denylist, program) {
+            self.respond(m, format!("笨！\n{program} is blocked by cmd-denylist"))
+                .await?;
+            return Ok(false);
+        }
+        if matches_program(&self.conf.confirm_commands, program) {
+            return self.confirmed(m).await;
+        }
+        Ok(true)
+    }
+
+    async fn run_shell(&self, cmd: &str, m: &Message) -> Result<()> {
+        let (command, target) = match redirect::split_redirect(cmd) {
+            Ok(split) => split,
+            Err(e) => {
+                self.respond(m, e).await?;
+                return Ok(());
+            }
+        };
+        let (command, format) = match outfmt::split_fmt(command) {
+            Ok(split) => split,
+            Err(e) => {
+                self.respond(m, e).await?;
+                return Ok(());
+            }
+        };
+        if target.is_some() && format != OutputFormat::Text {
+            self.respond(m, "--fmt only applies to output shown in the chat")
+                .await?;
+            return Ok(());
+        }
+        let words = match shellwords::split_args(command) {
+            Ok(words) => words,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+        let Some((program, args)) = words.split_first() else {
+            self.respond(m, "No command given").await?;
+            return Ok(());
+        };
+
+        if !self.may_spawn(program, m).await? {
+            return Ok(());
+        }
+
+        self.history
+            .lock()
+            .unwrap()
+            .push(m.chat().id(), cmd, self.conf.history_size);
+
+        if let Some(target) = target {
+            return self.run_shell_to_file(program, args, target, m).await;
+        }
 
         let mut resp = format!("❯ {cmd}");
         resp.push('\n');
+        let header_len = resp.len();
 
         let mut child = match Command::new(program)
             .args(args)
@@ -280,8 +699,8 @@ impl TomorinClient {
         {
             Ok(c) => c,
             Err(e) => {
-                resp.push_str(&format!("笨！\n{e}"));
-                self.edit_pre_msg(m, &resp, "StdErr").await?;
+                resp.push_str(&format!("笨！\n{}", BotError::Spawn(e)));
+                Self::edit_pre_msg(&self.responder(m), &resp, "StdErr").await?;
                 return Ok(());
             }
         };
@@ -291,32 +710,115 @@ impl TomorinClient {
         let mut stdout_reader = BufReader::new(stdout).lines();
         let mut stderr_reader = BufReader::new(stderr).lines();
 
-        let client = self.clone();
-        let m2 = m.clone();
-        self.read_buffer_per_tick(
-            &mut stdout_reader,
-            &mut stderr_reader,
-            &mut resp,
-            &mut async move |resp| client.edit_pre_msg(&m2, resp, "StdOut").await,
-        )
-        .await?;
+        let out = self.responder(m);
+        let truncated = self
+            .read_buffer_per_tick(
+                &mut stdout_reader,
+                &mut stderr_reader,
+                &mut resp,
+                &mut async move |resp| Self::edit_pre_msg(&out, resp, "StdOut").await,
+            )
+            .await?;
+        if truncated && self.conf.output_overflow_kill {
+            child.kill().await?;
+        }
 
+        let status = child.wait().await?;
+        if format != OutputFormat::Text && resp.len() > header_len {
+            let formatted = outfmt::format_output(&resp[header_len..], format);
+            resp.truncate(header_len);
+            resp.push_str(&formatted);
+            Self::edit_pre_msg(&self.responder(m), &resp, "StdOut").await?;
+        }
+        // Both streams stayed silent, make it clear the command did finish.
+        if resp.len() == header_len {
+            resp.push_str("(no output)");
+            if let Some(code) = status.code()
+                && code != 0
+            {
+                resp.push_str(&format!("\nexit code {code}"));
+            }
+            Self::edit_pre_msg(&self.responder(m), &resp, "StdOut").await?;
+        }
         Ok(())
     }
 
-    pub async fn handle_help(&self, m: &Message) -> anyhow::Result<()> {
+    pub async fn handle_help(m: &impl MessageOps) -> Result<()> {
         let help_text = "**Available commands**:
 
 `+` - Reply to forward/repeat the message    
 `r#<code>` - Evaluate Rust code    
+`r#show#<code>` - Show the wrapped source that would be evaluated    
+`r#check#<code>` - Compile the code without running it    
+`r#warn#<code>` - Evaluate Rust code and show the first compiler warnings    
+`r#time#<code>` - Evaluate Rust code and estimate its compile and run time    
+`r#bench:<N>#<code>` - Run the code N times in release mode and report timings    
+`r#>@<chat>#<code>` - Evaluate Rust code and send the result to another chat    
+`,evalping` - Check that the eval backend is reachable and how fast it answers    
+`,evalinfo` - List the editions, toolchains and crates snippets can use    
+`,evalset [channel=<c>] [mode=<m>] [edition=<e>] | evalset reset` - Show or set the toolchain channel, build mode and edition evals use in this chat    
+`nb#<code>` - Evaluate Rust code as a new notebook cell replying to the previous one, `nb#` alone starts over    
+`py#<code>` / `js#<code>` / `sh#<code>` - Run Python, JavaScript or shell code    
 `<prefix><command>` - Execute a shell command (e.g., `,ls`, `，ls`, `.ls`, `。ls`)    
+`,<command> > <file>` - Execute a shell command, writing its output to a file    
+`,<command> --fmt json|table` - Execute a shell command and pretty-print its output    
+`,setbio <text>` - Update your bio    
+`,setname <first> [last]` - Update your name    
+`,join <invite-link>` - Join a chat by invite link    
+`,leave` - Leave the current chat, after confirming    
+`,yes` / `,no` - Confirm or cancel the command awaiting confirmation in this chat    
+`,cleardraft` - Clear the saved draft of the current chat    
+`,read` - Mark the current chat as read, or reply to mark it read up to that message    
+`,schedule <time> <text>` - Schedule a message in this chat, time as 90m, HH:MM, YYYY-MM-DDTHH:MM or unix-ts    
+`,poll <question> <option>... [--quiz <n>]` - Send a poll, or a quiz with option n correct, quoting text with spaces like a shell    
+`,diff [text]` - Reply to diff a message against its reply target or the given text    
+`,wc` - Reply to count lines, words and characters of a message    
+`,entities` - Reply to show the formatting entities of a message    
+`,stickerset` - Reply to a sticker to show its set and a link to add it    
+`,applyfmt <text>` - Reply to a formatted message to apply its formatting to the text, or render the text as markdown    
+`,react <emoji | custom emoji id>` - Reply to react to a message    
+`,time [tz | unix-ts]` - Show the time in a timezone or convert a timestamp    
+`,history` - List the recent shell commands of this chat    
+`,!!` / `,!<n>` - Re-run the last / n-th shell command of this chat, in the current working directory and environment    
+`,df` - Show disk usage    
+`,top [mem|cpu]` - Show the top processes by memory or CPU usage    
+`,run [-k] <macro>` - Run the steps of a macro from the config, `-k` to keep going after a failure    
+`,shot <command>` - Execute a shell command and send its output as an image    
+`,upload <path>` - Send a file from the server as a document, showing the progress    
+`,share` - Reply to an eval result to share it as a playground gist    
+`,vs <code>` - Reply to an eval result to run it and the given code, and compare their outputs    
+`,tl <json>` - Invoke a read-only Telegram API method, if enabled in the config    
+`,limits` - Show the account's limits, such as the upload size, and its premium status    
+`,netinfo` - Show the current and nearest datacenter and the connection latency    
+`,unshorten <url>` - Follow the redirects of a URL without opening it and show each hop    
+`,bench-net <n>` - Time n message edits and report the latency    
+`,cache clear` - Flush the runtime caches    
+`,log [lines]` / `,log follow [duration]` - Show the last lines of the bot's log file, or follow it    
+`,memprofile` - Show the memory used by the bot and its RSS trend    
+`,apistats [reset]` - Show (and reset) the Telegram API calls made    
+`,showconf` - Show the active config with secrets redacted    
+`,resolve <t.me link>` - Show the linked message    
+`,link` - Reply to get the t.me link of a message    
+`,prefix [add|remove <p>]` - Show or change the command prefixes of this chat    
+`,version` - Show version and build details    
+`,about` - Show the build, enabled features, OS and uptime for a bug report    
+`,quiet <command>` - Run a command with its output sent without notification    
+`,outmode [inline|file|image|default]` - Show or set whether long output is also sent in full as a file or an image in this chat    
+`,drain` / `,resume` - Stop accepting commands and watches and report once the running ones finished, before a restart / accept commands again    
+`,state [<key> | clear <key>]` - Show the sizes of the in-memory state, one piece of it as JSON, or clear it    
+`,truncate [head|tail]` - Keep the first or last lines of long output    
+`,watch <seconds> <command>` / `,watch stop` - Re-run a command periodically / stop the watches of this chat    
+`,account [list | switch <name>]` - Show, list or switch the logged in accounts    
+`,config export [full]` - Upload the config file, secrets redacted unless full    
+`,config import [confirm]` - Reply to a KDL file to validate it, and with confirm to replace the config    
+`,mute-errors [<duration> | off]` - Show or set how long handler errors are only logged at debug level    
 `s#` - Show bot status    
 `h#` - Show this help message";
-        m.edit(InputMessage::markdown(&help_text)).await?;
+        m.edit(Outgoing::markdown(help_text)).await?;
         Ok(())
     }
 
-    pub async fn handle_status(&self, m: &Message) -> anyhow::Result<()> {
+    pub async fn handle_status(&self, m: &Message) -> Result<()> {
         use chrono::Duration;
 
         let uptime = std::time::Instant::now().duration_since(self.start_time);
@@ -327,6 +829,13 @@ impl TomorinClient {
 
         let current_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
 
+        let total_uptime = self.uptime.total + std::time::Duration::from_secs(uptime.as_secs());
+        let total_uptime_fmt = humantime::format_duration(total_uptime);
+        let first_start = chrono::DateTime::from_timestamp(self.uptime.first_start, 0)
+            .map(|dt| dt.with_timezone(&chrono::Local))
+            .unwrap_or(start_dt)
+            .format("%Y-%m-%d %H:%M:%S");
+
         let mut sys = sysinfo::System::new_all();
         sys.refresh_all();
         let pid = std::process::id();
@@ -346,31 +855,316 @@ impl TomorinClient {
 OS - {os_version} - {arch}    
 Start time - {start_time}    
 Uptime - {uptime_fmt}    
+First start - {first_start}    
+Total uptime - {total_uptime_fmt}    
 Current time - {current_time}    
 Memory usage - {tomorin_mem_usage} KiB    
 Tomorin Version - {version}
 "
         );
-        m.edit(InputMessage::markdown(&status_text)).await?;
+        self.respond(m, Outgoing::markdown(&status_text)).await?;
         Ok(())
     }
 
-    pub async fn handle_repeat(&self, m: &Message) -> anyhow::Result<()> {
-        let reply_m = m.get_reply().await?;
-        if let Some(reply) = reply_m
-            && reply.forward_to(reply.chat()).await.is_err()
+    pub async fn handle_repeat(&self, m: &Message) -> Result<()> {
+        if let Some(reply) = self.get_reply(m).await?
+            && !self.repeat_album(&reply).await?
         {
-            let mut input_message = InputMessage::text(reply.text())
-                .fmt_entities(reply.fmt_entities().cloned().unwrap_or_default());
-            if let Some(ref media) = reply.media() {
-                input_message = input_message.copy_media(media);
+            self.pace(reply.chat().id(), "forward").await;
+            if reply.forward_to(reply.chat()).await.is_err() {
+                let mut input_message =
+                    InputMessage::from(copied(reply.text(), reply.fmt_entities()));
+                if let Some(ref media) = reply.media() {
+                    input_message = input_message.copy_media(media);
+                }
+                self.pace(reply.chat().id(), "send_message").await;
+                self.client
+                    .send_message(reply.chat(), input_message)
+                    .await?;
             }
-            self.client
-                .send_message(reply.chat(), input_message)
-                .await?;
         }
+        self.pace(m.chat().id(), "delete").await;
         m.delete().await?;
 
         Ok(())
     }
 }
+
+/// Lines of output shown in a message, longer output is truncated.
+const MAX_OUTPUT_LINES: usize = 30;
+
+/// Output edits wait out short flood waits, rather than dropping the output.
+const EDIT_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(30),
+    jitter: 0.0,
+};
+
+/// Refuse `code` longer than `max` chars before it makes a round trip to be evaluated.
+fn check_code_len(code: &str, max: usize) -> Result<(), String> {
+    let len = code.chars().count();
+    if len > max {
+        return Err(format!(
+            "笨！\nSnippet too long: {len} characters, at most {max} are evaluated"
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `program`, given by name or path, is one of the program names in `list`.
+fn matches_program(list: &[String], program: &str) -> bool {
+    let name = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program);
+    list.iter().any(|listed| listed == name)
+}
+
+/// Append `line` to `msg` unless that would make it exceed `cap` bytes, in which case
+/// only the part that fits is appended and `false` returned.
+fn push_capped(msg: &mut String, line: &str, cap: usize) -> bool {
+    let room = cap.saturating_sub(msg.len());
+    if line.len() < room {
+        msg.push_str(line);
+        msg.push('\n');
+        return true;
+    }
+
+    let mut end = room.min(line.len());
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    msg.push_str(&line[..end]);
+    false
+}
+
+/// Render evaluated `code` and its `resp` as two code blocks, formatted as `render` says.
+fn eval_msg(render: EvalRender, code: &str, resp: &str) -> Outgoing {
+    match render {
+        EvalRender::Pre => snippet_msg("Rust", code, resp),
+        EvalRender::Markdown => Outgoing::markdown(&format!(
+            "{}\n{}",
+            fenced("Rust", code.trim()),
+            fenced("Output", resp.trim())
+        ))
+        .full_output(resp.trim()),
+    }
+}
+
+/// `body` in a markdown code fence longer than any run of backticks in it, so the body
+/// can't close the fence early.
+fn fenced(language: &str, body: &str) -> String {
+    let longest = body
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}{language}\n{body}\n{fence}")
+}
+
+/// A `language` snippet followed by its output, each in a code block.
+///
+/// Entity offsets and lengths count UTF-16 code units, not chars.
+fn snippet_msg(language: &str, code: &str, resp: &str) -> Outgoing {
+    let code = code.trim();
+    let resp = resp.trim();
+    let code_entity = MessageEntity::Pre(MessageEntityPre {
+        offset: 0,
+        length: code.encode_utf16().count() as i32,
+        language: language.to_string(),
+    });
+
+    let resp = format!("\n{resp}");
+
+    let resp_entity = MessageEntity::Pre(MessageEntityPre {
+        offset: code_entity.length(),
+        length: resp.encode_utf16().count() as i32,
+        language: "Output".to_string(),
+    });
+
+    let text = format!("{code}{resp}");
+
+    Outgoing::text(text)
+        .fmt_entities(vec![code_entity, resp_entity])
+        .full_output(resp.trim_start())
+}
+
+/// A copy of a message's text and formatting, for sending it again.
+///
+/// The text is kept verbatim, so the entities' UTF-16 offsets stay valid as they are.
+fn copied(text: &str, entities: Option<&Vec<MessageEntity>>) -> Outgoing {
+    Outgoing::text(text).fmt_entities(entities.cloned().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::message::mock::MockMessage;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handle_help() {
+        let m = MockMessage::new("h#");
+        TomorinClient::handle_help(&m).await.unwrap();
+
+        let edit = m.last_edit().unwrap();
+        assert!(edit.text.starts_with("Available commands"));
+        assert!(edit.text.contains("r#<code>"));
+        assert!(!edit.entities.is_empty());
+    }
+
+    #[test]
+    fn test_check_code_len() {
+        assert_eq!(check_code_len("1 + 1", 5), Ok(()));
+        assert_eq!(check_code_len("一二三", 3), Ok(()));
+        assert_eq!(
+            check_code_len("1 + 1;", 5),
+            Err("笨！\nSnippet too long: 6 characters, at most 5 are evaluated".to_string())
+        );
+    }
+
+    #[test]
+    fn test_matches_program() {
+        let denylist = ["rm".to_string(), "mkfs".to_string()];
+        assert!(matches_program(&denylist, "rm"));
+        assert!(matches_program(&denylist, "/usr/bin/rm"));
+        assert!(matches_program(&denylist, "./mkfs"));
+        assert!(!matches_program(&denylist, "ls"));
+        assert!(!matches_program(&denylist, "rmdir"));
+        assert!(!matches_program(&[], "rm"));
+    }
+
+    #[test]
+    fn test_push_capped() {
+        let mut msg = String::new();
+        assert!(push_capped(&mut msg, "abc", 8));
+        assert!(push_capped(&mut msg, "de", 8));
+        assert_eq!(msg, "abc\nde\n");
+        assert!(!push_capped(&mut msg, "你好", 8));
+        assert_eq!(msg, "abc\nde\n");
+
+        let mut msg = String::new();
+        assert!(!push_capped(&mut msg, "abcdef", 4));
+        assert_eq!(msg, "abcd");
+    }
+
+    #[test]
+    fn test_snippet_msg_utf16() {
+        let msg = eval_msg(EvalRender::Pre, "\"👍\"", "👍 ok");
+        assert_eq!(msg.text, "\"👍\"\n👍 ok");
+        let [code, output] = msg.entities.as_slice() else {
+            panic!("expected two entities, got {:?}", msg.entities);
+        };
+        assert_eq!((code.offset(), code.length()), (0, 4));
+        assert_eq!((output.offset(), output.length()), (4, 6));
+        assert_eq!(
+            fence::utf16_slice(&msg.text, output.offset(), output.length()).as_deref(),
+            Some("\n👍 ok")
+        );
+    }
+
+    #[test]
+    fn test_eval_msg_markdown() {
+        // Backticks in the code or output don't end the fences early.
+        let (code, resp) = ("let s = \"```\";", "() ``");
+        assert_eq!(fenced("Rust", code), "````Rust\nlet s = \"```\";\n````");
+        let msg = eval_msg(EvalRender::Markdown, code, resp);
+        let pre = eval_msg(EvalRender::Pre, code, resp);
+        assert_eq!(msg.text, pre.text);
+        let languages: Vec<_> = msg
+            .entities
+            .iter()
+            .map(|entity| match entity {
+                MessageEntity::Pre(pre) => pre.language.as_str(),
+                _ => panic!("expected only code blocks, got {entity:?}"),
+            })
+            .collect();
+        assert_eq!(languages, ["Rust", "Output"]);
+    }
+
+    #[test]
+    fn test_copied_keeps_entity_offsets() {
+        use grammers_client::grammers_tl_types::types::MessageEntityBold;
+
+        let text = "🎉 party 🎉 bold";
+        let bold = MessageEntity::Bold(MessageEntityBold {
+            offset: 12,
+            length: 4,
+        });
+        let copy = copied(text, Some(&vec![bold.clone()]));
+        assert_eq!(copy.entities, vec![bold]);
+        assert_eq!(
+            fence::utf16_slice(&copy.text, 12, 4).as_deref(),
+            Some("bold")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edit_pre_msg() {
+        let m = MockMessage::new(",seq 5");
+        TomorinClient::edit_pre_msg(&m, "\n1\n2\n3\n\n", "StdOut")
+            .await
+            .unwrap();
+        let edit = m.last_edit().unwrap();
+        assert_eq!(edit.text, "1\n2\n3");
+        assert_eq!(
+            edit.entities,
+            vec![MessageEntity::Pre(MessageEntityPre {
+                offset: 0,
+                length: 5,
+                language: "StdOut".to_string(),
+            })]
+        );
+
+        let output = (1..=40)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        TomorinClient::edit_pre_msg(&m, &output, "StdOut")
+            .await
+            .unwrap();
+        let edit = m.last_edit().unwrap();
+        let lines = edit.text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "以上行数被杜叔叔吃掉了！");
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2], "11");
+        assert_eq!(lines.last(), Some(&"40"));
+        assert_eq!(edit.full_output.as_deref(), Some(output.as_str()));
+        assert_eq!(m.edits.lock().unwrap().len(), 2);
+
+        let m = MockMessage {
+            truncation: Truncation::Head,
+            ..MockMessage::new(",seq 40")
+        };
+        TomorinClient::edit_pre_msg(&m, &output, "StdOut")
+            .await
+            .unwrap();
+        let edit = m.last_edit().unwrap();
+        let lines = edit.text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "1");
+        assert_eq!(lines[29], "30");
+        assert_eq!(lines[30], "");
+        assert_eq!(lines.last(), Some(&"以下行数被杜叔叔吃掉了！"));
+
+        let m = MockMessage {
+            wrap_columns: Some(4),
+            ..MockMessage::new(",echo")
+        };
+        TomorinClient::edit_pre_msg(
+            &m,
+            "abcdefgh
+一二三",
+            "StdOut",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            m.last_edit().unwrap().text,
+            "abcd
+efgh
+一二
+三"
+        );
+    }
+}
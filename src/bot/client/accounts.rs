@@ -0,0 +1,160 @@
+use std::{path::Path, sync::Arc};
+
+use grammers_client::types::Message;
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// The account logged in on first start.
+pub const DEFAULT_ACCOUNT: &str = "tomorin";
+
+const SESSION_EXTENSION: &str = "session";
+
+/// The session file of `account`, relative to the working directory.
+pub fn session_file(account: &str) -> String {
+    format!("{account}.{SESSION_EXTENSION}")
+}
+
+/// Whether `name` can be used as an account name without escaping the working directory.
+fn is_account_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The accounts with a session file among `files`, sorted.
+fn account_names<'a>(files: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut names = files
+        .into_iter()
+        .filter_map(|file| file.strip_suffix(SESSION_EXTENSION)?.strip_suffix('.'))
+        .filter(|name| is_account_name(name))
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names
+}
+
+impl TomorinClient {
+    pub(super) fn session_file(&self) -> String {
+        session_file(&self.account)
+    }
+
+    pub(super) fn username(&self) -> String {
+        match self.me.username() {
+            Some(username) => format!("@{username}"),
+            None => self.me.full_name(),
+        }
+    }
+
+    pub async fn handle_account(&self, args: &str, m: &Message) -> Result<()> {
+        let (action, name) = args.split_once(' ').unwrap_or((args, ""));
+        match (action, name.trim()) {
+            ("", "") => {
+                self.respond(
+                    m,
+                    format!("Logged in as {} ({})", self.username(), self.account),
+                )
+                .await?;
+            }
+            ("list", "") => self.handle_account_list(m).await?,
+            ("switch", name) if is_account_name(name) => {
+                self.handle_account_switch(name, m).await?;
+            }
+            _ => {
+                self.respond(m, "Usage: account [list | switch <name>]")
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_account_list(&self, m: &Message) -> Result<()> {
+        let files = std::fs::read_dir(".")?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect::<Vec<_>>();
+        let list = account_names(files.iter().map(String::as_str))
+            .into_iter()
+            .map(|name| {
+                let marker = if name == &*self.account { '*' } else { ' ' };
+                format!("{marker} {name}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self::edit_pre_msg(&self.responder(m), &list, "Accounts").await
+    }
+
+    async fn handle_account_switch(&self, name: &str, m: &Message) -> Result<()> {
+        if name == &*self.account {
+            self.respond(m, format!("Already logged in as {}", self.username()))
+                .await?;
+            return Ok(());
+        }
+        let session = session_file(name);
+        if !Path::new(&session).exists() {
+            self.respond(
+                m,
+                format!("笨！\nNo session {session}, log in to it from the command line first"),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        self.respond(m, "少女祈祷中......").await?;
+        let switched = async {
+            let client = Self::connect(&self.conf, &session).await?;
+            if !client.is_authorized().await? {
+                anyhow::bail!("{session} is not logged in");
+            }
+            let me = client.get_me().await?;
+            self.client.session().save_to_file(self.session_file())?;
+            Ok(Self {
+                client,
+                me,
+                account: name.into(),
+                ..self.clone()
+            })
+        };
+        match switched.await {
+            Ok(client) => {
+                let username = client.username();
+                if self.switched.send(Arc::new(client)).is_err() {
+                    self.respond(m, "笨！\nThe update loop is gone").await?;
+                    return Ok(());
+                }
+                self.respond(m, format!("Switched to {username} ({name})"))
+                    .await?;
+            }
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_account_name() {
+        assert!(is_account_name("tomorin"));
+        assert!(is_account_name("alt_2-work"));
+        assert!(!is_account_name(""));
+        assert!(!is_account_name("../tomorin"));
+        assert!(!is_account_name("a b"));
+    }
+
+    #[test]
+    fn test_account_names() {
+        let files = [
+            "tomorin.session",
+            "Cargo.toml",
+            "alt.session",
+            ".session",
+            "tomorin.state",
+            "bad name.session",
+        ];
+        assert_eq!(account_names(files), vec!["alt", "tomorin"]);
+    }
+}
@@ -0,0 +1,65 @@
+use std::{future::Future, pin::pin};
+
+use grammers_client::{grammers_tl_types as tl, types::Message};
+
+use super::TomorinClient;
+
+/// What the bot is shown doing in a chat while a long operation runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ChatAction {
+    Typing,
+    UploadDocument,
+    UploadPhoto,
+}
+
+impl ChatAction {
+    fn to_tl(self) -> tl::enums::SendMessageAction {
+        match self {
+            ChatAction::Typing => tl::types::SendMessageTypingAction {}.into(),
+            // Most clients don't show the progress, only that something is uploading.
+            ChatAction::UploadDocument => {
+                tl::types::SendMessageUploadDocumentAction { progress: 0 }.into()
+            }
+            ChatAction::UploadPhoto => {
+                tl::types::SendMessageUploadPhotoAction { progress: 0 }.into()
+            }
+        }
+    }
+}
+
+impl TomorinClient {
+    /// Run `task`, showing `action` in the chat of `m` until it completes and clearing it
+    /// afterwards, unless `chat-actions` is off. Failing to set the action does not affect
+    /// `task`.
+    pub(super) async fn with_action<T>(
+        &self,
+        m: &Message,
+        action: ChatAction,
+        task: impl Future<Output = T>,
+    ) -> T {
+        if !self.conf.chat_actions {
+            return task.await;
+        }
+
+        let sender = self.client.action(m.chat().pack());
+        let (output, result) = sender
+            .repeat(
+                || {
+                    self.count_call("set_typing");
+                    action.to_tl()
+                },
+                pin!(task),
+            )
+            .await;
+        if let Err(e) = result {
+            tracing::debug!("Failed to show {action:?} in {}: {e}", m.chat().id());
+            return output;
+        }
+
+        self.count_call("set_typing");
+        if let Err(e) = sender.cancel().await {
+            tracing::debug!("Failed to clear {action:?} in {}: {e}", m.chat().id());
+        }
+        output
+    }
+}
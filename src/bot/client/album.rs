@@ -0,0 +1,80 @@
+use grammers_client::{InputMedia, types::Message};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Telegram albums hold at most this many items.
+const MAX_ALBUM_SIZE: i32 = 10;
+
+/// The ids around `id` that may belong to the same album, whose items have consecutive ids.
+fn album_window(id: i32) -> Vec<i32> {
+    ((id - MAX_ALBUM_SIZE + 1).max(1)..id + MAX_ALBUM_SIZE).collect()
+}
+
+impl TomorinClient {
+    /// The items of the album `m` belongs to, in order, or `None` if it isn't part of
+    /// an album with more than one item.
+    async fn album_of(&self, m: &Message) -> Result<Option<Vec<Message>>> {
+        let Some(group) = m.grouped_id() else {
+            return Ok(None);
+        };
+
+        self.count_call("get_messages_by_id");
+        let mut items = self
+            .client
+            .get_messages_by_id(m.chat(), &album_window(m.id()))
+            .await?
+            .into_iter()
+            .flatten()
+            .filter(|item| item.grouped_id() == Some(group))
+            .collect::<Vec<_>>();
+        items.sort_by_key(Message::id);
+        Ok((items.len() > 1).then_some(items))
+    }
+
+    /// Repeat the whole album `m` belongs to, forwarding it or else sending a copy.
+    ///
+    /// Returns `false` if `m` is not part of an album, so it should be repeated alone.
+    pub async fn repeat_album(&self, m: &Message) -> Result<bool> {
+        let Some(items) = self.album_of(m).await? else {
+            return Ok(false);
+        };
+
+        let ids = items.iter().map(Message::id).collect::<Vec<_>>();
+        self.pace(m.chat().id(), "forward").await;
+        if self
+            .client
+            .forward_messages(m.chat(), &ids, m.chat())
+            .await
+            .is_ok()
+        {
+            return Ok(true);
+        }
+
+        let medias = items
+            .iter()
+            .filter_map(|item| {
+                let media = item.media()?;
+                Some(
+                    InputMedia::caption(item.text())
+                        .fmt_entities(item.fmt_entities().cloned().unwrap_or_default())
+                        .copy_media(&media),
+                )
+            })
+            .collect::<Vec<_>>();
+        self.pace(m.chat().id(), "send_album").await;
+        self.client.send_album(m.chat(), medias).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_album_window() {
+        assert_eq!(album_window(100), (91..=109).collect::<Vec<_>>());
+        assert_eq!(album_window(3), (1..=12).collect::<Vec<_>>());
+    }
+}
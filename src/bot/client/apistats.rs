@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use grammers_client::{InvocationError, grammers_tl_types::RemoteCall, types::Message};
+use serde_json::{Value, json};
+
+use super::{TomorinClient, state::Inspect};
+use crate::error::Result;
+
+/// How often each kind of Telegram API call was made since start or the last reset.
+#[derive(Debug, Default)]
+pub struct ApiStats {
+    calls: HashMap<&'static str, u64>,
+}
+
+impl ApiStats {
+    pub fn record(&mut self, method: &'static str) {
+        *self.calls.entry(method).or_default() += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.calls.values().sum()
+    }
+
+    /// Per-method counts, most frequent first.
+    pub fn counts(&self) -> Vec<(&'static str, u64)> {
+        let mut counts = self.calls.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    pub fn reset(&mut self) {
+        self.calls.clear();
+    }
+}
+
+/// Shorten the type name of a TL function, like `grammers_tl_types::generated::functions::help::GetConfig`,
+/// to `help::GetConfig`.
+fn tl_name(type_name: &'static str) -> &'static str {
+    type_name
+        .split_once("functions::")
+        .map_or(type_name, |(_, name)| name)
+}
+
+impl TomorinClient {
+    pub fn count_call(&self, method: &'static str) {
+        self.api_stats.lock().unwrap().record(method);
+    }
+
+    /// [`grammers_client::Client::invoke`], counted by function name.
+    pub async fn invoke<R: RemoteCall>(&self, request: &R) -> Result<R::Return, InvocationError> {
+        self.count_call(tl_name(std::any::type_name::<R>()));
+        self.client.invoke(request).await
+    }
+
+    /// [`Message::get_reply`], counted.
+    pub async fn get_reply(&self, m: &Message) -> Result<Option<Message>, InvocationError> {
+        self.count_call("get_reply");
+        m.get_reply().await
+    }
+
+    pub async fn handle_apistats(&self, args: &str, m: &Message) -> Result<()> {
+        let reset = match args {
+            "" => false,
+            "reset" => true,
+            _ => {
+                self.respond(m, "Usage: apistats [reset]").await?;
+                return Ok(());
+            }
+        };
+
+        let report = {
+            let mut stats = self.api_stats.lock().unwrap();
+            let mut report = format!("{:<28} {}", "total", stats.total());
+            for (method, count) in stats.counts() {
+                report.push_str(&format!("\n{method:<28} {count}"));
+            }
+            if reset {
+                stats.reset();
+                report.push_str("\n(reset)");
+            }
+            report
+        };
+        Self::edit_pre_msg(&self.responder(m), &report, "API calls").await
+    }
+}
+
+impl Inspect for ApiStats {
+    fn size(&self) -> usize {
+        self.calls.len()
+    }
+
+    fn dump(&self) -> Value {
+        json!(self.calls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_stats() {
+        let mut stats = ApiStats::default();
+        stats.record("edit");
+        stats.record("send_message");
+        stats.record("edit");
+        assert_eq!(stats.total(), 3);
+        assert_eq!(stats.counts(), vec![("edit", 2), ("send_message", 1)]);
+
+        stats.reset();
+        assert_eq!(stats.total(), 0);
+        assert!(stats.counts().is_empty());
+    }
+
+    #[test]
+    fn test_tl_name() {
+        use grammers_client::grammers_tl_types as tl;
+
+        assert_eq!(
+            tl_name(std::any::type_name::<tl::functions::help::GetNearestDc>()),
+            "help::GetNearestDc"
+        );
+        assert_eq!(tl_name("Other"), "Other");
+    }
+}
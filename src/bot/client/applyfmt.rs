@@ -0,0 +1,162 @@
+use grammers_client::{grammers_tl_types::enums::MessageEntity, types::Message};
+
+use super::{TomorinClient, message::Outgoing};
+use crate::error::Result;
+
+fn set_range(entity: &mut MessageEntity, offset: i32, length: i32) {
+    macro_rules! set {
+        ($($variant:ident),*) => {
+            match entity {
+                $(MessageEntity::$variant(e) => {
+                    e.offset = offset;
+                    e.length = length;
+                })*
+            }
+        };
+    }
+    set!(
+        Unknown,
+        Mention,
+        Hashtag,
+        BotCommand,
+        Url,
+        Email,
+        Bold,
+        Italic,
+        Code,
+        Pre,
+        TextUrl,
+        MentionName,
+        InputMessageEntityMentionName,
+        Phone,
+        Cashtag,
+        Underline,
+        Strike,
+        BankCard,
+        Spoiler,
+        CustomEmoji,
+        Blockquote
+    );
+}
+
+/// Carry `entities` of a text `old_len` UTF-16 units long over to `text`: kept as they are
+/// if the lengths match, otherwise scaled to cover the same share of `text`. Entities
+/// shrunk to nothing are dropped, and none ends inside a surrogate pair.
+fn map_entities(old_len: i32, entities: &[MessageEntity], text: &str) -> Vec<MessageEntity> {
+    let new_len = text.encode_utf16().count() as i32;
+    if old_len == new_len {
+        return entities.to_vec();
+    }
+    if old_len == 0 {
+        return Vec::new();
+    }
+
+    let mut boundaries = vec![0];
+    boundaries.extend(text.chars().scan(0, |pos, c| {
+        *pos += c.len_utf16() as i32;
+        Some(*pos)
+    }));
+    let snap = |pos: i64| {
+        let pos = pos.clamp(0, i64::from(new_len)) as i32;
+        match boundaries.binary_search(&pos) {
+            Ok(_) => pos,
+            Err(i) => boundaries[i - 1],
+        }
+    };
+    let scale = |pos: i32| {
+        (i64::from(pos) * i64::from(new_len) + i64::from(old_len) / 2) / i64::from(old_len)
+    };
+
+    entities
+        .iter()
+        .filter_map(|entity| {
+            let start = snap(scale(entity.offset()));
+            let end = snap(scale(entity.offset() + entity.length()));
+            (end > start).then(|| {
+                let mut entity = entity.clone();
+                set_range(&mut entity, start, end - start);
+                entity
+            })
+        })
+        .collect()
+}
+
+impl TomorinClient {
+    /// Reply to a formatted message to send `text` with its formatting, or without a reply
+    /// render `text` as markdown, for checking how entities come out.
+    pub async fn handle_applyfmt(&self, text: &str, m: &Message) -> Result<()> {
+        if text.is_empty() {
+            self.respond(
+                m,
+                "Usage: applyfmt <text>, replying to a formatted message or as markdown",
+            )
+            .await?;
+            return Ok(());
+        }
+        let out = match self.get_reply(m).await? {
+            Some(reply) => {
+                let entities = reply.fmt_entities().map(Vec::as_slice).unwrap_or_default();
+                if entities.is_empty() {
+                    self.respond(m, "The message has no formatting to apply")
+                        .await?;
+                    return Ok(());
+                }
+                let old_len = reply.text().encode_utf16().count() as i32;
+                Outgoing::text(text).fmt_entities(map_entities(old_len, entities, text))
+            }
+            None => Outgoing::markdown(text),
+        };
+        self.respond(m, out).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grammers_client::grammers_tl_types::types::{
+        MessageEntityBold, MessageEntityItalic, MessageEntityTextUrl,
+    };
+
+    use super::*;
+
+    fn bold(offset: i32, length: i32) -> MessageEntity {
+        MessageEntity::Bold(MessageEntityBold { offset, length })
+    }
+
+    fn ranges(entities: &[MessageEntity]) -> Vec<(i32, i32)> {
+        entities.iter().map(|e| (e.offset(), e.length())).collect()
+    }
+
+    #[test]
+    fn test_map_entities() {
+        let url = MessageEntity::TextUrl(MessageEntityTextUrl {
+            offset: 5,
+            length: 5,
+            url: "https://example.com".into(),
+        });
+        let entities = [bold(0, 5), url];
+
+        // Same length, kept as is.
+        let mapped = map_entities(10, &entities, "abcdefghij");
+        assert_eq!(mapped, entities);
+
+        // Twice as long, scaled.
+        let mapped = map_entities(10, &entities, "abcdefghijklmnopqrst");
+        assert_eq!(ranges(&mapped), [(0, 10), (10, 10)]);
+        assert!(
+            matches!(&mapped[1], MessageEntity::TextUrl(url) if url.url == "https://example.com")
+        );
+
+        // Shrunk to nothing, dropped.
+        let italic = MessageEntity::Italic(MessageEntityItalic {
+            offset: 9,
+            length: 1,
+        });
+        assert_eq!(ranges(&map_entities(10, &[italic], "ab")), []);
+
+        // Not ending inside the surrogate pair of 👍.
+        assert_eq!(ranges(&map_entities(8, &[bold(0, 3)], "a👍b")), [(0, 1)]);
+        assert_eq!(ranges(&map_entities(2, &[bold(0, 1)], "👍ab")), [(0, 2)]);
+        assert_eq!(ranges(&map_entities(3, &[bold(0, 1)], "👍")), []);
+    }
+}
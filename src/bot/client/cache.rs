@@ -0,0 +1,24 @@
+use grammers_client::types::Message;
+
+use super::TomorinClient;
+use crate::error::Result;
+
+impl TomorinClient {
+    /// Flush the runtime caches, e.g. to force commands to be handled afresh.
+    pub async fn handle_cache(&self, args: &str, m: &Message) -> Result<()> {
+        if args != "clear" {
+            self.respond(m, "Usage: cache clear").await?;
+            return Ok(());
+        }
+
+        // Add new caches here so they are flushed together.
+        let cleared = [("handled", self.handled.lock().unwrap().clear())];
+
+        let report = cleared
+            .iter()
+            .map(|(name, count)| format!("{name:<10} {count} entries cleared"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self::edit_pre_msg(&self.responder(m), &report, "Cache").await
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use chrono_tz::Tz;
+use grammers_client::types::Message;
+
+use super::TomorinClient;
+use crate::error::Result;
+
+const TIME_FMT: &str = "%Y-%m-%d %H:%M:%S %Z";
+
+/// Describe `arg`, which is either empty, a unix timestamp in seconds or an IANA timezone name.
+fn describe_time(arg: &str, now: DateTime<Utc>) -> Result<String, String> {
+    let local = |dt: DateTime<Utc>| dt.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S %:z");
+
+    if arg.is_empty() {
+        return Ok(format!(
+            "Local - {}\nUTC - {}",
+            local(now),
+            now.format(TIME_FMT)
+        ));
+    }
+
+    if let Ok(ts) = arg.parse::<i64>() {
+        let Some(dt) = Utc.timestamp_opt(ts, 0).single() else {
+            return Err(format!("Timestamp out of range: {ts}"));
+        };
+        return Ok(format!(
+            "Local - {}\nUTC - {}",
+            local(dt),
+            dt.format(TIME_FMT)
+        ));
+    }
+
+    match arg.parse::<Tz>() {
+        Ok(tz) => Ok(format!(
+            "{tz} - {}",
+            now.with_timezone(&tz).format(TIME_FMT)
+        )),
+        Err(_) => Err(format!("Unknown timezone or timestamp: {arg}")),
+    }
+}
+
+impl TomorinClient {
+    pub async fn handle_time(&self, arg: &str, m: &Message) -> Result<()> {
+        let text = describe_time(arg, Utc::now()).unwrap_or_else(|e| format!("笨！\n{e}"));
+        self.respond(m, text).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_time() {
+        let now = Utc.timestamp_opt(0, 0).unwrap();
+
+        assert_eq!(
+            describe_time("Asia/Tokyo", now).unwrap(),
+            "Asia/Tokyo - 1970-01-01 09:00:00 JST"
+        );
+        assert!(
+            describe_time("1700000000", now)
+                .unwrap()
+                .ends_with("UTC - 2023-11-14 22:13:20 UTC")
+        );
+        assert!(describe_time("Mars/Olympus", now).is_err());
+        assert!(describe_time(&i64::MAX.to_string(), now).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::Cursor;
+
+use grammers_client::{
+    InputMessage,
+    types::{Downloadable, Media, Message},
+};
+
+use super::{TomorinClient, action::ChatAction};
+use crate::conf::{Conf, ReplyMode};
+use crate::error::Result;
+
+/// Larger uploads are certainly no config file.
+const MAX_IMPORT_BYTES: i64 = 256 * 1024;
+
+impl TomorinClient {
+    pub async fn handle_config(&self, args: &str, m: &Message) -> Result<()> {
+        match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["export"] => self.handle_config_export(true, m).await,
+            ["export", "full"] => self.handle_config_export(false, m).await,
+            ["import"] => self.handle_config_import(false, m).await,
+            ["import", "confirm"] => self.handle_config_import(true, m).await,
+            _ => {
+                self.respond(m, "Usage: config export [full] | config import [confirm]")
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Upload the config file as a document, with the secrets masked if `redact`.
+    async fn handle_config_export(&self, redact: bool, m: &Message) -> Result<()> {
+        let kdl = match tokio::fs::read_to_string(Conf::PATH).await {
+            Ok(kdl) => kdl,
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to read {}: {e}", Conf::PATH))
+                    .await?;
+                return Ok(());
+            }
+        };
+        let kdl = if redact { Conf::redact_kdl(&kdl) } else { kdl };
+
+        let len = kdl.len();
+        self.count_call("upload");
+        let uploaded = self
+            .with_action(
+                m,
+                ChatAction::UploadDocument,
+                self.client
+                    .upload_stream(&mut Cursor::new(kdl), len, Conf::PATH.to_string()),
+            )
+            .await?;
+
+        let caption = if redact {
+            "Config, secrets redacted"
+        } else {
+            "Config, including secrets"
+        };
+        let msg = InputMessage::text(caption)
+            .document(uploaded)
+            .silent(self.is_silent(m));
+        self.pace(m.chat().id(), "send_message").await;
+        match self.conf.reply_mode {
+            ReplyMode::Edit => m.respond(msg).await?,
+            ReplyMode::Reply => m.reply(msg).await?,
+        };
+        self.discard_response(m).await?;
+        Ok(())
+    }
+
+    /// Validate the KDL document replied to and, once `confirmed`, replace the config file
+    /// with it. The previous config is kept next to it as a backup.
+    async fn handle_config_import(&self, confirmed: bool, m: &Message) -> Result<()> {
+        let document = match self.get_reply(m).await?.and_then(|reply| reply.media()) {
+            Some(Media::Document(document)) => document,
+            _ => {
+                self.respond(m, "Reply to a KDL config file to import it")
+                    .await?;
+                return Ok(());
+            }
+        };
+        if document.size() > MAX_IMPORT_BYTES {
+            self.respond(m, "笨！\nToo large for a config file").await?;
+            return Ok(());
+        }
+
+        let mut bytes = Vec::new();
+        let mut download = self
+            .client
+            .iter_download(&Downloadable::Media(Media::Document(document)));
+        self.count_call("download");
+        while let Some(chunk) = download.next().await? {
+            bytes.extend(chunk);
+        }
+        let Ok(kdl) = String::from_utf8(bytes) else {
+            self.respond(m, "笨！\nNot a text file").await?;
+            return Ok(());
+        };
+        if let Err(e) = Conf::validate(&kdl) {
+            self.respond(m, format!("笨！\nInvalid config: {e}"))
+                .await?;
+            return Ok(());
+        }
+
+        if !confirmed {
+            self.respond(
+                m,
+                "The config is valid, reply with `,config import confirm` to replace the current one",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let backup = format!("{}.bak", Conf::PATH);
+        if let Err(e) = tokio::fs::copy(Conf::PATH, &backup).await {
+            tracing::warn!("Failed to back up {} to {backup}: {e}", Conf::PATH);
+        }
+        tokio::fs::write(Conf::PATH, kdl).await?;
+        self.respond(
+            m,
+            format!("Config written, the old one is kept as {backup}. Restart to apply it"),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use grammers_client::types::Message;
+use serde_json::{Map, Value, json};
+
+use super::{TomorinClient, state::Inspect};
+use crate::error::Result;
+
+/// How long a `,yes` or `,no` is awaited for a command asking for confirmation.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Pending<M> {
+    /// The id of the message holding the command.
+    id: i32,
+    /// The command as it was sent, before the confirmation prompt replaced it.
+    text: String,
+    msg: M,
+    expires: Instant,
+    approved: bool,
+}
+
+/// The command message awaiting confirmation in each chat. Asking again replaces it.
+#[derive(Debug)]
+pub struct Confirms<M = Message> {
+    pending: HashMap<i64, Pending<M>>,
+}
+
+impl<M> Default for Confirms<M> {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl<M: Clone> Confirms<M> {
+    fn ask(&mut self, chat: i64, id: i32, text: &str, msg: M, now: Instant) {
+        self.pending.retain(|_, pending| pending.expires > now);
+        self.pending.insert(
+            chat,
+            Pending {
+                id,
+                text: text.to_string(),
+                msg,
+                expires: now + CONFIRM_TIMEOUT,
+                approved: false,
+            },
+        );
+    }
+
+    /// Approve the command pending in `chat`, returning its text and message to handle
+    /// it again.
+    fn approve(&mut self, chat: i64, now: Instant) -> Option<(String, M)> {
+        let pending = self
+            .pending
+            .get_mut(&chat)
+            .filter(|pending| pending.expires > now && !pending.approved)?;
+        pending.approved = true;
+        Some((pending.text.clone(), pending.msg.clone()))
+    }
+
+    /// Drop the command pending in `chat`, returning it unless it had expired.
+    fn cancel(&mut self, chat: i64, now: Instant) -> Option<String> {
+        self.pending
+            .remove(&chat)
+            .filter(|pending| pending.expires > now && !pending.approved)
+            .map(|pending| pending.text)
+    }
+
+    /// Whether the command in message `id` was approved in `chat`. The approval holds
+    /// for every program it spawns until it is `finish`ed.
+    fn is_approved(&self, chat: i64, id: i32) -> bool {
+        self.pending
+            .get(&chat)
+            .is_some_and(|pending| pending.approved && pending.id == id)
+    }
+
+    /// Drop the approval of the command in message `id` once it was handled again.
+    fn finish(&mut self, chat: i64, id: i32) {
+        if self.is_approved(chat, id) {
+            self.pending.remove(&chat);
+        }
+    }
+}
+
+impl TomorinClient {
+    /// Whether the command in `m` may spawn a program on `confirm-commands`: it was just
+    /// approved by `,yes`. Otherwise asks for confirmation and returns `false`, the handler
+    /// should stop.
+    pub(super) async fn confirmed(&self, m: &Message) -> Result<bool> {
+        let chat = m.chat().id();
+        let text = m.text().to_string();
+        {
+            let mut confirms = self.confirms.lock().unwrap();
+            if confirms.is_approved(chat, m.id()) {
+                return Ok(true);
+            }
+            confirms.ask(chat, m.id(), &text, m.clone(), Instant::now());
+        }
+
+        self.respond(
+            m,
+            format!(
+                "Really run `{text}`? Send ,yes within {} to confirm or ,no to cancel",
+                humantime::format_duration(CONFIRM_TIMEOUT)
+            ),
+        )
+        .await?;
+        Ok(false)
+    }
+
+    /// `,yes` handles the command awaiting confirmation in this chat again, in its own
+    /// message, `,no` cancels it.
+    pub async fn handle_confirm(&self, yes: bool, m: &Message) -> Result<()> {
+        let chat = m.chat().id();
+        let now = Instant::now();
+        if !yes {
+            let cancelled = self.confirms.lock().unwrap().cancel(chat, now);
+            let resp = match cancelled {
+                Some(text) => format!("Cancelled `{text}`"),
+                None => "Nothing to confirm".to_string(),
+            };
+            self.respond(m, resp).await?;
+            return Ok(());
+        }
+
+        let approved = self.confirms.lock().unwrap().approve(chat, now);
+        let Some((text, cmd_msg)) = approved else {
+            self.respond(m, "Nothing to confirm").await?;
+            return Ok(());
+        };
+        let _owned = self.own(&cmd_msg);
+        let routed = self.route(&text, chat);
+        let result = match self.refuse_while_draining(&routed, &cmd_msg).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Box::pin(self.dispatch(routed, &cmd_msg)).await,
+            Err(e) => Err(e),
+        };
+        self.confirms.lock().unwrap().finish(chat, cmd_msg.id());
+        result
+    }
+}
+
+impl Inspect for Confirms {
+    fn size(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn dump(&self) -> Value {
+        let now = Instant::now();
+        self.pending
+            .iter()
+            .map(|(chat, pending)| {
+                let value = json!({
+                    "message_id": pending.id,
+                    "cmd": pending.text,
+                    "approved": pending.approved,
+                    "expires_in_secs": pending.expires.saturating_duration_since(now).as_secs(),
+                });
+                (chat.to_string(), value)
+            })
+            .collect::<Map<_, _>>()
+            .into()
+    }
+
+    fn clear(&mut self) -> bool {
+        self.pending.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirms() {
+        let now = Instant::now();
+        let mut confirms = Confirms::<()>::default();
+        assert_eq!(confirms.approve(1, now), None);
+
+        confirms.ask(1, 10, ",leave", (), now);
+        assert!(!confirms.is_approved(1, 10));
+        assert_eq!(confirms.approve(1, now), Some((",leave".into(), ())));
+        // Approved once, for that message only, until it is finished.
+        assert_eq!(confirms.approve(1, now), None);
+        assert!(!confirms.is_approved(1, 11));
+        assert!(!confirms.is_approved(2, 10));
+        assert!(confirms.is_approved(1, 10));
+        assert!(confirms.is_approved(1, 10));
+        confirms.finish(1, 10);
+        assert!(!confirms.is_approved(1, 10));
+
+        confirms.ask(1, 12, "sh#rm x", (), now);
+        assert_eq!(confirms.cancel(1, now), Some("sh#rm x".into()));
+        assert_eq!(confirms.approve(1, now), None);
+
+        confirms.ask(1, 13, ",rm x", (), now);
+        let later = now + CONFIRM_TIMEOUT;
+        assert_eq!(confirms.approve(1, later), None);
+        assert_eq!(confirms.cancel(1, later), None);
+    }
+}
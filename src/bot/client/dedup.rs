@@ -0,0 +1,151 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use serde_json::{Map, Value};
+
+use super::state::{Inspect, key_name};
+
+/// Identifies a message across chats, as message ids are only unique per chat.
+pub type MessageKey = (i64, i32);
+
+/// Remembers the last text handled for recent messages, so that `MessageEdited`
+/// updates which leave the text untouched are not handled twice.
+#[derive(Debug, Default)]
+pub struct HandledCache {
+    order: VecDeque<MessageKey>,
+    texts: HashMap<MessageKey, String>,
+}
+
+impl HandledCache {
+    const CAPACITY: usize = 256;
+
+    /// Record `text` as handled for `key`.
+    ///
+    /// Returns `false` if exactly this text was already handled for the message.
+    pub fn insert(&mut self, key: MessageKey, text: &str) -> bool {
+        if let Some(last) = self.texts.get_mut(&key) {
+            if last == text {
+                return false;
+            }
+            *last = text.to_string();
+            return true;
+        }
+
+        if self.order.len() >= Self::CAPACITY
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.texts.remove(&oldest);
+        }
+        self.order.push_back(key);
+        self.texts.insert(key, text.to_string());
+        true
+    }
+
+    /// Forget every handled message, returning how many there were.
+    pub fn clear(&mut self) -> usize {
+        let len = self.order.len();
+        self.order.clear();
+        self.texts.clear();
+        len
+    }
+}
+
+/// Command messages whose handler is running and rendering output into them.
+///
+/// Edits to these are the bot's own doing and must never be handled as commands.
+#[derive(Debug, Default)]
+pub struct OwnedMessages {
+    keys: HashSet<MessageKey>,
+}
+
+impl OwnedMessages {
+    pub fn contains(&self, key: &MessageKey) -> bool {
+        self.keys.contains(key)
+    }
+}
+
+/// Keeps a message in [`OwnedMessages`] until dropped, once its handler returned.
+#[must_use]
+pub struct Owned {
+    owned: Arc<Mutex<OwnedMessages>>,
+    key: MessageKey,
+}
+
+impl Owned {
+    pub fn new(owned: &Arc<Mutex<OwnedMessages>>, key: MessageKey) -> Self {
+        owned.lock().unwrap().keys.insert(key);
+        Self {
+            owned: owned.clone(),
+            key,
+        }
+    }
+}
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        // Also dropped while a panicking handler unwinds, which must not panic again.
+        let mut owned = self.owned.lock().unwrap_or_else(PoisonError::into_inner);
+        owned.keys.remove(&self.key);
+    }
+}
+
+impl Inspect for HandledCache {
+    fn size(&self) -> usize {
+        self.texts.len()
+    }
+
+    fn dump(&self) -> Value {
+        self.order
+            .iter()
+            .filter_map(|key| Some((key_name(*key), self.texts.get(key)?.as_str().into())))
+            .collect::<Map<_, _>>()
+            .into()
+    }
+}
+
+impl Inspect for OwnedMessages {
+    fn size(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn dump(&self) -> Value {
+        self.keys.iter().copied().map(key_name).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handled_cache() {
+        let mut cache = HandledCache::default();
+        assert!(cache.insert((1, 1), ",ls"));
+        assert!(!cache.insert((1, 1), ",ls"));
+        assert!(cache.insert((1, 1), ",ls -l"));
+        assert!(cache.insert((2, 1), ",ls -l"));
+
+        for id in 0..HandledCache::CAPACITY as i32 {
+            cache.insert((3, id), "");
+        }
+        assert!(cache.insert((1, 1), ",ls -l"));
+        assert_eq!(cache.texts.len(), HandledCache::CAPACITY);
+
+        assert_eq!(cache.clear(), HandledCache::CAPACITY);
+        assert!(cache.insert((1, 1), ",ls -l"));
+    }
+
+    #[test]
+    fn test_owned_messages() {
+        let owned = Arc::new(Mutex::new(OwnedMessages::default()));
+        let guard = Owned::new(&owned, (1, 1));
+        assert!(owned.lock().unwrap().contains(&(1, 1)));
+        assert!(!owned.lock().unwrap().contains(&(2, 1)));
+
+        // Released once the handler returned, so editing the command runs it again.
+        drop(guard);
+        assert!(!owned.lock().unwrap().contains(&(1, 1)));
+    }
+}
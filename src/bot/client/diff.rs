@@ -0,0 +1,78 @@
+use similar::TextDiff;
+
+use super::{TomorinClient, message::MessageOps};
+use crate::error::Result;
+
+/// Render a unified line diff of `old` against `new`, or `None` if they are equal.
+fn render_diff(old: &str, new: &str) -> Option<String> {
+    let diff = TextDiff::from_lines(old, new);
+    let out = diff
+        .unified_diff()
+        .context_radius(3)
+        .missing_newline_hint(false)
+        .to_string();
+
+    (!out.is_empty()).then_some(out)
+}
+
+impl TomorinClient {
+    /// Diff the replied message against either the text given after the command,
+    /// or against the message it replies to in turn.
+    pub async fn handle_diff(args: &str, m: &impl MessageOps) -> Result<()> {
+        let Some(reply) = m.get_reply().await? else {
+            m.edit("Reply to a message to diff it").await?;
+            return Ok(());
+        };
+
+        let (old, new) = if !args.is_empty() {
+            (reply.text().to_string(), args.to_string())
+        } else if let Some(original) = reply.get_reply().await? {
+            (original.text().to_string(), reply.text().to_string())
+        } else {
+            // Telegram does not expose the edit history of a message to clients.
+            m.edit("Nothing to diff against: give a text or reply to a message which is itself a reply")
+                .await?;
+            return Ok(());
+        };
+
+        match render_diff(&old, &new) {
+            Some(diff) => Self::edit_pre_msg(m, &diff, "diff").await,
+            None => {
+                m.edit("No differences").await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::message::mock::MockMessage;
+    use super::*;
+
+    #[test]
+    fn test_render_diff() {
+        assert_eq!(render_diff("a\nb\n", "a\nb\n"), None);
+        assert_eq!(
+            render_diff("a\nb\nc", "a\nB\nc").as_deref(),
+            Some("@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_diff() {
+        let m = MockMessage::new(",diff");
+        TomorinClient::handle_diff("", &m).await.unwrap();
+        assert_eq!(m.last_edit().unwrap().text, "Reply to a message to diff it");
+
+        let m = MockMessage {
+            reply: Some(Box::new(MockMessage {
+                reply: Some(Box::new(MockMessage::new("a\nb"))),
+                ..MockMessage::new("a\nc")
+            })),
+            ..MockMessage::new(",diff")
+        };
+        TomorinClient::handle_diff("", &m).await.unwrap();
+        assert_eq!(m.last_edit().unwrap().text, "@@ -1,2 +1,2 @@\n a\n-b\n+c");
+    }
+}
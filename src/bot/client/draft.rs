@@ -0,0 +1,28 @@
+use grammers_client::{grammers_tl_types as tl, types::Message};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+impl TomorinClient {
+    /// Clear the draft saved in the current chat, by saving an empty one.
+    pub async fn handle_cleardraft(&self, m: &Message) -> Result<()> {
+        let request = tl::functions::messages::SaveDraft {
+            no_webpage: false,
+            invert_media: false,
+            reply_to: None,
+            peer: m.chat().pack().to_input_peer(),
+            message: String::new(),
+            entities: None,
+            media: None,
+            effect: None,
+        };
+        match self.invoke(&request).await {
+            Ok(_) => self.respond(m, "Draft cleared").await?,
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to clear the draft: {e}"))
+                    .await?
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::atomic::Ordering;
+
+use grammers_client::types::Message;
+use tokio::task;
+
+use super::{TomorinClient, route::RoutedCommand};
+use crate::error::Result;
+
+/// Asks the update loop to report on `message` once every handler running has finished.
+#[derive(Debug)]
+pub struct DrainRequest {
+    pub message: Message,
+    /// The handler asking, which is not waited for.
+    pub task: Option<task::Id>,
+}
+
+/// Whether `routed` is still handled while draining: only what ends or reports on the
+/// drain, or stops or settles work already started, so no new work starts before a
+/// restart. A command approved by `,yes` is checked again on its own.
+fn allowed_while_draining(routed: &RoutedCommand) -> bool {
+    match routed {
+        RoutedCommand::Cmd(cmd) => {
+            let mut words = cmd.split_whitespace();
+            matches!(
+                (words.next(), words.next(), words.next()),
+                (Some("drain" | "resume" | "yes" | "no"), _, _)
+                    | (Some("watch"), Some("stop"), None)
+            )
+        }
+        RoutedCommand::Help | RoutedCommand::Status | RoutedCommand::None => true,
+        _ => false,
+    }
+}
+
+impl TomorinClient {
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Refuse `routed` if it may not run while draining, returning whether it was refused.
+    pub(super) async fn refuse_while_draining(
+        &self,
+        routed: &RoutedCommand<'_>,
+        m: &Message,
+    ) -> Result<bool> {
+        if !self.is_draining() || allowed_while_draining(routed) {
+            return Ok(false);
+        }
+        self.respond(m, "Draining, no new commands until `,resume`")
+            .await?;
+        Ok(true)
+    }
+
+    /// Stop accepting commands and report once the handlers still running have finished.
+    pub async fn handle_drain(&self, m: &Message) -> Result<()> {
+        if self.draining.swap(true, Ordering::Relaxed) {
+            self.respond(m, "Already draining, `,resume` to accept commands again")
+                .await?;
+            return Ok(());
+        }
+        let request = DrainRequest {
+            message: m.clone(),
+            task: task::try_id(),
+        };
+        if self.drains.send(request).is_err() {
+            self.draining.store(false, Ordering::Relaxed);
+            self.respond(m, "笨！\nThe update loop is gone").await?;
+            return Ok(());
+        }
+        // Watches and log follows would otherwise keep the drain waiting until stopped by
+        // hand or timed out, log follows notice the drain on their own.
+        let stopped = self.watches.lock().unwrap().stop_all();
+        let watches = if stopped > 0 {
+            format!(", stopped {stopped} watch(es)")
+        } else {
+            String::new()
+        };
+        self.respond(
+            m,
+            format!("Draining{watches}, waiting for the running handlers to finish......"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Called by the update loop once draining finished, after `finished` other handlers.
+    pub async fn report_drained(&self, m: &Message, finished: usize) {
+        // Resumed in the meantime, nothing to report.
+        if !self.is_draining() {
+            return;
+        }
+        let text = format!(
+            "Drained, {finished} running handlers finished\nSafe to restart, or `,resume` to accept commands again"
+        );
+        if let Err(e) = self.respond(m, text).await {
+            tracing::warn!("Failed to report the drain: {e}");
+        }
+    }
+
+    /// Accept commands again after `,drain`.
+    pub async fn handle_resume(&self, m: &Message) -> Result<()> {
+        let text = if self.draining.swap(false, Ordering::Relaxed) {
+            "Accepting commands again"
+        } else {
+            "Not draining"
+        };
+        self.respond(m, text).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_while_draining() {
+        assert!(allowed_while_draining(&RoutedCommand::Cmd("resume")));
+        assert!(allowed_while_draining(&RoutedCommand::Cmd(" drain")));
+        assert!(allowed_while_draining(&RoutedCommand::Status));
+        assert!(!allowed_while_draining(&RoutedCommand::Cmd("ls -l")));
+        assert!(!allowed_while_draining(&RoutedCommand::Cmd("resumes")));
+        assert!(allowed_while_draining(&RoutedCommand::Cmd("yes")));
+        assert!(allowed_while_draining(&RoutedCommand::Cmd("no")));
+        assert!(allowed_while_draining(&RoutedCommand::Cmd("watch stop")));
+        assert!(!allowed_while_draining(&RoutedCommand::Cmd("watch 5 ls")));
+        assert!(!allowed_while_draining(&RoutedCommand::Cmd(
+            "watch stop now"
+        )));
+        assert!(!allowed_while_draining(&RoutedCommand::Eval("1 + 1")));
+    }
+}
@@ -0,0 +1,91 @@
+use grammers_client::{grammers_tl_types::enums::MessageEntity, types::Message};
+use serde_json::{Value, json};
+
+use super::{TomorinClient, fence::utf16_slice};
+use crate::error::Result;
+
+/// Describe `entities` of `text` as JSON, with the text each one covers.
+fn entities_json(text: &str, entities: &[MessageEntity]) -> Value {
+    entities
+        .iter()
+        .map(|entity| {
+            let debug = format!("{entity:?}");
+            let kind = debug.split('(').next().unwrap_or_default();
+            let mut json = json!({
+                "type": kind,
+                "offset": entity.offset(),
+                "length": entity.length(),
+                "text": utf16_slice(text, entity.offset(), entity.length()),
+            });
+            let extra = match entity {
+                MessageEntity::Pre(pre) => Some(("language", json!(pre.language))),
+                MessageEntity::TextUrl(url) => Some(("url", json!(url.url))),
+                MessageEntity::MentionName(mention) => Some(("user_id", json!(mention.user_id))),
+                MessageEntity::CustomEmoji(emoji) => {
+                    Some(("document_id", json!(emoji.document_id)))
+                }
+                MessageEntity::Blockquote(quote) => Some(("collapsed", json!(quote.collapsed))),
+                _ => None,
+            };
+            if let Some((key, value)) = extra {
+                json[key] = value;
+            }
+            json
+        })
+        .collect()
+}
+
+impl TomorinClient {
+    pub async fn handle_entities(&self, m: &Message) -> Result<()> {
+        let Some(reply) = self.get_reply(m).await? else {
+            self.respond(m, "Reply to a message to show its entities")
+                .await?;
+            return Ok(());
+        };
+        let entities = reply.fmt_entities().map(Vec::as_slice).unwrap_or_default();
+        if entities.is_empty() {
+            self.respond(m, "The message has no entities").await?;
+            return Ok(());
+        }
+
+        let json = serde_json::to_string_pretty(&entities_json(reply.text(), entities))
+            .map_err(anyhow::Error::from)?;
+        Self::edit_pre_msg(&self.responder(m), &json, "JSON").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grammers_client::grammers_tl_types::types::{MessageEntityBold, MessageEntityTextUrl};
+
+    use super::*;
+
+    #[test]
+    fn test_entities_json() {
+        let text = "👍 bold link";
+        let entities = [
+            MessageEntity::Bold(MessageEntityBold {
+                offset: 3,
+                length: 4,
+            }),
+            MessageEntity::TextUrl(MessageEntityTextUrl {
+                offset: 8,
+                length: 4,
+                url: "https://example.com".to_string(),
+            }),
+        ];
+        assert_eq!(
+            entities_json(text, &entities),
+            json!([
+                {"type": "Bold", "offset": 3, "length": 4, "text": "bold"},
+                {
+                    "type": "TextUrl",
+                    "offset": 8,
+                    "length": 4,
+                    "text": "link",
+                    "url": "https://example.com",
+                },
+            ])
+        );
+    }
+}
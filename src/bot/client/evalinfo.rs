@@ -0,0 +1,90 @@
+use grammers_client::types::Message;
+
+use super::TomorinClient;
+use crate::{
+    error::{BotError, Result},
+    eval::{CrateVersion, EDITIONS, EvalClient, EvalInfo},
+};
+
+fn render_info(info: &EvalInfo) -> String {
+    let mut text = format!("editions  {}", EDITIONS.join(", "));
+    for (toolchain, version) in &info.toolchains {
+        text.push_str(&format!("\n{toolchain:<9} {version}"));
+    }
+    if info.crates.is_empty() {
+        text.push_str("\ncrates    std only");
+    } else {
+        let crates: Vec<_> = info
+            .crates
+            .iter()
+            .map(|CrateVersion { name, version }| format!("{name} {version}"))
+            .collect();
+        text.push_str(&format!(
+            "\ncrates    {}\n{}",
+            crates.len(),
+            crates.join(", ")
+        ));
+    }
+    text
+}
+
+impl TomorinClient {
+    /// Show the editions, toolchains and crates snippets can use.
+    pub async fn handle_evalinfo(&self, m: &Message) -> Result<()> {
+        self.respond(m, "少女祈祷中......").await?;
+        match EvalClient::intance().info().await {
+            Ok(info) => {
+                Self::edit_pre_msg(&self.responder(m), &render_info(&info), "EvalInfo").await
+            }
+            Err(
+                e @ (BotError::Http(_)
+                | BotError::HttpTimeout
+                | BotError::HttpRateLimited
+                | BotError::Other(_)),
+            ) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_info() {
+        let info = EvalInfo {
+            toolchains: vec![
+                ("stable", "1.90.0".into()),
+                ("nightly", "1.92.0-nightly".into()),
+            ],
+            crates: vec![
+                CrateVersion {
+                    name: "rand".into(),
+                    version: "0.9.2".into(),
+                },
+                CrateVersion {
+                    name: "serde".into(),
+                    version: "1.0.228".into(),
+                },
+            ],
+        };
+        assert_eq!(
+            render_info(&info),
+            "editions  2015, 2018, 2021, 2024
+stable    1.90.0
+nightly   1.92.0-nightly
+crates    2
+rand 0.9.2, serde 1.0.228"
+        );
+
+        let local = EvalInfo {
+            toolchains: vec![("local", "rustc 1.90.0".into())],
+            crates: Vec::new(),
+        };
+        assert!(render_info(&local).ends_with("local     rustc 1.90.0\ncrates    std only"));
+    }
+}
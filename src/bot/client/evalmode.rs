@@ -0,0 +1,187 @@
+use grammers_client::{
+    grammers_tl_types::{enums::MessageEntity, types::MessageEntityPre},
+    types::Message,
+};
+
+use super::{TomorinClient, eval_msg, message::Outgoing};
+use crate::error::{BotError, Result};
+
+/// What to do with the code of an `r#` message, selected by a `<mode>#` prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvalMode<'a> {
+    /// Run the code on the playground.
+    Run,
+    /// Only show the source that would be sent to the playground.
+    Show,
+    /// Compile the code without running it.
+    Check,
+    /// Run the code and also show the first compiler warnings.
+    Warn,
+    /// Run the code and estimate how long compiling and running took.
+    Timing,
+    /// Run the code the given number of times and report timings.
+    Bench(u32),
+    /// Run the code and send the result to the chat with this username.
+    SendTo(&'a str),
+}
+
+/// Upper bound for `bench:N#`, so a run stays well within the playground's time limit.
+const MAX_BENCH_ITERATIONS: u32 = 100_000;
+
+/// Split the optional mode prefix off the code of an `r#` message.
+pub fn split_eval_mode(code: &str) -> Result<(EvalMode<'_>, &str), String> {
+    if let Some(code) = code.strip_prefix("show#") {
+        return Ok((EvalMode::Show, code));
+    }
+
+    if let Some(code) = code.strip_prefix("check#") {
+        return Ok((EvalMode::Check, code));
+    }
+
+    if let Some(code) = code.strip_prefix("warn#") {
+        return Ok((EvalMode::Warn, code));
+    }
+
+    if let Some(code) = code.strip_prefix("time#") {
+        return Ok((EvalMode::Timing, code));
+    }
+
+    if let Some(rest) = code.strip_prefix("bench:") {
+        let Some((iterations, code)) = rest.split_once('#') else {
+            return Err("Usage: r#bench:<N>#<code>".to_string());
+        };
+        return match iterations.trim().parse() {
+            Ok(n @ 1..=MAX_BENCH_ITERATIONS) => Ok((EvalMode::Bench(n), code)),
+            _ => Err(format!(
+                "Iterations must be between 1 and {MAX_BENCH_ITERATIONS}"
+            )),
+        };
+    }
+
+    if let Some(rest) = code.strip_prefix(">@") {
+        return match rest.split_once('#') {
+            Some((target, code)) if is_username(target) => Ok((EvalMode::SendTo(target), code)),
+            _ => Err("Usage: r#>@<username>#<code>".to_string()),
+        };
+    }
+
+    Ok((EvalMode::Run, code))
+}
+
+/// Whether `name` could be a Telegram username, which are 4 to 32 letters, digits or underscores.
+fn is_username(name: &str) -> bool {
+    (4..=32).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl TomorinClient {
+    /// Show the fully wrapped source `code` would be evaluated as, without running it.
+    pub async fn handle_eval_show(&self, code: &str, m: &Message) -> Result<()> {
+        use crate::eval::EvalClient;
+
+        let source = EvalClient::intance().generate_code(code);
+        let source = source.trim();
+        let entity = MessageEntity::Pre(MessageEntityPre {
+            offset: 0,
+            length: source.encode_utf16().count() as i32,
+            language: "Rust".to_string(),
+        });
+        self.respond(m, Outgoing::text(source).fmt_entities(vec![entity]))
+            .await?;
+        Ok(())
+    }
+
+    /// Evaluate `code` and send the result to `@target`, leaving a confirmation behind.
+    pub async fn handle_eval_send(&self, target: &str, code: &str, m: &Message) -> Result<()> {
+        use crate::eval::EvalClient;
+
+        self.respond(m, "少女祈祷中......").await?;
+        self.count_call("resolve_username");
+        let chat = match self.client.resolve_username(target).await {
+            Ok(Some(chat)) => chat,
+            Ok(None) => {
+                self.respond(m, format!("笨！\nNo chat named @{target}"))
+                    .await?;
+                return Ok(());
+            }
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to resolve @{target}: {e}"))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        // Built the way this chat evals, wherever the result goes.
+        let settings = self.eval_settings(m.chat().id());
+        let resp = match EvalClient::intance().eval(code, settings).await {
+            Ok(resp) => resp,
+            Err(e @ (BotError::Http(_) | BotError::HttpTimeout | BotError::HttpRateLimited)) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.pace(chat.id(), "send_message").await;
+        match self
+            .client
+            .send_message(&chat, eval_msg(self.conf.eval_render, code, &resp))
+            .await
+        {
+            Ok(_) => self.respond(m, format!("Sent to @{target}")).await?,
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to send to @{target}: {e}"))
+                    .await?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_eval_mode() {
+        assert_eq!(split_eval_mode("1 + 1"), Ok((EvalMode::Run, "1 + 1")));
+        assert_eq!(split_eval_mode("show#1 + 1"), Ok((EvalMode::Show, "1 + 1")));
+        assert_eq!(split_eval_mode("show#"), Ok((EvalMode::Show, "")));
+        assert_eq!(
+            split_eval_mode("check#1 + 1"),
+            Ok((EvalMode::Check, "1 + 1"))
+        );
+        assert_eq!(
+            split_eval_mode("time#1 + 1"),
+            Ok((EvalMode::Timing, "1 + 1"))
+        );
+        assert_eq!(split_eval_mode(" show#1"), Ok((EvalMode::Run, " show#1")));
+    }
+
+    #[test]
+    fn test_split_eval_mode_bench() {
+        assert_eq!(
+            split_eval_mode("bench:100#v.sort()"),
+            Ok((EvalMode::Bench(100), "v.sort()"))
+        );
+        assert_eq!(
+            split_eval_mode("bench:1#x#y"),
+            Ok((EvalMode::Bench(1), "x#y"))
+        );
+        assert!(split_eval_mode("bench:0#1").is_err());
+        assert!(split_eval_mode("bench:100001#1").is_err());
+        assert!(split_eval_mode("bench:-1#1").is_err());
+        assert!(split_eval_mode("bench:lots#1").is_err());
+        assert!(split_eval_mode("bench:10").is_err());
+    }
+
+    #[test]
+    fn test_split_eval_mode_send_to() {
+        assert_eq!(
+            split_eval_mode(">@rust_group#1 + 1"),
+            Ok((EvalMode::SendTo("rust_group"), "1 + 1"))
+        );
+        assert!(split_eval_mode(">@#1").is_err());
+        assert!(split_eval_mode(">@bad name#1").is_err());
+        assert!(split_eval_mode(">@no_code").is_err());
+    }
+}
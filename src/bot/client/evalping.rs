@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use grammers_client::types::Message;
+
+use super::TomorinClient;
+use crate::{conf::EvalBackend, error::Result, eval::EvalClient};
+
+fn render_ping(
+    backend: EvalBackend,
+    latency: Duration,
+    result: &std::result::Result<String, String>,
+) -> String {
+    let backend = match backend {
+        EvalBackend::Playground => "The playground",
+        EvalBackend::Local => "The local rustc",
+    };
+    let ms = latency.as_millis();
+    match result {
+        Ok(answer) => format!("{backend} answered in {ms}ms\n{answer}"),
+        Err(e) => format!("笨！\n{backend} failed after {ms}ms\n{e}"),
+    }
+}
+
+impl TomorinClient {
+    /// Check that the eval backend is reachable from here, independently of Telegram.
+    pub async fn handle_evalping(&self, m: &Message) -> Result<()> {
+        self.respond(m, "少女祈祷中......").await?;
+        let start = Instant::now();
+        let result = EvalClient::intance().ping().await;
+        let text = render_ping(self.conf.eval_backend, start.elapsed(), &result);
+        self.respond(m, text).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_ping() {
+        assert_eq!(
+            render_ping(
+                EvalBackend::Playground,
+                Duration::from_millis(312),
+                &Ok("HTTP 200 OK".into())
+            ),
+            "The playground answered in 312ms\nHTTP 200 OK"
+        );
+        assert_eq!(
+            render_ping(
+                EvalBackend::Local,
+                Duration::from_secs(5),
+                &Err("Timed out after 5s".into())
+            ),
+            "笨！\nThe local rustc failed after 5000ms\nTimed out after 5s"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use grammers_client::types::Message;
+use serde_json::{Value, json};
+
+use super::{TomorinClient, state::Inspect};
+use crate::{
+    conf::EvalBackend,
+    error::Result,
+    eval::{Channel, EDITIONS, EvalSettings, Mode},
+};
+
+/// Eval settings chosen with `,evalset` in single chats, until the next restart.
+#[derive(Debug, Default)]
+pub struct ChatEvalSettings {
+    chats: HashMap<i64, EvalSettings>,
+}
+
+impl ChatEvalSettings {
+    fn get(&self, chat: i64) -> EvalSettings {
+        self.chats.get(&chat).copied().unwrap_or_default()
+    }
+
+    /// Set the settings of `chat`, dropping them if they are the defaults again.
+    fn set(&mut self, chat: i64, settings: EvalSettings) {
+        if settings == EvalSettings::default() {
+            self.chats.remove(&chat);
+        } else {
+            self.chats.insert(chat, settings);
+        }
+    }
+}
+
+impl Inspect for ChatEvalSettings {
+    fn size(&self) -> usize {
+        self.chats.len()
+    }
+
+    fn dump(&self) -> Value {
+        json!(self.chats)
+    }
+
+    fn clear(&mut self) -> bool {
+        self.chats.clear();
+        true
+    }
+}
+
+/// Apply `key=value` pairs from `args` to `settings`.
+fn parse_evalset(args: &str, mut settings: EvalSettings) -> Result<EvalSettings, String> {
+    for pair in args.split_whitespace() {
+        let Some((key, value)) = pair.split_once('=') else {
+            return Err(format!("Expected key=value, got {pair}"));
+        };
+        match key {
+            "channel" => {
+                settings.channel =
+                    Channel::parse(value).ok_or("The channel is one of stable, beta or nightly")?;
+            }
+            "mode" => {
+                settings.mode = Mode::parse(value).ok_or("The mode is debug or release")?;
+            }
+            "edition" => {
+                settings.edition = EDITIONS
+                    .into_iter()
+                    .find(|edition| *edition == value)
+                    .ok_or_else(|| format!("The edition is one of {}", EDITIONS.join(", ")))?;
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown setting {key}, try channel, mode or edition"
+                ));
+            }
+        }
+    }
+    Ok(settings)
+}
+
+fn render_settings(settings: EvalSettings) -> String {
+    format!(
+        "channel={} mode={} edition={}",
+        settings.channel.as_str(),
+        settings.mode.as_str(),
+        settings.edition
+    )
+}
+
+impl TomorinClient {
+    /// The settings `r#` evals in `chat` are built with.
+    pub(super) fn eval_settings(&self, chat: i64) -> EvalSettings {
+        self.eval_settings.lock().unwrap().get(chat)
+    }
+
+    /// Show or change the eval settings of this chat.
+    pub async fn handle_evalset(&self, args: &str, m: &Message) -> Result<()> {
+        let chat = m.chat().id();
+        let settings = if args == "reset" {
+            EvalSettings::default()
+        } else {
+            match parse_evalset(args, self.eval_settings(chat)) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    self.respond(
+                        m,
+                        format!(
+                            "笨！\n{e}\nUsage: evalset [channel=<c>] [mode=<m>] [edition=<e>] | evalset reset"
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+        };
+        self.eval_settings.lock().unwrap().set(chat, settings);
+
+        let mut text = format!("Evals in this chat use {}", render_settings(settings));
+        if self.conf.eval_backend == EvalBackend::Local {
+            text.push_str("\nThe local backend ignores the channel");
+        }
+        self.respond(m, text).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_evalset() {
+        let settings = parse_evalset(
+            "channel=beta mode=release edition=2021",
+            EvalSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            render_settings(settings),
+            "channel=beta mode=release edition=2021"
+        );
+
+        // Unnamed settings are kept.
+        let settings = parse_evalset("mode=debug", settings).unwrap();
+        assert_eq!(
+            render_settings(settings),
+            "channel=beta mode=debug edition=2021"
+        );
+        assert_eq!(parse_evalset("", settings).unwrap(), settings);
+
+        assert!(parse_evalset("channel=alpha", settings).is_err());
+        assert!(parse_evalset("edition=2019", settings).is_err());
+        assert!(parse_evalset("mode", settings).is_err());
+        assert!(parse_evalset("opt=3", settings).is_err());
+    }
+
+    #[test]
+    fn test_chat_eval_settings() {
+        let mut chats = ChatEvalSettings::default();
+        let beta = EvalSettings {
+            channel: Channel::Beta,
+            ..Default::default()
+        };
+        chats.set(1, beta);
+        assert_eq!(chats.get(1), beta);
+        assert_eq!(chats.get(2), EvalSettings::default());
+
+        chats.set(1, EvalSettings::default());
+        assert!(chats.chats.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use grammers_client::{
+    grammers_tl_types::{enums::MessageEntity, types::MessageEntityPre},
+    types::Message,
+};
+
+use super::{TomorinClient, action::ChatAction, check_code_len, message::Outgoing};
+use crate::error::Result;
+
+/// Slice `text` by UTF-16 code units, the unit Telegram entity offsets are measured in.
+pub(super) fn utf16_slice(text: &str, offset: i32, length: i32) -> Option<String> {
+    let units = text.encode_utf16().collect::<Vec<_>>();
+    let start = usize::try_from(offset).ok()?;
+    let end = start.checked_add(usize::try_from(length).ok()?)?;
+    String::from_utf16(units.get(start..end)?).ok()
+}
+
+fn is_rust(language: &str) -> bool {
+    matches!(language.to_ascii_lowercase().as_str(), "rust" | "rs")
+}
+
+/// Find the code of the only Rust code block in a message.
+///
+/// Clients usually turn fences into `Pre` entities, literal fences are handled as a fallback.
+fn single_rust_fence(text: &str, entities: &[MessageEntity]) -> Option<String> {
+    let pres = entities
+        .iter()
+        .filter_map(|e| match e {
+            MessageEntity::Pre(pre) => Some(pre),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !pres.is_empty() {
+        return match pres.as_slice() {
+            [pre] if is_rust(&pre.language) => utf16_slice(text, pre.offset, pre.length),
+            _ => None,
+        };
+    }
+
+    if text.matches("```").count() != 2 {
+        return None;
+    }
+    let block = text.split("```").nth(1)?;
+    let (language, code) = block.split_once('\n')?;
+    is_rust(language.trim()).then(|| code.to_string())
+}
+
+impl TomorinClient {
+    pub fn auto_eval_code(&self, m: &Message) -> Option<String> {
+        if !self.conf.auto_eval_fences {
+            return None;
+        }
+        single_rust_fence(
+            m.text(),
+            m.fmt_entities().map(Vec::as_slice).unwrap_or_default(),
+        )
+        .filter(|code| check_code_len(code, self.conf.max_code_chars).is_ok())
+    }
+
+    /// Evaluate `code` and append the result to the message, keeping its original content.
+    pub async fn handle_auto_eval(&self, code: &str, m: &Message) -> Result<()> {
+        use crate::eval::EvalClient;
+
+        let settings = self.eval_settings(m.chat().id());
+        let resp = self
+            .with_action(
+                m,
+                ChatAction::Typing,
+                EvalClient::intance().eval(code, settings),
+            )
+            .await?;
+        let resp = resp.trim();
+
+        let text = m.text();
+        let mut entities = m.fmt_entities().cloned().unwrap_or_default();
+        entities.push(MessageEntity::Pre(MessageEntityPre {
+            offset: text.encode_utf16().count() as i32 + 1,
+            length: resp.encode_utf16().count() as i32,
+            language: "Output".to_string(),
+        }));
+
+        let out = Outgoing::text(format!("{text}\n{resp}")).fmt_entities(entities);
+        self.respond(m, out).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pre(offset: i32, length: i32, language: &str) -> MessageEntity {
+        MessageEntity::Pre(MessageEntityPre {
+            offset,
+            length,
+            language: language.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_single_rust_fence_entities() {
+        let text = "看 👀\n1 + 1";
+        assert_eq!(
+            single_rust_fence(text, &[pre(5, 5, "rust")]).as_deref(),
+            Some("1 + 1")
+        );
+        assert_eq!(single_rust_fence(text, &[pre(5, 5, "python")]), None);
+        assert_eq!(
+            single_rust_fence(text, &[pre(0, 1, "rust"), pre(5, 5, "rust")]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_single_rust_fence_literal() {
+        assert_eq!(
+            single_rust_fence("try\n```rust\n1 + 1\n```", &[]).as_deref(),
+            Some("1 + 1\n")
+        );
+        assert_eq!(single_rust_fence("```py\n1\n```", &[]), None);
+        assert_eq!(
+            single_rust_fence("```rust\n1\n```\n```rust\n2\n```", &[]),
+            None
+        );
+        assert_eq!(single_rust_fence("```rust\n1", &[]), None);
+        assert_eq!(single_rust_fence("no fences", &[]), None);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, VecDeque};
+
+use grammers_client::types::Message;
+use serde_json::{Value, json};
+
+use super::{TomorinClient, message::MessageOps, state::Inspect};
+use crate::error::Result;
+
+/// Shell commands recently run in each chat, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    chats: HashMap<i64, VecDeque<String>>,
+}
+
+impl History {
+    /// Record `cmd`, dropping the oldest entry once `capacity` is exceeded.
+    ///
+    /// Running the same command twice in a row is only recorded once.
+    pub fn push(&mut self, chat: i64, cmd: &str, capacity: usize) {
+        let entries = self.chats.entry(chat).or_default();
+        if entries.back().is_some_and(|last| last == cmd) {
+            return;
+        }
+
+        entries.push_back(cmd.to_string());
+        while entries.len() > capacity {
+            entries.pop_front();
+        }
+    }
+
+    pub fn last(&self, chat: i64) -> Option<&str> {
+        self.chats.get(&chat)?.back().map(String::as_str)
+    }
+
+    /// Get an entry by its 1-based index, as shown by `history`.
+    pub fn get(&self, chat: i64, index: usize) -> Option<&str> {
+        let entries = self.chats.get(&chat)?;
+        entries.get(index.checked_sub(1)?).map(String::as_str)
+    }
+
+    pub fn list(&self, chat: i64) -> impl Iterator<Item = (usize, &str)> {
+        self.chats
+            .get(&chat)
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, cmd)| (i + 1, cmd.as_str()))
+    }
+}
+
+impl TomorinClient {
+    pub async fn handle_history(&self, m: &impl MessageOps) -> Result<()> {
+        let listing = self
+            .history
+            .lock()
+            .unwrap()
+            .list(m.chat_id())
+            .map(|(i, cmd)| format!("{i:<3} {cmd}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if listing.is_empty() {
+            m.edit("No command history in this chat").await?;
+            return Ok(());
+        }
+        Self::edit_pre_msg(m, &listing, "History").await
+    }
+
+    /// Re-run the last command (`!!`) or the one at `index` (`!<n>`).
+    ///
+    /// Only the command line is recorded. Commands are spawned in the bot's working
+    /// directory and environment, which no command changes, so the re-run sees the same
+    /// state unless the bot was restarted elsewhere in the meantime.
+    pub async fn handle_history_rerun(&self, index: &str, m: &Message) -> Result<()> {
+        let index = match index {
+            "!" => None,
+            n => match n.parse() {
+                Ok(n) => Some(n),
+                Err(_) => {
+                    self.respond(m, format!("Invalid history index: {n}"))
+                        .await?;
+                    return Ok(());
+                }
+            },
+        };
+
+        let cmd = {
+            let history = self.history.lock().unwrap();
+            let chat = m.chat().id();
+            match index {
+                Some(n) => history.get(chat, n),
+                None => history.last(chat),
+            }
+            .map(str::to_string)
+        };
+
+        match cmd {
+            Some(cmd) => self.run_shell(&cmd, m).await,
+            None => {
+                self.respond(m, "No such command in this chat's history")
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Inspect for History {
+    fn size(&self) -> usize {
+        self.chats.len()
+    }
+
+    fn dump(&self) -> Value {
+        json!(self.chats)
+    }
+
+    fn clear(&mut self) -> bool {
+        self.chats.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut history = History::default();
+        assert_eq!(history.last(1), None);
+
+        history.push(1, "ls", 2);
+        history.push(1, "ls", 2);
+        history.push(1, "pwd", 2);
+        history.push(2, "whoami", 2);
+        assert_eq!(history.list(1).collect::<Vec<_>>(), [(1, "ls"), (2, "pwd")]);
+
+        history.push(1, "date", 2);
+        assert_eq!(history.get(1, 1), Some("pwd"));
+        assert_eq!(history.get(1, 0), None);
+        assert_eq!(history.get(1, 3), None);
+        assert_eq!(history.last(1), Some("date"));
+        assert_eq!(history.last(2), Some("whoami"));
+    }
+}
@@ -0,0 +1,52 @@
+use grammers_client::types::{InlineQuery, inline::query::Article};
+
+use super::{TomorinClient, check_code_len, eval_msg};
+use crate::{
+    conf::EvalBackend,
+    error::{BotError, Result},
+};
+
+impl TomorinClient {
+    /// Answer `@bot <code>` with the evaluation result, for bot accounts only.
+    ///
+    /// Anyone can send inline queries to a bot, so they are only evaluated on the
+    /// sandboxed playground, never with the local backend.
+    pub async fn handle_inline_query(&self, query: InlineQuery) -> Result<()> {
+        use crate::eval::{EvalClient, EvalSettings};
+
+        let code = query.text().trim();
+        let result = if code.is_empty() {
+            Article::new(
+                "Type some Rust code to evaluate",
+                "Type some Rust code after the bot name",
+            )
+        } else if self.conf.eval_backend == EvalBackend::Local {
+            Article::new(
+                "Inline eval is unavailable",
+                "Inline eval is disabled with the local backend",
+            )
+        } else if let Err(e) = check_code_len(code, self.conf.max_code_chars) {
+            Article::new("Snippet too long", e.clone()).description(e)
+        } else {
+            match EvalClient::intance()
+                .eval(code, EvalSettings::default())
+                .await
+            {
+                Ok(resp) => {
+                    let description = resp.trim().lines().next().unwrap_or_default().to_string();
+                    Article::new("Evaluate", eval_msg(self.conf.eval_render, code, &resp))
+                        .description(description)
+                }
+                Err(
+                    e @ (BotError::Http(_) | BotError::HttpTimeout | BotError::HttpRateLimited),
+                ) => Article::new("笨！", format!("笨！\n{e}")).description(e.to_string()),
+                Err(e) => return Err(e),
+            }
+        };
+
+        self.count_call("answer_inline_query");
+        // Results depend on the code alone, so Telegram may serve repeated queries from its cache.
+        query.answer([result.into()]).cache_time(300).send().await?;
+        Ok(())
+    }
+}
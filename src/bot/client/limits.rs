@@ -0,0 +1,153 @@
+use grammers_client::{grammers_tl_types as tl, types::Message};
+use serde_json::{Map, Value};
+
+use super::{TomorinClient, monitor::format_size};
+use crate::error::Result;
+
+/// Uploads are counted in parts of this size.
+const UPLOAD_PART_SIZE: u64 = 512 * 1024;
+
+/// Limits shown by `,limits`: the label, and the app config key without the `_default` or
+/// `_premium` suffix.
+const LIMITS: [(&str, &str); 8] = [
+    ("Upload size", "upload_max_fileparts"),
+    ("Folders", "dialog_filters_limit"),
+    ("Chats per folder", "dialog_filters_chats_limit"),
+    ("Joined chats", "channels_limit"),
+    ("Pinned chats", "dialogs_pinned_limit"),
+    ("Saved GIFs", "saved_gifs_limit"),
+    ("Favorite stickers", "stickers_faved_limit"),
+    ("Caption length", "caption_length_limit"),
+];
+
+/// The app config as JSON. Its keys are not part of the TL schema and change over time.
+fn json_value(value: tl::enums::Jsonvalue) -> Value {
+    use tl::enums::{JsonobjectValue, Jsonvalue};
+
+    match value {
+        Jsonvalue::JsonNull => Value::Null,
+        Jsonvalue::JsonBool(b) => b.value.into(),
+        Jsonvalue::JsonNumber(n) => n.value.into(),
+        Jsonvalue::JsonString(s) => s.value.into(),
+        Jsonvalue::JsonArray(a) => a.value.into_iter().map(json_value).collect(),
+        Jsonvalue::JsonObject(o) => o
+            .value
+            .into_iter()
+            .map(|JsonobjectValue::JsonObjectValue(kv)| (kv.key, json_value(kv.value)))
+            .collect::<Map<_, _>>()
+            .into(),
+    }
+}
+
+/// The known limits in `config`, the ones of the account's tier first. Limits missing or
+/// not numbers are left out.
+fn render_limits(config: &Value, premium: bool) -> String {
+    let mut lines = vec![format!("Premium - {}", if premium { "yes" } else { "no" })];
+    for (label, key) in LIMITS {
+        let limit = |tier| config[format!("{key}_{tier}")].as_f64().map(|n| n as u64);
+        let show = |n: u64| {
+            if key == "upload_max_fileparts" {
+                format_size(n * UPLOAD_PART_SIZE)
+            } else {
+                n.to_string()
+            }
+        };
+        let (current, other, other_tier) = if premium {
+            (limit("premium"), limit("default"), "without premium")
+        } else {
+            (limit("default"), limit("premium"), "premium")
+        };
+        let Some(current) = current else {
+            continue;
+        };
+        let mut line = format!("{label} - {}", show(current));
+        if let Some(other) = other
+            && other != current
+        {
+            line.push_str(&format!(" ({other_tier} {})", show(other)));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+impl TomorinClient {
+    /// Show the limits of the account, as told by the app config.
+    pub async fn handle_limits(&self, m: &Message) -> Result<()> {
+        let request = tl::functions::help::GetAppConfig { hash: 0 };
+        let config = match self.invoke(&request).await {
+            Ok(tl::enums::help::AppConfig::Config(config)) => json_value(config.config),
+            Ok(tl::enums::help::AppConfig::NotModified) => {
+                self.respond(m, "笨！\nTelegram sent no app config").await?;
+                return Ok(());
+            }
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to get the app config: {e}"))
+                    .await?;
+                return Ok(());
+            }
+        };
+        let text = render_limits(&config, self.me.raw.premium);
+        Self::edit_pre_msg(&self.responder(m), &text, "Limits").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json_value() {
+        let value = tl::types::JsonObject {
+            value: vec![
+                tl::types::JsonObjectValue {
+                    key: "n".into(),
+                    value: tl::types::JsonNumber { value: 4000.0 }.into(),
+                }
+                .into(),
+                tl::types::JsonObjectValue {
+                    key: "list".into(),
+                    value: tl::types::JsonArray {
+                        value: vec![
+                            tl::enums::Jsonvalue::JsonNull,
+                            tl::types::JsonBool { value: true }.into(),
+                            tl::types::JsonString { value: "a".into() }.into(),
+                        ],
+                    }
+                    .into(),
+                }
+                .into(),
+            ],
+        };
+        assert_eq!(
+            json_value(value.into()),
+            json!({"n": 4000.0, "list": [null, true, "a"]})
+        );
+    }
+
+    #[test]
+    fn test_render_limits() {
+        let config = json!({
+            "upload_max_fileparts_default": 4000.0,
+            "upload_max_fileparts_premium": 8000.0,
+            "dialog_filters_limit_default": 10.0,
+            "dialog_filters_limit_premium": 30.0,
+            "caption_length_limit_default": 1024.0,
+            "caption_length_limit_premium": 1024.0,
+            "channels_limit_default": "500",
+        });
+        assert_eq!(
+            render_limits(&config, false),
+            "Premium - no
+Upload size - 2.0 GiB (premium 3.9 GiB)
+Folders - 10 (premium 30)
+Caption length - 1024"
+        );
+        assert_eq!(
+            render_limits(&config, true).lines().nth(2),
+            Some("Folders - 30 (without premium 10)")
+        );
+    }
+}
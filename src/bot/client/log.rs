@@ -0,0 +1,162 @@
+use std::{io::SeekFrom, path::Path, time::Duration};
+
+use grammers_client::types::Message;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    time::Instant,
+};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Only this much of the end of the log file is read to find its last lines.
+const TAIL_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, PartialEq)]
+enum LogArgs {
+    Tail(usize),
+    Follow(Duration),
+}
+
+fn parse_log_args(args: &str) -> Option<LogArgs> {
+    const DEFAULT_LINES: usize = 20;
+    const DEFAULT_FOLLOW: Duration = Duration::from_secs(5 * 60);
+    const MAX_FOLLOW: Duration = Duration::from_secs(60 * 60);
+
+    let mut parts = args.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (None, ..) => Some(LogArgs::Tail(DEFAULT_LINES)),
+        (Some("follow"), None, _) => Some(LogArgs::Follow(DEFAULT_FOLLOW)),
+        (Some("follow"), Some(duration), None) => humantime::parse_duration(duration)
+            .ok()
+            .filter(|duration| *duration <= MAX_FOLLOW)
+            .map(LogArgs::Follow),
+        (Some(n), None, _) => n.parse().ok().filter(|n| *n > 0).map(LogArgs::Tail),
+        _ => None,
+    }
+}
+
+/// The last `n` lines of `text`.
+fn last_lines(text: &str, n: usize) -> &str {
+    if n == 0 {
+        return "";
+    }
+    let text = text.trim_end_matches('\n');
+    match text.rmatch_indices('\n').nth(n - 1) {
+        Some((pos, _)) => &text[pos + 1..],
+        None => text,
+    }
+}
+
+/// Read the log file from byte `from` to its end, returning the text and the end offset.
+/// Starts over from the beginning if the file shrank, as when it was rotated.
+async fn read_from(path: &Path, from: u64) -> std::io::Result<(String, u64)> {
+    let mut file = File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let from = if from > len { 0 } else { from };
+    file.seek(SeekFrom::Start(from)).await?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).await?;
+    Ok((
+        String::from_utf8_lossy(&bytes).into_owned(),
+        from + bytes.len() as u64,
+    ))
+}
+
+impl TomorinClient {
+    /// Show the last lines of the bot's own log file, or follow it for a while.
+    pub async fn handle_log(&self, args: &str, m: &Message) -> Result<()> {
+        let Some(log_args) = parse_log_args(args) else {
+            self.respond(m, "Usage: log [lines] | log follow [duration]")
+                .await?;
+            return Ok(());
+        };
+        let Some(path) = &self.conf.log_file else {
+            self.respond(
+                m,
+                "笨！\nFile logging is disabled, set log-file in the config",
+            )
+            .await?;
+            return Ok(());
+        };
+        let len = match tokio::fs::metadata(path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to read {}: {e}", path.display()))
+                    .await?;
+                return Ok(());
+            }
+        };
+        let out = self.responder(m);
+
+        match log_args {
+            LogArgs::Tail(n) => {
+                let (text, _) = read_from(path, len.saturating_sub(TAIL_BYTES)).await?;
+                let text = last_lines(&text, n);
+                let text = if text.is_empty() { "(empty log)" } else { text };
+                Self::edit_pre_msg(&out, text, "Log").await?;
+            }
+            LogArgs::Follow(duration) => {
+                let header = format!(
+                    "Following {} for {}\n",
+                    path.display(),
+                    humantime::format_duration(duration)
+                );
+                Self::edit_pre_msg(&out, &header, "Log").await?;
+                let tick = Duration::from_millis(self.conf.output_tick_ms.max(1));
+                let deadline = Instant::now() + duration;
+                let mut offset = len;
+                let mut lines = String::new();
+                // Stop early on `,drain`, which waits for this handler.
+                while Instant::now() < deadline && !self.is_draining() {
+                    tokio::time::sleep(tick).await;
+                    let (text, end) = read_from(path, offset).await?;
+                    offset = end;
+                    if text.is_empty() {
+                        continue;
+                    }
+                    lines.push_str(&text);
+                    if lines.len() > self.conf.output_max_bytes {
+                        lines = format!("{}\n", last_lines(&lines, 30));
+                    }
+                    Self::edit_pre_msg(&out, &format!("{header}{lines}"), "Log").await?;
+                }
+                Self::edit_pre_msg(&out, &format!("{header}{lines}\n(stopped)"), "Log").await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_args() {
+        assert_eq!(parse_log_args(""), Some(LogArgs::Tail(20)));
+        assert_eq!(parse_log_args("50"), Some(LogArgs::Tail(50)));
+        assert_eq!(
+            parse_log_args("follow"),
+            Some(LogArgs::Follow(Duration::from_secs(300)))
+        );
+        assert_eq!(
+            parse_log_args("follow 30s"),
+            Some(LogArgs::Follow(Duration::from_secs(30)))
+        );
+        assert_eq!(parse_log_args("0"), None);
+        assert_eq!(parse_log_args("follow 2h"), None);
+        assert_eq!(parse_log_args("follow 30s x"), None);
+        assert_eq!(parse_log_args("lots"), None);
+    }
+
+    #[test]
+    fn test_last_lines() {
+        assert_eq!(last_lines("a\nb\nc\n", 2), "b\nc");
+        assert_eq!(last_lines("a\nb\nc", 3), "a\nb\nc");
+        assert_eq!(last_lines("a\nb\nc", 10), "a\nb\nc");
+        assert_eq!(last_lines("a\nb\nc", 0), "");
+        assert_eq!(last_lines("", 5), "");
+    }
+}
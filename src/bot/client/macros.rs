@@ -0,0 +1,161 @@
+use grammers_client::types::Message;
+use tokio::process::Command;
+
+use super::{TomorinClient, shellwords::split_args};
+use crate::{error::Result, eval::output_capped};
+
+/// The arguments of `,run`: whether to keep going after a failing step, and the macro.
+fn parse_run_args(args: &str) -> Option<(bool, &str)> {
+    let mut keep_going = false;
+    let mut name = None;
+    for arg in args.split_whitespace() {
+        match arg {
+            "-k" | "--keep-going" => keep_going = true,
+            _ if name.is_none() => name = Some(arg),
+            _ => return None,
+        }
+    }
+    name.map(|name| (keep_going, name))
+}
+
+/// The header of the `index`-th of `total` steps, with its outcome.
+fn step_header(index: usize, total: usize, step: &str, outcome: &Result<i32, String>) -> String {
+    match outcome {
+        Ok(0) => format!("[{index}/{total}] ✓ {step}"),
+        Ok(code) => format!("[{index}/{total}] ✗ {step} (exit code {code})"),
+        Err(e) => format!("[{index}/{total}] ✗ {step} ({e})"),
+    }
+}
+
+impl TomorinClient {
+    /// Run the steps of a macro from the config in order, reporting the outcome of each.
+    /// A failing step stops the macro unless `-k` is given.
+    pub async fn handle_run(&self, args: &str, m: &Message) -> Result<()> {
+        let Some((keep_going, name)) = parse_run_args(args) else {
+            self.respond(m, "Usage: run [-k] <macro>").await?;
+            return Ok(());
+        };
+        let Some(steps) = self
+            .conf
+            .macros
+            .iter()
+            .find(|macro_| macro_.name == name)
+            .map(|macro_| &macro_.steps)
+        else {
+            let names: Vec<_> = self.conf.macros.iter().map(|m| m.name.as_str()).collect();
+            let known = if names.is_empty() {
+                "none configured".to_string()
+            } else {
+                names.join(", ")
+            };
+            self.respond(m, format!("笨！\nNo macro {name} (known: {known})"))
+                .await?;
+            return Ok(());
+        };
+
+        // Nothing runs unless every step may, so a macro never stops halfway for a
+        // confirmation.
+        for step in steps {
+            if let Ok(words) = split_args(step)
+                && let Some(program) = words.first()
+                && !self.may_spawn(program, m).await?
+            {
+                return Ok(());
+            }
+        }
+
+        let out = self.responder(m);
+        let mut resp = format!("❯ run {name}\n");
+        Self::edit_pre_msg(&out, &format!("{resp}少女祈祷中......"), "StdOut").await?;
+        let total = steps.len();
+        for (i, step) in steps.iter().enumerate() {
+            let (outcome, output) = self.run_step(step).await;
+            resp.push_str(&step_header(i + 1, total, step, &outcome));
+            resp.push('\n');
+            let output = output.trim_end();
+            if !output.is_empty() {
+                resp.push_str(output);
+                resp.push('\n');
+            }
+            let failed = outcome != Ok(0);
+            if failed && !keep_going {
+                if i + 1 < total {
+                    resp.push_str(&format!("Stopped, {} step(s) skipped\n", total - i - 1));
+                }
+                break;
+            }
+            if i + 1 < total {
+                Self::edit_pre_msg(&out, &resp, "StdOut").await?;
+            }
+        }
+        if total == 0 {
+            resp.push_str("(no steps)");
+        }
+        Self::edit_pre_msg(&out, &resp, "StdOut").await?;
+        Ok(())
+    }
+
+    /// Run a single step, returning its exit code, or why it did not run, and its output.
+    async fn run_step(&self, step: &str) -> (Result<i32, String>, String) {
+        let words = match split_args(step) {
+            Ok(words) => words,
+            Err(e) => return (Err(e), String::new()),
+        };
+        let Some((program, args)) = words.split_first() else {
+            return (Ok(0), String::new());
+        };
+        let timeout = *self.conf.macro_step_timeout;
+        let cap = self.conf.output_max_bytes;
+        let mut command = Command::new(program);
+        command.args(args);
+        match output_capped(&mut command, timeout, cap).await {
+            Ok(Some((output, capped))) => {
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                if capped {
+                    text.push_str(&format!("\n[output truncated at {cap} bytes]"));
+                }
+                (Ok(output.status.code().unwrap_or(-1)), text)
+            }
+            Ok(None) => (
+                Err(format!(
+                    "timed out after {}",
+                    humantime::format_duration(timeout)
+                )),
+                String::new(),
+            ),
+            Err(e) => (Err(e.to_string()), String::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_run_args() {
+        assert_eq!(parse_run_args("deploy"), Some((false, "deploy")));
+        assert_eq!(parse_run_args("-k deploy"), Some((true, "deploy")));
+        assert_eq!(
+            parse_run_args("deploy --keep-going"),
+            Some((true, "deploy"))
+        );
+        assert_eq!(parse_run_args(""), None);
+        assert_eq!(parse_run_args("-k"), None);
+        assert_eq!(parse_run_args("deploy build"), None);
+    }
+
+    #[test]
+    fn test_step_header() {
+        assert_eq!(step_header(1, 3, "git pull", &Ok(0)), "[1/3] ✓ git pull");
+        assert_eq!(
+            step_header(2, 3, "cargo build", &Ok(101)),
+            "[2/3] ✗ cargo build (exit code 101)"
+        );
+        assert_eq!(
+            step_header(3, 3, "rm -rf x", &Err("blocked by cmd-denylist".into())),
+            "[3/3] ✗ rm -rf x (blocked by cmd-denylist)"
+        );
+    }
+}
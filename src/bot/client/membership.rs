@@ -0,0 +1,88 @@
+use grammers_client::{
+    InvocationError, grammers_tl_types as tl,
+    types::{Chat, Message},
+};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// The path of a `t.me` link, without the scheme and the host.
+///
+/// The `telegram.me` / `telegram.dog` mirrors are accepted too.
+pub(super) fn link_path(link: &str) -> Option<&str> {
+    let link = link.trim();
+    let link = link
+        .strip_prefix("https://")
+        .or_else(|| link.strip_prefix("http://"))
+        .unwrap_or(link);
+    ["t.me/", "telegram.me/", "telegram.dog/"]
+        .into_iter()
+        .find_map(|host| link.strip_prefix(host))
+}
+
+/// Extract the invite hash from `t.me/+hash` or `t.me/joinchat/hash` links.
+fn parse_invite_hash(link: &str) -> Option<&str> {
+    let path = link_path(link)?;
+    let hash = path
+        .strip_prefix('+')
+        .or_else(|| path.strip_prefix("joinchat/"))?;
+    let hash = hash.split(['/', '?', '#']).next().unwrap_or_default();
+
+    (!hash.is_empty()).then_some(hash)
+}
+
+impl TomorinClient {
+    pub async fn handle_join(&self, link: &str, m: &Message) -> Result<()> {
+        let Some(hash) = parse_invite_hash(link) else {
+            self.respond(m, "Usage: join <t.me/+hash | t.me/joinchat/hash>")
+                .await?;
+            return Ok(());
+        };
+
+        let request = tl::functions::messages::ImportChatInvite {
+            hash: hash.to_string(),
+        };
+        match self.invoke(&request).await {
+            Ok(_) => self.respond(m, "Joined").await?,
+            Err(InvocationError::Rpc(e)) if e.name == "USER_ALREADY_PARTICIPANT" => {
+                self.respond(m, "Already a member of this chat").await?
+            }
+            Err(e) => self.respond(m, format!("笨！\n{e}")).await?,
+        }
+        Ok(())
+    }
+
+    pub async fn handle_leave(&self, m: &Message) -> Result<()> {
+        let chat = m.chat();
+        if let Chat::User(_) = chat {
+            self.respond(m, "Can't leave a private chat").await?;
+            return Ok(());
+        }
+
+        // Once we have left, the command message can no longer be edited.
+        self.respond(m, "Bye~").await?;
+        self.count_call("delete_dialog");
+        if let Err(e) = self.client.delete_dialog(&chat).await {
+            self.respond(m, format!("笨！\n{e}")).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_invite_hash() {
+        assert_eq!(parse_invite_hash("https://t.me/+AbC_123"), Some("AbC_123"));
+        assert_eq!(parse_invite_hash("t.me/joinchat/AbC_123"), Some("AbC_123"));
+        assert_eq!(
+            parse_invite_hash("https://telegram.me/joinchat/AbC_123/"),
+            Some("AbC_123")
+        );
+        assert_eq!(parse_invite_hash("https://t.me/+"), None);
+        assert_eq!(parse_invite_hash("https://t.me/somechannel"), None);
+        assert_eq!(parse_invite_hash("AbC_123"), None);
+    }
+}
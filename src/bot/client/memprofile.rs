@@ -0,0 +1,150 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Local};
+use grammers_client::types::Message;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+use tokio::task::JoinHandle;
+
+use super::{TomorinClient, monitor::format_size};
+use crate::{alloc_stats, error::Result};
+
+/// How often the resident set size is sampled for the trend shown by `,memprofile`.
+const RSS_SAMPLE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The last samples of the process' resident set size, oldest first.
+#[derive(Debug)]
+struct RssHistory {
+    samples: VecDeque<(DateTime<Local>, u64)>,
+}
+
+impl RssHistory {
+    /// Four hours of samples.
+    const MAX_SAMPLES: usize = 48;
+
+    const fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, at: DateTime<Local>, rss: u64) {
+        if self.samples.len() == Self::MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((at, rss));
+    }
+
+    /// One line per sample with the change since the previous one.
+    fn render(&self) -> String {
+        let mut previous = None;
+        let mut lines = Vec::with_capacity(self.samples.len());
+        for (at, rss) in &self.samples {
+            let change = match previous {
+                Some(previous) if *rss >= previous => {
+                    format!(" (+{})", format_size(rss - previous))
+                }
+                Some(previous) => format!(" (-{})", format_size(previous - rss)),
+                None => String::new(),
+            };
+            lines.push(format!(
+                "{} {}{change}",
+                at.format("%H:%M"),
+                format_size(*rss)
+            ));
+            previous = Some(*rss);
+        }
+        lines.join("\n")
+    }
+}
+
+/// RSS is process-wide, so the history outlives account switches and reconnects.
+static RSS_HISTORY: Mutex<RssHistory> = Mutex::new(RssHistory::new());
+
+/// The resident set size and virtual memory of this process, in bytes.
+fn own_memory() -> Option<(u64, u64)> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory(),
+    );
+    let process = sys.process(pid)?;
+    Some((process.memory(), process.virtual_memory()))
+}
+
+impl TomorinClient {
+    /// Sample the resident set size periodically until the returned task is aborted.
+    pub fn spawn_rss_sampler() -> JoinHandle<()> {
+        tokio::spawn(async {
+            let mut interval = tokio::time::interval(RSS_SAMPLE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Some((rss, _)) = own_memory() {
+                    RSS_HISTORY.lock().unwrap().push(Local::now(), rss);
+                }
+            }
+        })
+    }
+
+    /// Show the memory used by the bot: its RSS trend, and the allocation counters when
+    /// built with the `alloc-stats` feature.
+    pub async fn handle_memprofile(&self, m: &Message) -> Result<()> {
+        let mut text = match own_memory() {
+            Some((rss, virt)) => format!(
+                "RSS - {}\nVirtual - {}\n",
+                format_size(rss),
+                format_size(virt)
+            ),
+            None => "RSS - unknown\n".to_string(),
+        };
+
+        match alloc_stats::stats() {
+            Some(stats) => text.push_str(&format!(
+                "\nHeap live - {}\nHeap peak - {}\nAllocations - {}\nDeallocations - {}\nLive allocations - {}\n",
+                format_size(stats.live_bytes as u64),
+                format_size(stats.peak_bytes as u64),
+                stats.allocations,
+                stats.deallocations,
+                stats.allocations.saturating_sub(stats.deallocations),
+            )),
+            None => text.push_str("\nHeap stats need a build with the alloc-stats feature\n"),
+        }
+
+        let trend = RSS_HISTORY.lock().unwrap().render();
+        if !trend.is_empty() {
+            text.push_str(&format!(
+                "\nRSS every {}:\n{trend}",
+                humantime::format_duration(RSS_SAMPLE_INTERVAL)
+            ));
+        }
+        Self::edit_pre_msg(&self.responder(m), &text, "Memory").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_rss_history() {
+        let mut history = RssHistory::new();
+        assert_eq!(history.render(), "");
+
+        let at = |min| Local.with_ymd_and_hms(2025, 1, 1, 12, min, 0).unwrap();
+        history.push(at(0), 10 * 1024 * 1024);
+        history.push(at(5), 12 * 1024 * 1024);
+        history.push(at(10), 11 * 1024 * 1024);
+        assert_eq!(
+            history.render(),
+            "12:00 10.0 MiB\n12:05 12.0 MiB (+2.0 MiB)\n12:10 11.0 MiB (-1.0 MiB)"
+        );
+
+        for min in 0..RssHistory::MAX_SAMPLES as u32 {
+            history.push(at(min % 60), 0);
+        }
+        assert_eq!(history.samples.len(), RssHistory::MAX_SAMPLES);
+    }
+}
@@ -0,0 +1,170 @@
+use std::future::Future;
+
+use grammers_client::{
+    InputMessage, InvocationError, grammers_tl_types::enums::MessageEntity, types::Message,
+};
+
+use crate::conf::Truncation;
+
+/// Message content to edit into a message.
+///
+/// Unlike `InputMessage` its text and entities can be inspected, which is what the mock needs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Outgoing {
+    pub text: String,
+    pub entities: Vec<MessageEntity>,
+    /// The command output shown in the text, in full before any truncation, delivered as
+    /// the chat's output mode says if too long to show.
+    pub full_output: Option<String>,
+}
+
+impl Outgoing {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            entities: Vec::new(),
+            full_output: None,
+        }
+    }
+
+    pub fn markdown(text: &str) -> Self {
+        let (text, entities) = grammers_client::parsers::parse_markdown_message(text);
+        Self {
+            text,
+            entities,
+            full_output: None,
+        }
+    }
+
+    pub fn fmt_entities(mut self, entities: Vec<MessageEntity>) -> Self {
+        self.entities = entities;
+        self
+    }
+
+    pub fn full_output(mut self, output: impl Into<String>) -> Self {
+        self.full_output = Some(output.into());
+        self
+    }
+}
+
+impl From<&str> for Outgoing {
+    fn from(text: &str) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<String> for Outgoing {
+    fn from(text: String) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<Outgoing> for InputMessage {
+    fn from(out: Outgoing) -> Self {
+        InputMessage::text(out.text).fmt_entities(out.entities)
+    }
+}
+
+/// The message operations handlers rely on, so they can run against a mock in tests.
+pub trait MessageOps: Sized + Send + Sync {
+    fn text(&self) -> &str;
+
+    fn chat_id(&self) -> i64;
+
+    fn edit(
+        &self,
+        new: impl Into<Outgoing> + Send,
+    ) -> impl Future<Output = Result<(), InvocationError>> + Send;
+
+    fn get_reply(&self) -> impl Future<Output = Result<Option<Self>, InvocationError>> + Send;
+
+    /// Which lines of long output edited into this message are kept.
+    fn truncation(&self) -> Truncation {
+        Truncation::Tail
+    }
+
+    /// The column output edited into this message is wrapped at, if any.
+    fn wrap_columns(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl MessageOps for Message {
+    fn text(&self) -> &str {
+        Message::text(self)
+    }
+
+    fn chat_id(&self) -> i64 {
+        self.chat().id()
+    }
+
+    fn edit(
+        &self,
+        new: impl Into<Outgoing> + Send,
+    ) -> impl Future<Output = Result<(), InvocationError>> + Send {
+        let new = InputMessage::from(new.into());
+        Message::edit(self, new)
+    }
+
+    fn get_reply(&self) -> impl Future<Output = Result<Option<Self>, InvocationError>> + Send {
+        Message::get_reply(self)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// A message that records every edit instead of talking to Telegram.
+    #[derive(Clone, Debug, Default)]
+    pub struct MockMessage {
+        pub text: String,
+        pub chat_id: i64,
+        pub reply: Option<Box<MockMessage>>,
+        pub edits: Arc<Mutex<Vec<Outgoing>>>,
+        pub truncation: Truncation,
+        pub wrap_columns: Option<usize>,
+    }
+
+    impl MockMessage {
+        pub fn new(text: &str) -> Self {
+            Self {
+                text: text.to_string(),
+                ..Default::default()
+            }
+        }
+
+        pub fn last_edit(&self) -> Option<Outgoing> {
+            self.edits.lock().unwrap().last().cloned()
+        }
+    }
+
+    impl MessageOps for MockMessage {
+        fn text(&self) -> &str {
+            &self.text
+        }
+
+        fn chat_id(&self) -> i64 {
+            self.chat_id
+        }
+
+        async fn edit(&self, new: impl Into<Outgoing> + Send) -> Result<(), InvocationError> {
+            self.edits.lock().unwrap().push(new.into());
+            Ok(())
+        }
+
+        async fn get_reply(&self) -> Result<Option<Self>, InvocationError> {
+            Ok(self.reply.as_deref().cloned())
+        }
+
+        fn truncation(&self) -> Truncation {
+            self.truncation
+        }
+
+        fn wrap_columns(&self) -> Option<usize> {
+            self.wrap_columns
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use grammers_client::types::Message;
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Format a byte count with a binary unit, e.g. `1.5 GiB`.
+pub(super) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Lay out `rows` as left-aligned columns separated by two spaces.
+fn render_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths = (0..columns)
+        .map(|c| {
+            rows.iter()
+                .filter_map(|row| row.get(c))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl TomorinClient {
+    pub async fn handle_df(&self, m: &Message) -> Result<()> {
+        let disks = sysinfo::Disks::new_with_refreshed_list();
+        if disks.list().is_empty() {
+            self.respond(
+                m,
+                "No disks found, disk enumeration may be unsupported on this platform",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let mut rows = vec![
+            ["Mount", "FS", "Size", "Used", "Avail", "Use%"]
+                .map(String::from)
+                .to_vec(),
+        ];
+        for disk in disks.list() {
+            let total = disk.total_space();
+            let available = disk.available_space();
+            let used = total.saturating_sub(available);
+            let percent = (used * 100).checked_div(total).unwrap_or(0);
+
+            rows.push(vec![
+                disk.mount_point().display().to_string(),
+                disk.file_system().to_string_lossy().into_owned(),
+                format_size(total),
+                format_size(used),
+                format_size(available),
+                format!("{percent}%"),
+            ]);
+        }
+
+        Self::edit_pre_msg(&self.responder(m), &render_table(&rows), "Disks").await
+    }
+
+    /// List the top processes, sorted by memory or, with `cpu`, by CPU usage.
+    pub async fn handle_top(&self, sort: &str, m: &Message) -> Result<()> {
+        use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+        const TOP_N: usize = 5;
+
+        let by_cpu = match sort {
+            "" | "mem" => false,
+            "cpu" => true,
+            _ => {
+                self.respond(m, "Usage: top [mem|cpu]").await?;
+                return Ok(());
+            }
+        };
+
+        // CPU usage is computed between two refreshes, so the first one alone is meaningless.
+        let refresh_kind = ProcessRefreshKind::nothing().with_cpu().with_memory();
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh_kind);
+        tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+        sys.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh_kind);
+
+        let mut processes = sys.processes().values().collect::<Vec<_>>();
+        if by_cpu {
+            processes.sort_by(|a, b| b.cpu_usage().total_cmp(&a.cpu_usage()));
+        } else {
+            processes.sort_by_key(|p| std::cmp::Reverse(p.memory()));
+        }
+
+        let mut rows = vec![["PID", "Name", "Mem", "CPU%"].map(String::from).to_vec()];
+        for p in processes.into_iter().take(TOP_N) {
+            rows.push(vec![
+                p.pid().to_string(),
+                p.name().to_string_lossy().into_owned(),
+                format_size(p.memory()),
+                format!("{:.1}", p.cpu_usage()),
+            ]);
+        }
+
+        Self::edit_pre_msg(&self.responder(m), &render_table(&rows), "Processes").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let rows = [
+            vec!["a".into(), "bb".into()],
+            vec!["ccc".into(), "d".into()],
+        ];
+        assert_eq!(render_table(&rows), "a    bb\nccc  d");
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use grammers_client::types::Message;
+use serde_json::{Value, json};
+
+use super::{TomorinClient, state::Inspect};
+use crate::error::Result;
+
+/// Until when handler errors are kept quiet, lifting by itself once that has passed.
+#[derive(Debug, Default)]
+pub struct ErrorMute {
+    until: Option<Instant>,
+}
+
+impl ErrorMute {
+    fn mute(&mut self, now: Instant, duration: Duration) {
+        self.until = Some(now + duration);
+    }
+
+    fn unmute(&mut self) {
+        self.until = None;
+    }
+
+    /// How long errors stay muted after `now`, `None` if they are not.
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.until
+            .and_then(|until| until.checked_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+impl TomorinClient {
+    /// Whether handler errors are currently muted by `mute-errors`.
+    pub fn errors_muted(&self) -> bool {
+        self.error_mute
+            .lock()
+            .unwrap()
+            .remaining(Instant::now())
+            .is_some()
+    }
+
+    pub async fn handle_mute_errors(&self, args: &str, m: &Message) -> Result<()> {
+        let now = Instant::now();
+        let remaining = {
+            let mut mute = self.error_mute.lock().unwrap();
+            match args {
+                "" => {}
+                "off" => mute.unmute(),
+                duration => match humantime::parse_duration(duration) {
+                    Ok(duration) => mute.mute(now, duration),
+                    Err(_) => {
+                        drop(mute);
+                        self.respond(m, "Usage: mute-errors [<duration> | off]")
+                            .await?;
+                        return Ok(());
+                    }
+                },
+            }
+            mute.remaining(now)
+        };
+
+        let status = match remaining {
+            Some(remaining) => format!(
+                "Errors muted for {}",
+                humantime::format_duration(Duration::from_secs(remaining.as_secs().max(1)))
+            ),
+            None => "Errors are not muted".to_string(),
+        };
+        self.respond(m, status).await?;
+        Ok(())
+    }
+}
+
+impl Inspect for ErrorMute {
+    fn size(&self) -> usize {
+        usize::from(self.until.is_some())
+    }
+
+    fn dump(&self) -> Value {
+        let remaining = self.remaining(Instant::now());
+        json!({ "muted_for_secs": remaining.map(|d| d.as_secs()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_mute() {
+        let now = Instant::now();
+        let mut mute = ErrorMute::default();
+        assert_eq!(mute.remaining(now), None);
+
+        mute.mute(now, Duration::from_secs(60));
+        assert_eq!(mute.remaining(now), Some(Duration::from_secs(60)));
+        assert_eq!(
+            mute.remaining(now + Duration::from_secs(45)),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(mute.remaining(now + Duration::from_secs(60)), None);
+        assert_eq!(mute.remaining(now + Duration::from_secs(90)), None);
+
+        mute.unmute();
+        assert_eq!(mute.remaining(now), None);
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use grammers_client::{grammers_tl_types as tl, types::Message};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Rough verdict on the connection from the round-trip time of a trivial RPC.
+fn health(rtt: Duration) -> &'static str {
+    match rtt.as_millis() {
+        0..300 => "good",
+        300..1000 => "slow",
+        _ => "poor",
+    }
+}
+
+/// Summary of the round-trip times measured by `bench-net`.
+#[derive(Debug, PartialEq, Eq)]
+struct LatencyStats {
+    min: Duration,
+    avg: Duration,
+    max: Duration,
+    p95: Duration,
+}
+
+impl LatencyStats {
+    /// `None` if there are no samples.
+    fn new(samples: &mut [Duration]) -> Option<Self> {
+        samples.sort();
+        let min = *samples.first()?;
+        let max = *samples.last()?;
+        let avg = samples.iter().sum::<Duration>() / samples.len() as u32;
+        // Nearest-rank percentile.
+        let rank = (samples.len() * 95).div_ceil(100);
+        let p95 = samples[rank.max(1) - 1];
+        Some(Self { min, avg, max, p95 })
+    }
+}
+
+impl TomorinClient {
+    const BENCH_NET_MAX: u32 = 50;
+    const BENCH_NET_PAUSE: Duration = Duration::from_millis(500);
+
+    /// Edit the command message `n` times in a row and report the latency of the edits.
+    pub async fn handle_bench_net(&self, args: &str, m: &Message) -> Result<()> {
+        let n = match args.parse::<u32>() {
+            Ok(n) if (1..=Self::BENCH_NET_MAX).contains(&n) => n,
+            _ => {
+                self.respond(
+                    m,
+                    format!(
+                        "Usage: bench-net <n>, with n from 1 to {}",
+                        Self::BENCH_NET_MAX
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let mut samples = Vec::with_capacity(n as usize);
+        for i in 1..=n {
+            if i > 1 {
+                tokio::time::sleep(Self::BENCH_NET_PAUSE).await;
+            }
+            let started = Instant::now();
+            self.respond(m, format!("Measuring edit {i}/{n}......"))
+                .await?;
+            samples.push(started.elapsed());
+        }
+
+        let Some(stats) = LatencyStats::new(&mut samples) else {
+            return Ok(());
+        };
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let report = format!(
+            "Edits   {n}
+Min     {:.0} ms
+Avg     {:.0} ms
+P95     {:.0} ms
+Max     {:.0} ms",
+            ms(stats.min),
+            ms(stats.avg),
+            ms(stats.p95),
+            ms(stats.max),
+        );
+        Self::edit_pre_msg(&self.responder(m), &report, "Edit latency").await
+    }
+
+    pub async fn handle_netinfo(&self, m: &Message) -> Result<()> {
+        let started = Instant::now();
+        let resp = self.invoke(&tl::functions::help::GetNearestDc {}).await;
+        let rtt = started.elapsed();
+
+        let tl::enums::NearestDc::Dc(dc) = match resp {
+            Ok(dc) => dc,
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to query the nearest DC: {e}"))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let info = format!(
+            "Current DC   {}
+Nearest DC   {}
+Country      {}
+Round trip   {} ms ({})",
+            dc.this_dc,
+            dc.nearest_dc,
+            dc.country,
+            rtt.as_millis(),
+            health(rtt),
+        );
+        Self::edit_pre_msg(&self.responder(m), &info, "Network").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health() {
+        assert_eq!(health(Duration::from_millis(42)), "good");
+        assert_eq!(health(Duration::from_millis(300)), "slow");
+        assert_eq!(health(Duration::from_secs(3)), "poor");
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let ms = Duration::from_millis;
+        assert_eq!(LatencyStats::new(&mut []), None);
+
+        let mut samples = (1..=20).rev().map(|i| ms(i * 10)).collect::<Vec<_>>();
+        assert_eq!(
+            LatencyStats::new(&mut samples),
+            Some(LatencyStats {
+                min: ms(10),
+                avg: ms(105),
+                max: ms(200),
+                p95: ms(190),
+            })
+        );
+
+        assert_eq!(
+            LatencyStats::new(&mut [ms(42)]),
+            Some(LatencyStats {
+                min: ms(42),
+                avg: ms(42),
+                max: ms(42),
+                p95: ms(42),
+            })
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use grammers_client::{InputMessage, types::Message};
+use serde_json::{Value, json};
+
+use super::{TomorinClient, action::ChatAction, check_code_len, eval_msg, state::Inspect};
+use crate::error::{BotError, Result};
+
+/// The last cell of the notebook running in each chat, which the next cell replies to.
+#[derive(Debug, Default)]
+pub struct Notebooks {
+    last_cells: HashMap<i64, i32>,
+}
+
+impl Notebooks {
+    fn last_cell(&self, chat: i64) -> Option<i32> {
+        self.last_cells.get(&chat).copied()
+    }
+
+    fn append(&mut self, chat: i64, cell: i32) {
+        self.last_cells.insert(chat, cell);
+    }
+
+    /// Start over with a new thread, returning whether there was one.
+    fn reset(&mut self, chat: i64) -> bool {
+        self.last_cells.remove(&chat).is_some()
+    }
+}
+
+impl TomorinClient {
+    /// Evaluate `code` and send it with its output as a new cell, replying to the previous
+    /// cell of this chat, so the cells form a thread. `nb#` alone starts a new thread.
+    pub async fn handle_notebook(&self, code: &str, m: &Message) -> Result<()> {
+        use crate::eval::EvalClient;
+
+        let chat = m.chat().id();
+        if code.trim().is_empty() {
+            let text = if self.notebooks.lock().unwrap().reset(chat) {
+                "The next cell starts a new notebook"
+            } else {
+                "Usage: nb#<code>, or nb# alone to start a new notebook"
+            };
+            self.respond(m, text).await?;
+            return Ok(());
+        }
+
+        if let Err(e) = check_code_len(code, self.conf.max_code_chars) {
+            self.respond(m, e).await?;
+            return Ok(());
+        }
+
+        self.respond(m, "少女祈祷中......").await?;
+        let settings = self.eval_settings(m.chat().id());
+        let resp = match self
+            .with_action(
+                m,
+                ChatAction::Typing,
+                EvalClient::intance().eval(code, settings),
+            )
+            .await
+        {
+            Ok(resp) => resp,
+            Err(e @ (BotError::Http(_) | BotError::HttpTimeout | BotError::HttpRateLimited)) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        let last_cell = self.notebooks.lock().unwrap().last_cell(chat);
+        let cell = InputMessage::from(eval_msg(self.conf.eval_render, code, &resp))
+            .reply_to(last_cell)
+            .silent(self.is_silent(m));
+        self.pace(chat, "send_message").await;
+        let cell = self.client.send_message(m.chat(), cell).await?;
+        self.mark_written(&cell, cell.text());
+        self.notebooks.lock().unwrap().append(chat, cell.id());
+
+        self.discard_response(m).await?;
+        Ok(())
+    }
+}
+
+impl Inspect for Notebooks {
+    fn size(&self) -> usize {
+        self.last_cells.len()
+    }
+
+    fn dump(&self) -> Value {
+        json!(self.last_cells)
+    }
+
+    fn clear(&mut self) -> bool {
+        self.last_cells.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notebooks() {
+        let mut notebooks = Notebooks::default();
+        assert_eq!(notebooks.last_cell(1), None);
+
+        notebooks.append(1, 10);
+        notebooks.append(1, 11);
+        notebooks.append(2, 20);
+        assert_eq!(notebooks.last_cell(1), Some(11));
+        assert_eq!(notebooks.last_cell(2), Some(20));
+
+        assert!(notebooks.reset(1));
+        assert!(!notebooks.reset(1));
+        assert_eq!(notebooks.last_cell(1), None);
+        assert_eq!(notebooks.last_cell(2), Some(20));
+    }
+}
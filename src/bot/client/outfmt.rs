@@ -0,0 +1,117 @@
+use unicode_width::UnicodeWidthStr;
+
+/// How the captured output of a shell command is post-processed, chosen with `--fmt`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Table,
+}
+
+/// Split a trailing `--fmt <format>` off a shell command.
+pub fn split_fmt(cmd: &str) -> Result<(&str, OutputFormat), String> {
+    let trimmed = cmd.trim_end();
+    let Some((rest, format)) = trimmed.rsplit_once(char::is_whitespace) else {
+        return Ok((cmd, OutputFormat::Text));
+    };
+    let Some(rest) = rest.trim_end().strip_suffix("--fmt") else {
+        return Ok((cmd, OutputFormat::Text));
+    };
+    if !rest.is_empty() && !rest.ends_with(char::is_whitespace) {
+        return Ok((cmd, OutputFormat::Text));
+    }
+
+    let format = match format {
+        "text" => OutputFormat::Text,
+        "json" => OutputFormat::Json,
+        "table" => OutputFormat::Table,
+        _ => {
+            return Err(format!(
+                "Unknown format {format}, expected json, text or table"
+            ));
+        }
+    };
+    Ok((rest.trim_end(), format))
+}
+
+/// Pretty-print `output` as JSON, or return it unchanged if it isn't valid JSON.
+fn pretty_json(output: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(output)
+        .and_then(|json| serde_json::to_string_pretty(&json))
+        .unwrap_or_else(|_| output.to_string())
+}
+
+/// Align the whitespace separated columns of `output`.
+fn align_table(output: &str) -> String {
+    let rows = output
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    let widths = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.width())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    let mut table = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            line.push_str(cell);
+            line.push_str(&" ".repeat(width - cell.width() + 2));
+        }
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+pub fn format_output(output: &str, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Text => output.to_string(),
+        OutputFormat::Json => pretty_json(output),
+        OutputFormat::Table => align_table(output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_fmt() {
+        assert_eq!(split_fmt("ls -l"), Ok(("ls -l", OutputFormat::Text)));
+        assert_eq!(
+            split_fmt("curl -s api --fmt json"),
+            Ok(("curl -s api", OutputFormat::Json))
+        );
+        assert_eq!(
+            split_fmt("ps --fmt  table "),
+            Ok(("ps", OutputFormat::Table))
+        );
+        assert_eq!(
+            split_fmt("echo x--fmt json"),
+            Ok(("echo x--fmt json", OutputFormat::Text))
+        );
+        assert!(split_fmt("ls --fmt yaml").is_err());
+    }
+
+    #[test]
+    fn test_format_output() {
+        assert_eq!(
+            format_output(r#"{"a":[1,2]}"#, OutputFormat::Json),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}"
+        );
+        assert_eq!(format_output("not json", OutputFormat::Json), "not json");
+        assert_eq!(
+            format_output("PID CMD\n1 init\n4242 bash", OutputFormat::Table),
+            "PID   CMD\n1     init\n4242  bash\n"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::{collections::HashMap, io::Cursor};
+
+use grammers_client::{InputMessage, types::Message};
+use serde_json::{Value, json};
+
+use super::{
+    MAX_OUTPUT_LINES, TomorinClient,
+    action::ChatAction,
+    dedup::MessageKey,
+    state::{Inspect, key_name},
+};
+use crate::{conf::OutputMode, error::Result};
+
+/// Output modes overriding the configured `output-mode` in single chats, until the next
+/// restart.
+#[derive(Debug, Default)]
+pub struct ChatOutputModes {
+    chats: HashMap<i64, OutputMode>,
+}
+
+impl ChatOutputModes {
+    fn get(&self, chat: i64, default: OutputMode) -> OutputMode {
+        self.chats.get(&chat).copied().unwrap_or(default)
+    }
+
+    /// Set the mode of `chat`, dropping the override if it is the default again.
+    fn set(&mut self, chat: i64, mode: OutputMode, default: OutputMode) {
+        if mode == default {
+            self.chats.remove(&chat);
+        } else {
+            self.chats.insert(chat, mode);
+        }
+    }
+}
+
+/// The full output of commands still running that is too long to show, per command
+/// message, delivered once the command finished.
+#[derive(Debug, Default)]
+pub struct LongOutputs {
+    outputs: HashMap<MessageKey, String>,
+}
+
+impl LongOutputs {
+    /// Keep `output`, just shown for the command `key`, if it is too long to show in full.
+    /// Output that fits replaces what was kept.
+    pub(super) fn record(&mut self, key: MessageKey, output: &str) {
+        if output.trim().lines().count() > MAX_OUTPUT_LINES {
+            self.outputs.insert(key, output.to_string());
+        } else {
+            self.outputs.remove(&key);
+        }
+    }
+
+    fn take(&mut self, key: &MessageKey) -> Option<String> {
+        self.outputs.remove(key)
+    }
+}
+
+fn parse_output_mode(mode: &str) -> Option<OutputMode> {
+    match mode {
+        "inline" => Some(OutputMode::Inline),
+        "file" => Some(OutputMode::File),
+        "image" => Some(OutputMode::Image),
+        _ => None,
+    }
+}
+
+fn mode_name(mode: OutputMode) -> &'static str {
+    match mode {
+        OutputMode::Inline => "inline",
+        OutputMode::File => "file",
+        OutputMode::Image => "image",
+    }
+}
+
+impl TomorinClient {
+    fn output_mode(&self, chat: i64) -> OutputMode {
+        self.output_modes
+            .lock()
+            .unwrap()
+            .get(chat, self.conf.output_mode)
+    }
+
+    /// Deliver the full output of the command `m`, if it was too long to show in full, as
+    /// a file or an image next to the truncated message when the chat's output mode asks
+    /// for it.
+    pub(super) async fn deliver_full_output(&self, m: &Message) -> Result<()> {
+        let output = self
+            .long_outputs
+            .lock()
+            .unwrap()
+            .take(&(m.chat().id(), m.id()));
+        let mode = self.output_mode(m.chat().id());
+        let Some(output) = output.filter(|_| mode != OutputMode::Inline) else {
+            return Ok(());
+        };
+
+        let caption = format!("❯ {}", m.text().trim());
+        match mode {
+            OutputMode::Inline => {}
+            OutputMode::File => {
+                let len = output.len();
+                self.count_call("upload");
+                let uploaded = self
+                    .with_action(
+                        m,
+                        ChatAction::UploadDocument,
+                        self.client.upload_stream(
+                            &mut Cursor::new(output.as_bytes()),
+                            len,
+                            "output.txt".to_string(),
+                        ),
+                    )
+                    .await?;
+                self.send_output_media(m, InputMessage::text(caption).document(uploaded))
+                    .await?;
+            }
+            OutputMode::Image => {
+                let font = match self.load_shot_font() {
+                    Ok(font) => font,
+                    Err(e) => {
+                        tracing::warn!("Can't render output as an image: {e}");
+                        return Ok(());
+                    }
+                };
+                let png = self.render_png(output, font).await?;
+                self.send_shot(m, &caption, png).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Show or set how long output is delivered in this chat.
+    pub async fn handle_outmode(&self, args: &str, m: &Message) -> Result<()> {
+        let chat = m.chat().id();
+        let mode = match args {
+            "" => self.output_mode(chat),
+            "default" => self.conf.output_mode,
+            _ => match parse_output_mode(args) {
+                Some(mode) => mode,
+                None => {
+                    self.respond(m, "Usage: outmode [inline|file|image|default]")
+                        .await?;
+                    return Ok(());
+                }
+            },
+        };
+        self.output_modes
+            .lock()
+            .unwrap()
+            .set(chat, mode, self.conf.output_mode);
+
+        let text = match mode {
+            OutputMode::Inline => "only truncated",
+            OutputMode::File => "also sent in full as a file",
+            OutputMode::Image => "also sent in full as an image",
+        };
+        self.respond(
+            m,
+            format!("Long output is {text} in this chat ({})", mode_name(mode)),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl Inspect for LongOutputs {
+    fn size(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// The length in bytes of the output kept for each command.
+    fn dump(&self) -> Value {
+        self.outputs
+            .iter()
+            .map(|(key, output)| (key_name(*key), json!(output.len())))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+impl Inspect for ChatOutputModes {
+    fn size(&self) -> usize {
+        self.chats.len()
+    }
+
+    fn dump(&self) -> Value {
+        json!(self.chats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_output_modes() {
+        let mut modes = ChatOutputModes::default();
+        assert_eq!(modes.get(1, OutputMode::Inline), OutputMode::Inline);
+
+        modes.set(1, OutputMode::File, OutputMode::Inline);
+        assert_eq!(modes.get(1, OutputMode::Inline), OutputMode::File);
+        assert_eq!(modes.get(2, OutputMode::Image), OutputMode::Image);
+
+        modes.set(1, OutputMode::Inline, OutputMode::Inline);
+        assert!(modes.chats.is_empty());
+    }
+
+    #[test]
+    fn test_long_outputs() {
+        let mut outputs = LongOutputs::default();
+        let long = "line\n".repeat(MAX_OUTPUT_LINES + 1);
+        outputs.record((1, 1), "short");
+        assert_eq!(outputs.take(&(1, 1)), None);
+
+        outputs.record((1, 1), &long);
+        outputs.record((1, 2), &long);
+        assert_eq!(outputs.take(&(1, 1)), Some(long.clone()));
+        assert_eq!(outputs.take(&(1, 1)), None);
+
+        outputs.record((1, 2), "fits now");
+        assert_eq!(outputs.take(&(1, 2)), None);
+    }
+
+    #[test]
+    fn test_parse_output_mode() {
+        for mode in [OutputMode::Inline, OutputMode::File, OutputMode::Image] {
+            assert_eq!(parse_output_mode(mode_name(mode)), Some(mode));
+        }
+        assert_eq!(parse_output_mode("pdf"), None);
+    }
+}
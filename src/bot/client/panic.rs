@@ -0,0 +1,87 @@
+use std::{any::Any, panic::AssertUnwindSafe};
+
+use futures_util::FutureExt;
+use grammers_client::Update::{self, MessageEdited, NewMessage};
+
+use super::{TomorinClient, route::RoutedCommand};
+use crate::error::Result;
+
+/// How much of the triggering message a panic is logged with.
+const CONTEXT_CHARS: usize = 80;
+
+/// The message a panic was raised with, as given to `panic!` or `.unwrap()`.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// The first line of `text`, shortened to fit in a log line.
+fn command_context(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    let mut context: String = line.chars().take(CONTEXT_CHARS).collect();
+    if context.len() < text.len() {
+        context.push('…');
+    }
+    context
+}
+
+impl TomorinClient {
+    /// [`TomorinClient::update`], with a panic in its handler caught and logged along with
+    /// the message that triggered it, so it can't take the task down silently. With
+    /// `report-panics`, commands also get the panic as their output.
+    pub async fn update_isolated(&self, update: Update) -> Result<()> {
+        let message = match &update {
+            NewMessage(m) | MessageEdited(m) => Some(m.clone()),
+            _ => None,
+        };
+        let payload = match AssertUnwindSafe(self.update(update)).catch_unwind().await {
+            Ok(handled) => return handled,
+            Err(payload) => payload,
+        };
+        let panic = panic_message(&*payload);
+        let Some(m) = message else {
+            tracing::error!("Handler panicked: {panic}");
+            return Ok(());
+        };
+        tracing::error!(
+            "Handler panicked on {:?} in chat {}: {panic}",
+            command_context(m.text()),
+            m.chat().id()
+        );
+        // Only commands are answered, never arbitrary messages.
+        let is_command = m.sender().is_some_and(|sender| sender.id() == self.me.id())
+            && self.route(m.text(), m.chat().id()) != RoutedCommand::None;
+        if self.conf.report_panics && is_command {
+            self.respond(&m, format!("笨！\nThe command panicked: {panic}"))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "static");
+        let payload = std::panic::catch_unwind(|| panic!("formatted {}", 1)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "formatted 1");
+        let payload = std::panic::catch_unwind(|| std::panic::panic_any(1)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "unknown panic");
+    }
+
+    #[test]
+    fn test_command_context() {
+        assert_eq!(command_context(",ls -l"), ",ls -l");
+        assert_eq!(command_context("r#let a = 1;\nlet b = 2;"), "r#let a = 1;…");
+        assert_eq!(command_context(&"x".repeat(100)).chars().count(), 81);
+    }
+}
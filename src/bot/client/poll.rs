@@ -0,0 +1,207 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use grammers_client::{grammers_tl_types as tl, types::Message};
+
+use super::{TomorinClient, shellwords::split_args};
+use crate::error::Result;
+
+/// Telegram's limits on polls.
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+const MAX_QUESTION_CHARS: usize = 255;
+const MAX_OPTION_CHARS: usize = 100;
+
+#[derive(Debug, PartialEq)]
+struct PollSpec {
+    question: String,
+    options: Vec<String>,
+    /// The index of the correct option, for a quiz.
+    quiz: Option<usize>,
+}
+
+fn parse_poll(args: &str) -> Result<PollSpec, String> {
+    let mut quiz = None;
+    let mut words = Vec::new();
+    let mut split = split_args(args)?.into_iter();
+    while let Some(word) = split.next() {
+        if word == "--quiz" {
+            let index = split
+                .next()
+                .and_then(|index| index.parse::<usize>().ok())
+                .filter(|index| *index > 0)
+                .ok_or("--quiz needs the number of the correct option, from 1")?;
+            quiz = Some(index - 1);
+        } else {
+            words.push(word);
+        }
+    }
+
+    let mut words = words.into_iter();
+    let question = words
+        .next()
+        .filter(|q| !q.is_empty())
+        .ok_or("No question given")?;
+    let options: Vec<_> = words.collect();
+    if !(MIN_OPTIONS..=MAX_OPTIONS).contains(&options.len()) {
+        return Err(format!(
+            "A poll needs {MIN_OPTIONS} to {MAX_OPTIONS} options, got {}",
+            options.len()
+        ));
+    }
+    if question.chars().count() > MAX_QUESTION_CHARS {
+        return Err(format!(
+            "The question is longer than {MAX_QUESTION_CHARS} characters"
+        ));
+    }
+    if options
+        .iter()
+        .any(|option| option.is_empty() || option.chars().count() > MAX_OPTION_CHARS)
+    {
+        return Err(format!(
+            "Options must have 1 to {MAX_OPTION_CHARS} characters"
+        ));
+    }
+    if let Some(index) = quiz
+        && index >= options.len()
+    {
+        return Err(format!("There is no option {}", index + 1));
+    }
+    Ok(PollSpec {
+        question,
+        options,
+        quiz,
+    })
+}
+
+fn text(text: String) -> tl::enums::TextWithEntities {
+    tl::types::TextWithEntities {
+        text,
+        entities: Vec::new(),
+    }
+    .into()
+}
+
+impl PollSpec {
+    fn into_input_media(self) -> tl::enums::InputMedia {
+        let answers = self
+            .options
+            .into_iter()
+            .enumerate()
+            .map(|(i, option)| {
+                tl::types::PollAnswer {
+                    text: text(option),
+                    option: vec![i as u8],
+                }
+                .into()
+            })
+            .collect();
+        let poll = tl::types::Poll {
+            // Chosen by the server for new polls.
+            id: 0,
+            closed: false,
+            public_voters: false,
+            multiple_choice: false,
+            quiz: self.quiz.is_some(),
+            question: text(self.question),
+            answers,
+            close_period: None,
+            close_date: None,
+        };
+        tl::types::InputMediaPoll {
+            poll: poll.into(),
+            correct_answers: self.quiz.map(|index| vec![vec![index as u8]]),
+            solution: None,
+            solution_entities: None,
+        }
+        .into()
+    }
+}
+
+impl TomorinClient {
+    /// Send a native poll, or a quiz with `--quiz <n>`, to the current chat.
+    pub async fn handle_poll(&self, args: &str, m: &Message) -> Result<()> {
+        if args.is_empty() {
+            self.respond(
+                m,
+                "Usage: poll \"question\" \"option 1\" \"option 2\" ... [--quiz <n>]",
+            )
+            .await?;
+            return Ok(());
+        }
+        let spec = match parse_poll(args) {
+            Ok(spec) => spec,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+
+        let random_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as i64);
+        let request = tl::functions::messages::SendMedia {
+            silent: self.is_silent(m),
+            background: false,
+            clear_draft: false,
+            noforwards: false,
+            update_stickersets_order: false,
+            invert_media: false,
+            peer: m.chat().pack().to_input_peer(),
+            reply_to: None,
+            media: spec.into_input_media(),
+            message: String::new(),
+            random_id,
+            reply_markup: None,
+            entities: None,
+            schedule_date: None,
+            send_as: None,
+            quick_reply_shortcut: None,
+            effect: None,
+        };
+        self.pace(m.chat().id(), "send_media").await;
+        if let Err(e) = self.client.invoke(&request).await {
+            self.respond(m, format!("笨！\nFailed to send the poll: {e}"))
+                .await?;
+            return Ok(());
+        }
+        self.discard_response(m).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_poll() {
+        assert_eq!(
+            parse_poll(r#""2 + 2?" 3 4 5 --quiz 2"#),
+            Ok(PollSpec {
+                question: "2 + 2?".into(),
+                options: vec!["3".into(), "4".into(), "5".into()],
+                quiz: Some(1),
+            })
+        );
+        assert_eq!(
+            parse_poll(r#"Lunch? yes no"#).map(|spec| spec.quiz),
+            Ok(None)
+        );
+        assert_eq!(
+            parse_poll(r#"'Best lang?' "C \"plus\" plus" it\'s"#),
+            Ok(PollSpec {
+                question: "Best lang?".into(),
+                options: vec![r#"C "plus" plus"#.into(), "it's".into()],
+                quiz: None,
+            })
+        );
+        assert!(parse_poll(r#""open a b"#).is_err());
+        assert!(parse_poll("").is_err());
+        assert!(parse_poll("Lunch? yes").is_err());
+        assert!(parse_poll("Q 1 2 3 4 5 6 7 8 9 10 11").is_err());
+        assert!(parse_poll("Q a b --quiz 3").is_err());
+        assert!(parse_poll("Q a b --quiz 0").is_err());
+        assert!(parse_poll("Q a b --quiz").is_err());
+        assert!(parse_poll(r#"Q a """#).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, path::Path};
+
+use grammers_client::types::Message;
+use serde_json::{Value, json};
+
+use super::{TomorinClient, route::Triggers, state::Inspect};
+use crate::error::Result;
+
+/// Command prefixes overriding the global defaults in single chats.
+#[derive(Debug, Default)]
+pub struct ChatPrefixes {
+    chats: HashMap<i64, Vec<String>>,
+}
+
+impl ChatPrefixes {
+    pub fn load(path: &Path) -> Self {
+        let chats = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(chats) => Some(chats),
+                Err(e) => {
+                    tracing::warn!("ignoring malformed prefix file {path:?}: {e}");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self { chats }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string(&self.chats)?)
+    }
+
+    /// The prefixes in effect in `chat`.
+    pub fn get(&self, chat: i64) -> Vec<String> {
+        self.chats.get(&chat).cloned().unwrap_or_else(|| {
+            Triggers::DEFAULT
+                .cmd_prefixes
+                .iter()
+                .map(|p| p.to_string())
+                .collect()
+        })
+    }
+
+    /// Set the prefixes of `chat`, dropping the override if they match the defaults again.
+    fn set(&mut self, chat: i64, prefixes: Vec<String>) {
+        let sorted = |mut prefixes: Vec<String>| {
+            prefixes.sort();
+            prefixes
+        };
+        if sorted(prefixes.clone()) == sorted(Self::default().get(chat)) {
+            self.chats.remove(&chat);
+        } else {
+            self.chats.insert(chat, prefixes);
+        }
+    }
+
+    /// Returns `false` if `prefix` was already in effect.
+    pub fn add(&mut self, chat: i64, prefix: &str) -> bool {
+        let mut prefixes = self.get(chat);
+        if prefixes.iter().any(|p| p == prefix) {
+            return false;
+        }
+        prefixes.push(prefix.to_string());
+        self.set(chat, prefixes);
+        true
+    }
+
+    /// Returns `false` if `prefix` was not in effect.
+    pub fn remove(&mut self, chat: i64, prefix: &str) -> bool {
+        let mut prefixes = self.get(chat);
+        let len = prefixes.len();
+        prefixes.retain(|p| p != prefix);
+        if prefixes.len() == len {
+            return false;
+        }
+        self.set(chat, prefixes);
+        true
+    }
+}
+
+/// Whether `prefix` would shadow, or be shadowed by, one of the other triggers.
+fn clashes(prefix: &str) -> bool {
+    let triggers = Triggers::DEFAULT;
+    [
+        triggers.repeat,
+        triggers.eval,
+        triggers.notebook,
+        triggers.help,
+        triggers.status,
+    ]
+    .into_iter()
+    .chain(triggers.scripts.iter().map(|&(t, _)| t))
+    .any(|t| t.starts_with(prefix) || prefix.starts_with(t))
+}
+
+impl TomorinClient {
+    /// Change the prefix overrides with `f` and persist the result.
+    fn edit_prefixes<T>(&self, f: impl FnOnce(&mut ChatPrefixes) -> T) -> T {
+        let mut prefixes = self.prefixes.lock().unwrap();
+        let result = f(&mut prefixes);
+        if let Err(e) = prefixes.save(Path::new(Self::PREFIXES)) {
+            tracing::warn!("Failed to save prefixes to {}: {e}", Self::PREFIXES);
+        }
+        result
+    }
+
+    pub async fn handle_prefix(&self, args: &str, m: &Message) -> Result<()> {
+        let chat = m.chat().id();
+        let reply = match args.split_once(' ') {
+            None if args.is_empty() => {
+                let prefixes = self.prefixes.lock().unwrap().get(chat);
+                format!("Prefixes here: {}", prefixes.join(" "))
+            }
+            Some(("add", p)) if clashes(p) => format!("笨！\n{p} clashes with another trigger"),
+            Some(("add", p)) if !p.is_empty() => {
+                if self.edit_prefixes(|prefixes| prefixes.add(chat, p)) {
+                    format!("Added prefix {p}")
+                } else {
+                    format!("{p} is already a prefix here")
+                }
+            }
+            Some(("remove", p)) if self.prefixes.lock().unwrap().get(chat) == [p] => {
+                "笨！\nCan't remove the last prefix".to_string()
+            }
+            Some(("remove", p)) if !p.is_empty() => {
+                if self.edit_prefixes(|prefixes| prefixes.remove(chat, p)) {
+                    format!("Removed prefix {p}")
+                } else {
+                    format!("{p} is not a prefix here")
+                }
+            }
+            _ => "Usage: prefix [add <p> | remove <p>]".to_string(),
+        };
+        self.respond(m, reply).await?;
+        Ok(())
+    }
+}
+
+impl Inspect for ChatPrefixes {
+    fn size(&self) -> usize {
+        self.chats.len()
+    }
+
+    fn dump(&self) -> Value {
+        json!(self.chats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_prefixes() {
+        let mut prefixes = ChatPrefixes::default();
+        assert_eq!(prefixes.get(1), [",", "，", ".", "。"]);
+
+        assert!(prefixes.remove(1, "."));
+        assert!(!prefixes.remove(1, "."));
+        assert_eq!(prefixes.get(1), [",", "，", "。"]);
+        assert!(!prefixes.chats.contains_key(&2));
+
+        assert!(prefixes.add(1, "!"));
+        assert!(!prefixes.add(1, "!"));
+        assert_eq!(prefixes.get(1), [",", "，", "。", "!"]);
+
+        assert!(prefixes.remove(1, "!"));
+        assert!(prefixes.add(1, "."));
+        assert!(prefixes.chats.is_empty());
+    }
+
+    #[test]
+    fn test_clashes() {
+        assert!(clashes("r"));
+        assert!(clashes("h#"));
+        assert!(clashes("s#!"));
+        assert!(clashes("py"));
+        assert!(clashes("n"));
+        assert!(clashes("nb#"));
+        assert!(clashes("+"));
+        assert!(!clashes("!"));
+        assert!(!clashes("#"));
+    }
+}
@@ -0,0 +1,68 @@
+use grammers_client::{grammers_tl_types as tl, types::Message};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Telegram rejects bios longer than this for non-premium accounts.
+const MAX_BIO_LEN: usize = 70;
+/// Both the first and the last name are capped at this length.
+const MAX_NAME_LEN: usize = 64;
+
+impl TomorinClient {
+    pub async fn handle_setbio(&self, bio: &str, m: &Message) -> Result<()> {
+        let len = bio.chars().count();
+        if len > MAX_BIO_LEN {
+            self.respond(m, format!("Bio too long: {len}/{MAX_BIO_LEN} chars"))
+                .await?;
+            return Ok(());
+        }
+
+        let request = tl::functions::account::UpdateProfile {
+            first_name: None,
+            last_name: None,
+            about: Some(bio.to_string()),
+        };
+        self.update_profile(&request, m, "Bio updated").await
+    }
+
+    pub async fn handle_setname(&self, args: &str, m: &Message) -> Result<()> {
+        let (first, last) = match args.split_once(char::is_whitespace) {
+            Some((first, last)) => (first, last.trim()),
+            None => (args, ""),
+        };
+
+        if first.is_empty() {
+            self.respond(m, "Usage: setname <first> [last]").await?;
+            return Ok(());
+        }
+
+        for (field, value) in [("First name", first), ("Last name", last)] {
+            let len = value.chars().count();
+            if len > MAX_NAME_LEN {
+                self.respond(m, format!("{field} too long: {len}/{MAX_NAME_LEN} chars"))
+                    .await?;
+                return Ok(());
+            }
+        }
+
+        let request = tl::functions::account::UpdateProfile {
+            first_name: Some(first.to_string()),
+            last_name: Some(last.to_string()),
+            about: None,
+        };
+        self.update_profile(&request, m, "Name updated").await
+    }
+
+    async fn update_profile(
+        &self,
+        request: &tl::functions::account::UpdateProfile,
+        m: &Message,
+        done: &str,
+    ) -> Result<()> {
+        match self.invoke(request).await {
+            Ok(_) => self.respond(m, done).await?,
+            Err(e) => self.respond(m, format!("笨！\n{e}")).await?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde_json::{Value, json};
+
+use super::{TomorinClient, state::Inspect};
+
+/// A token bucket handing out reservations: taking a token never fails, it tells how
+/// long to wait until the token would have been there.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    per_sec: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// Refill at `rate` tokens per minute, allowing bursts of ten seconds' worth of them.
+    fn per_minute(rate: u32, now: Instant) -> Self {
+        let per_sec = f64::from(rate) / 60.0;
+        let capacity = (per_sec * 10.0).max(1.0);
+        Self {
+            capacity,
+            per_sec,
+            tokens: capacity,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.capacity);
+        self.last = now;
+    }
+
+    fn take(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_sec)
+        }
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+}
+
+/// Paces outgoing sends and edits, across all chats and per chat. A rate of 0 disables
+/// the respective limit.
+#[derive(Debug)]
+pub struct Outbound {
+    chat_rate: u32,
+    global: Option<TokenBucket>,
+    chats: HashMap<i64, TokenBucket>,
+}
+
+impl Outbound {
+    /// Idle chats are forgotten once this many are tracked.
+    const MAX_CHATS: usize = 1024;
+
+    pub fn new(global_rate: u32, chat_rate: u32) -> Self {
+        let now = Instant::now();
+        Self {
+            chat_rate,
+            global: (global_rate > 0).then(|| TokenBucket::per_minute(global_rate, now)),
+            chats: HashMap::new(),
+        }
+    }
+
+    /// Reserve a send to `chat` at `now`, returning how long to wait before making it.
+    fn reserve(&mut self, chat: i64, now: Instant) -> Duration {
+        let global = self
+            .global
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.take(now));
+        if self.chat_rate == 0 {
+            return global;
+        }
+
+        if self.chats.len() >= Self::MAX_CHATS {
+            self.chats.retain(|_, bucket| !bucket.is_full(now));
+        }
+        let chat_rate = self.chat_rate;
+        let chat = self
+            .chats
+            .entry(chat)
+            .or_insert_with(|| TokenBucket::per_minute(chat_rate, now))
+            .take(now);
+        global.max(chat)
+    }
+}
+
+impl TomorinClient {
+    /// Wait for the outbound limits to allow another `method` call in `chat`, and count it.
+    pub async fn pace(&self, chat: i64, method: &'static str) {
+        let wait = self.outbound.lock().unwrap().reserve(chat, Instant::now());
+        if !wait.is_zero() {
+            tracing::debug!("pacing {method} in chat {chat} for {wait:?}");
+            tokio::time::sleep(wait).await;
+        }
+        self.count_call(method);
+    }
+}
+
+impl Inspect for Outbound {
+    fn size(&self) -> usize {
+        self.chats.len()
+    }
+
+    /// The tokens left in each bucket, as of their last use.
+    fn dump(&self) -> Value {
+        let chats: HashMap<_, _> = self
+            .chats
+            .iter()
+            .map(|(chat, bucket)| (chat, bucket.tokens.floor()))
+            .collect();
+        json!({
+            "chat_rate": self.chat_rate,
+            "global": self.global.as_ref().map(|bucket| bucket.tokens.floor()),
+            "chats": chats,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        // One token per second, bursts of ten.
+        let mut bucket = TokenBucket::per_minute(60, now);
+        for _ in 0..10 {
+            assert_eq!(bucket.take(now), Duration::ZERO);
+        }
+        assert_eq!(bucket.take(now), Duration::from_secs(1));
+        assert_eq!(bucket.take(now), Duration::from_secs(2));
+
+        // The two reservations are paid back after two seconds, the third refills.
+        let later = now + Duration::from_secs(3);
+        assert_eq!(bucket.take(later), Duration::ZERO);
+        assert_eq!(bucket.take(later), Duration::from_secs(1));
+
+        let idle = now + Duration::from_secs(60);
+        assert!(bucket.is_full(idle));
+    }
+
+    #[test]
+    fn test_outbound() {
+        let now = Instant::now();
+        let mut outbound = Outbound::new(0, 6);
+        // Six per minute allow a single send at once per chat.
+        assert_eq!(outbound.reserve(1, now), Duration::ZERO);
+        assert_eq!(outbound.reserve(1, now), Duration::from_secs(10));
+        assert_eq!(outbound.reserve(2, now), Duration::ZERO);
+
+        let mut outbound = Outbound::new(6, 0);
+        assert_eq!(outbound.reserve(1, now), Duration::ZERO);
+        assert_eq!(outbound.reserve(2, now), Duration::from_secs(10));
+
+        let mut unlimited = Outbound::new(0, 0);
+        for chat in 0..100 {
+            assert_eq!(unlimited.reserve(chat, now), Duration::ZERO);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use grammers_client::{grammers_tl_types as tl, types::Message};
+use serde::Deserialize;
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Longest debug output sent back, comfortably below Telegram's message limit.
+const MAX_OUTPUT_CHARS: usize = 4000;
+
+/// A raw TL call such as `{"_": "help.getNearestDc"}`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct RawCall {
+    #[serde(rename = "_")]
+    method: String,
+}
+
+/// The TL functions `tl` may invoke, limited to getters without side effects.
+#[derive(Debug, PartialEq)]
+enum SafeCall {
+    Config,
+    NearestDc,
+    State,
+    FullSelf,
+}
+
+impl SafeCall {
+    const NAMES: [&str; 4] = [
+        "help.getConfig",
+        "help.getNearestDc",
+        "updates.getState",
+        "users.getFullUser",
+    ];
+
+    fn parse(json: &str) -> std::result::Result<Self, String> {
+        let call: RawCall = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        match call.method.as_str() {
+            "help.getConfig" => Ok(Self::Config),
+            "help.getNearestDc" => Ok(Self::NearestDc),
+            "updates.getState" => Ok(Self::State),
+            "users.getFullUser" => Ok(Self::FullSelf),
+            method => Err(format!(
+                "Unsupported method {method}, expected one of: {}",
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl TomorinClient {
+    pub async fn handle_tl(&self, args: &str, m: &Message) -> Result<()> {
+        if !self.conf.enable_raw_tl {
+            self.respond(
+                m,
+                "Raw TL calls are disabled, set `enable-raw-tl true` in the config",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let call = match SafeCall::parse(args) {
+            Ok(call) => call,
+            Err(e) => {
+                self.respond(
+                    m,
+                    format!("Usage: tl {{\"_\": \"help.getNearestDc\"}}\n{e}"),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let resp = match call {
+            SafeCall::Config => self
+                .invoke(&tl::functions::help::GetConfig {})
+                .await
+                .map(|r| format!("{r:?}")),
+            SafeCall::NearestDc => self
+                .invoke(&tl::functions::help::GetNearestDc {})
+                .await
+                .map(|r| format!("{r:?}")),
+            SafeCall::State => self
+                .invoke(&tl::functions::updates::GetState {})
+                .await
+                .map(|r| format!("{r:?}")),
+            SafeCall::FullSelf => self
+                .invoke(&tl::functions::users::GetFullUser {
+                    id: tl::enums::InputUser::UserSelf,
+                })
+                .await
+                .map(|r| format!("{r:?}")),
+        };
+
+        match resp {
+            Ok(resp) => {
+                let resp = match resp.char_indices().nth(MAX_OUTPUT_CHARS) {
+                    Some((end, _)) => format!("{}...", &resp[..end]),
+                    None => resp,
+                };
+                Self::edit_pre_msg(&self.responder(m), &resp, "TL").await
+            }
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_call_parse() {
+        assert_eq!(
+            SafeCall::parse(r#"{"_": "help.getNearestDc"}"#),
+            Ok(SafeCall::NearestDc)
+        );
+        assert_eq!(
+            SafeCall::parse(r#" {"_":"users.getFullUser"} "#),
+            Ok(SafeCall::FullSelf)
+        );
+        assert!(
+            SafeCall::parse(r#"{"_": "account.deleteAccount"}"#)
+                .unwrap_err()
+                .starts_with("Unsupported method account.deleteAccount")
+        );
+        assert!(SafeCall::parse("help.getConfig").is_err());
+        assert!(SafeCall::parse("").is_err());
+    }
+}
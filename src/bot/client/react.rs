@@ -0,0 +1,122 @@
+use grammers_client::{
+    InvocationError,
+    grammers_tl_types::enums::MessageEntity,
+    types::{InputReactions, Message},
+};
+
+use super::TomorinClient;
+use crate::conf::ReplyMode;
+use crate::error::Result;
+
+/// The reaction asked for by the arguments of `react`.
+#[derive(Debug, PartialEq, Eq)]
+enum Reaction<'a> {
+    Emoji(&'a str),
+    /// A custom emoji, by the id of its document.
+    Custom(i64),
+}
+
+impl From<Reaction<'_>> for InputReactions {
+    fn from(reaction: Reaction<'_>) -> Self {
+        match reaction {
+            Reaction::Emoji(emoji) => InputReactions::emoticon(emoji),
+            Reaction::Custom(id) => InputReactions::custom_emoji(id),
+        }
+    }
+}
+
+/// Pick the reaction from `args`, preferring a custom emoji among `entities`, as those
+/// only show up as their placeholder emoji in the text.
+fn parse_reaction<'a>(args: &'a str, entities: &[MessageEntity]) -> Option<Reaction<'a>> {
+    let custom = entities.iter().find_map(|e| match e {
+        MessageEntity::CustomEmoji(emoji) => Some(emoji.document_id),
+        _ => None,
+    });
+    if let Some(id) = custom {
+        return Some(Reaction::Custom(id));
+    }
+
+    match args.trim() {
+        "" => None,
+        id if id.bytes().all(|b| b.is_ascii_digit()) => id.parse().ok().map(Reaction::Custom),
+        emoji => Some(Reaction::Emoji(emoji)),
+    }
+}
+
+impl TomorinClient {
+    /// React to the replied message, then remove the command.
+    pub async fn handle_react(&self, args: &str, m: &Message) -> Result<()> {
+        let entities = m.fmt_entities().map(Vec::as_slice).unwrap_or_default();
+        let Some(reaction) = parse_reaction(args, entities) else {
+            self.respond(m, "Usage: react <emoji | custom emoji id>")
+                .await?;
+            return Ok(());
+        };
+        let Some(reply) = self.get_reply(m).await? else {
+            self.respond(m, "Reply to a message to react to it").await?;
+            return Ok(());
+        };
+
+        self.count_call("send_reaction");
+        match self
+            .client
+            .send_reactions(reply.chat(), reply.id(), reaction)
+            .await
+        {
+            Ok(()) => {}
+            Err(InvocationError::Rpc(e)) => {
+                let reason = match e.name.as_str() {
+                    "REACTION_INVALID" | "REACTION_EMPTY" => "That reaction isn't available here",
+                    "PREMIUM_ACCOUNT_REQUIRED" => "Custom emoji reactions need Telegram Premium",
+                    "REACTIONS_TOO_MANY" => "The message has too many different reactions",
+                    "CHAT_ADMIN_REQUIRED" | "CHAT_WRITE_FORBIDDEN" => {
+                        "Reactions aren't allowed in this chat"
+                    }
+                    _ => return Err(InvocationError::Rpc(e).into()),
+                };
+                self.respond(m, format!("笨！\n{reason}")).await?;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        // In reply mode that only removes the output, the command itself goes as well.
+        self.discard_response(m).await?;
+        if self.conf.reply_mode == ReplyMode::Reply {
+            self.pace(m.chat().id(), "delete").await;
+            m.delete().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grammers_client::grammers_tl_types::types::{MessageEntityBold, MessageEntityCustomEmoji};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_reaction() {
+        assert_eq!(parse_reaction("👍", &[]), Some(Reaction::Emoji("👍")));
+        assert_eq!(parse_reaction(" 🔥 ", &[]), Some(Reaction::Emoji("🔥")));
+        assert_eq!(
+            parse_reaction("5368324170671202286", &[]),
+            Some(Reaction::Custom(5368324170671202286))
+        );
+        assert_eq!(parse_reaction("", &[]), None);
+
+        let custom = MessageEntity::CustomEmoji(MessageEntityCustomEmoji {
+            offset: 7,
+            length: 2,
+            document_id: 42,
+        });
+        assert_eq!(parse_reaction("👍", &[custom]), Some(Reaction::Custom(42)));
+
+        let bold = MessageEntity::Bold(MessageEntityBold {
+            offset: 0,
+            length: 5,
+        });
+        assert_eq!(parse_reaction("👍", &[bold]), Some(Reaction::Emoji("👍")));
+    }
+}
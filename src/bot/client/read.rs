@@ -0,0 +1,44 @@
+use grammers_client::{grammers_tl_types as tl, types::Message};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// The message the read marker is moved to: the replied one, or else the command itself.
+fn read_up_to(m: &Message) -> i32 {
+    m.reply_to_message_id().unwrap_or_else(|| m.id())
+}
+
+impl TomorinClient {
+    /// Mark the current chat as read, up to the replied message if any.
+    ///
+    /// Channels and supergroups keep their read marker apart from the other chats and
+    /// need their own RPC, which only says whether the marker moved.
+    pub async fn handle_read(&self, m: &Message) -> Result<()> {
+        let max_id = read_up_to(m);
+        let chat = m.chat().pack();
+        let advanced = match chat.try_to_input_channel() {
+            Some(channel) => {
+                self.invoke(&tl::functions::channels::ReadHistory { channel, max_id })
+                    .await
+            }
+            None => self
+                .invoke(&tl::functions::messages::ReadHistory {
+                    peer: chat.to_input_peer(),
+                    max_id,
+                })
+                .await
+                .map(
+                    |tl::enums::messages::AffectedMessages::Messages(affected)| {
+                        affected.pts_count > 0
+                    },
+                ),
+        };
+        let text = match advanced {
+            Ok(true) => format!("Marked read up to message {max_id}"),
+            Ok(false) => format!("Already read up to message {max_id}"),
+            Err(e) => format!("笨！\nFailed to mark the chat read: {e}"),
+        };
+        self.respond(m, text).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::process::Stdio;
+
+use grammers_client::types::Message;
+use tokio::process::Command;
+
+use super::TomorinClient;
+use crate::error::{BotError, Result};
+
+/// Split a trailing `> file` (or `>file`) off a shell command.
+///
+/// The `>` has to start a word and be followed by exactly one more word, anything else
+/// is passed to the program untouched. Returns an error for a `>` without a target.
+pub fn split_redirect(cmd: &str) -> Result<(&str, Option<&str>), &'static str> {
+    let Some(index) = cmd.rfind('>') else {
+        return Ok((cmd, None));
+    };
+    let (command, target) = (&cmd[..index], cmd[index + 1..].trim());
+    if !command.ends_with(char::is_whitespace) || target.contains(char::is_whitespace) {
+        return Ok((cmd, None));
+    }
+    if target.is_empty() {
+        return Err("Missing file name after >");
+    }
+    Ok((command.trim_end(), Some(target)))
+}
+
+impl TomorinClient {
+    /// Run `program` with its stdout written to the file `target`, reporting only the size
+    /// written, along with stderr if there was any.
+    pub async fn run_shell_to_file(
+        &self,
+        program: &str,
+        args: &[String],
+        target: &str,
+        m: &Message,
+    ) -> Result<()> {
+        let file = match std::fs::File::create(target) {
+            Ok(file) => file,
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to create {target}: {e}"))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        self.respond(m, "少女祈祷中......").await?;
+        let output = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(file)
+            .stderr(Stdio::piped())
+            .output()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{}", BotError::Spawn(e)))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let written = std::fs::metadata(target).map_or(0, |meta| meta.len());
+        let path = std::fs::canonicalize(target).unwrap_or_else(|_| target.into());
+        let mut resp = format!("Wrote {written} bytes to {}", path.display());
+        if let Some(code) = output.status.code()
+            && code != 0
+        {
+            resp.push_str(&format!("\nexit code {code}"));
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            resp.push_str(&format!("\n\n{}", stderr.trim_end()));
+        }
+        Self::edit_pre_msg(&self.responder(m), &resp, "StdErr").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_redirect() {
+        assert_eq!(split_redirect("ls -l"), Ok(("ls -l", None)));
+        assert_eq!(
+            split_redirect("ls -l > out.txt"),
+            Ok(("ls -l", Some("out.txt")))
+        );
+        assert_eq!(
+            split_redirect("ls -l >/tmp/out.txt"),
+            Ok(("ls -l", Some("/tmp/out.txt")))
+        );
+        assert_eq!(split_redirect("echo a>b"), Ok(("echo a>b", None)));
+        assert_eq!(split_redirect("echo > a b"), Ok(("echo > a b", None)));
+        assert_eq!(split_redirect("ls >"), Err("Missing file name after >"));
+    }
+}
@@ -0,0 +1,233 @@
+use grammers_client::{
+    InvocationError,
+    types::{Chat, Media, Message},
+};
+
+use super::{TomorinClient, membership::link_path};
+use crate::error::Result;
+
+/// A link to a single message, `t.me/<username>/<id>` or `t.me/c/<channel id>/<id>`.
+#[derive(Debug, PartialEq, Eq)]
+enum MessageLink<'a> {
+    Public { username: &'a str, id: i32 },
+    Private { channel_id: i64, id: i32 },
+}
+
+impl std::fmt::Display for MessageLink<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageLink::Public { username, id } => write!(f, "https://t.me/{username}/{id}"),
+            MessageLink::Private { channel_id, id } => {
+                write!(f, "https://t.me/c/{channel_id}/{id}")
+            }
+        }
+    }
+}
+
+/// The link to message `id` of `chat`, `None` for private chats and basic groups,
+/// whose messages can't be linked to.
+fn message_link(chat: &Chat, id: i32) -> Option<MessageLink<'_>> {
+    match chat {
+        Chat::Channel(channel) => Some(match channel.username() {
+            Some(username) => MessageLink::Public { username, id },
+            None => MessageLink::Private {
+                channel_id: channel.id(),
+                id,
+            },
+        }),
+        Chat::User(_) | Chat::Group(_) => None,
+    }
+}
+
+fn parse_message_link(link: &str) -> Option<MessageLink<'_>> {
+    let path = link_path(link)?;
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut segments = path.trim_end_matches('/').split('/');
+
+    let chat = segments.next()?;
+    let link = if chat == "c" {
+        let channel_id = segments.next()?.parse().ok()?;
+        // Links into forum topics have the topic id in between, so take the last segment.
+        let id = segments.next_back()?.parse().ok()?;
+        MessageLink::Private { channel_id, id }
+    } else {
+        let valid = chat.len() >= 4 && chat.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return None;
+        }
+        let id = segments.next_back()?.parse().ok()?;
+        MessageLink::Public { username: chat, id }
+    };
+    Some(link)
+}
+
+fn media_kind(media: &Media) -> String {
+    match media {
+        Media::Photo(_) => "photo".to_string(),
+        Media::Document(doc) if doc.name().is_empty() => "document".to_string(),
+        Media::Document(doc) => format!("document {}", doc.name()),
+        Media::Sticker(_) => "sticker".to_string(),
+        Media::Contact(_) => "contact".to_string(),
+        Media::Poll(_) => "poll".to_string(),
+        Media::Geo(_) | Media::GeoLive(_) => "location".to_string(),
+        Media::Dice(_) => "dice".to_string(),
+        Media::Venue(_) => "venue".to_string(),
+        Media::WebPage(_) => "link preview".to_string(),
+        _ => "media".to_string(),
+    }
+}
+
+impl TomorinClient {
+    /// Find a private channel by id among our dialogs, the only place its access hash is known.
+    pub(super) async fn find_dialog_chat(
+        &self,
+        channel_id: i64,
+    ) -> Result<Option<Chat>, InvocationError> {
+        self.count_call("iter_dialogs");
+        let mut dialogs = self.client.iter_dialogs();
+        while let Some(dialog) = dialogs.next().await? {
+            if dialog.chat().id() == channel_id {
+                return Ok(Some(dialog.chat().clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn handle_resolve(&self, link: &str, m: &Message) -> Result<()> {
+        let Some(link) = parse_message_link(link) else {
+            self.respond(m, "Usage: resolve <t.me/channel/id | t.me/c/chat/id>")
+                .await?;
+            return Ok(());
+        };
+
+        self.respond(m, "少女祈祷中......").await?;
+        let (chat, id) = match link {
+            MessageLink::Public { username, id } => {
+                self.count_call("resolve_username");
+                match self.client.resolve_username(username).await {
+                    Ok(Some(chat)) => (chat, id),
+                    Ok(None) => {
+                        self.respond(m, format!("笨！\nNo chat named @{username}"))
+                            .await?;
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        self.respond(m, format!("笨！\nFailed to resolve @{username}: {e}"))
+                            .await?;
+                        return Ok(());
+                    }
+                }
+            }
+            MessageLink::Private { channel_id, id } => {
+                match self.find_dialog_chat(channel_id).await {
+                    Ok(Some(chat)) => (chat, id),
+                    Ok(None) => {
+                        self.respond(m, "笨！\nNot a member of this private chat")
+                            .await?;
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        self.respond(m, format!("笨！\n{e}")).await?;
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        self.count_call("get_messages_by_id");
+        let linked = match self.client.get_messages_by_id(&chat, &[id]).await {
+            Ok(mut messages) => messages.pop().flatten(),
+            Err(InvocationError::Rpc(e)) if e.name == "CHANNEL_PRIVATE" => {
+                self.respond(m, "笨！\nNot a member of this private chat")
+                    .await?;
+                return Ok(());
+            }
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+        let Some(linked) = linked else {
+            self.respond(m, format!("笨！\nNo message #{id} in {}", chat.name()))
+                .await?;
+            return Ok(());
+        };
+
+        let mut info = format!(
+            "{} #{id} · {}",
+            chat.name(),
+            linked.date().format("%Y-%m-%d %H:%M UTC")
+        );
+        if let Some(media) = linked.media() {
+            info.push_str(&format!("\n[{}]", media_kind(&media)));
+        }
+        if !linked.text().is_empty() {
+            info.push_str("\n\n");
+            info.push_str(linked.text());
+        }
+        self.respond(m, info).await?;
+        Ok(())
+    }
+
+    /// Show the `t.me` link to the replied message.
+    pub async fn handle_link(&self, m: &Message) -> Result<()> {
+        let Some(reply) = self.get_reply(m).await? else {
+            self.respond(m, "Reply to a message to get its link")
+                .await?;
+            return Ok(());
+        };
+        let chat = reply.chat();
+        match message_link(&chat, reply.id()) {
+            Some(link) => self.respond(m, link.to_string()).await?,
+            None => {
+                self.respond(
+                    m,
+                    "笨！\nMessages in private chats and basic groups have no links",
+                )
+                .await?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message_link() {
+        assert_eq!(
+            parse_message_link("https://t.me/rust_lang/456"),
+            Some(MessageLink::Public {
+                username: "rust_lang",
+                id: 456
+            })
+        );
+        assert_eq!(
+            parse_message_link("t.me/c/123456/789?single"),
+            Some(MessageLink::Private {
+                channel_id: 123456,
+                id: 789
+            })
+        );
+        assert_eq!(
+            parse_message_link("https://t.me/c/123456/2/789"),
+            Some(MessageLink::Private {
+                channel_id: 123456,
+                id: 789
+            })
+        );
+        assert_eq!(parse_message_link("https://t.me/rust_lang"), None);
+        assert_eq!(parse_message_link("https://t.me/c/abc/1"), None);
+        assert_eq!(parse_message_link("https://t.me/+AbC_123"), None);
+        assert_eq!(parse_message_link("https://example.com/rust_lang/1"), None);
+    }
+
+    #[test]
+    fn test_message_link_display() {
+        for link in ["https://t.me/rust_lang/456", "https://t.me/c/123456/789"] {
+            assert_eq!(parse_message_link(link).unwrap().to_string(), link);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+};
+
+use grammers_client::{InputMessage, InvocationError, types::Message};
+use serde_json::{Map, Value};
+
+use super::{
+    TomorinClient,
+    dedup::MessageKey,
+    message::{MessageOps, Outgoing},
+    state::{Inspect, key_name},
+};
+use crate::{
+    conf::{ReplyMode, Truncation},
+    error::Result,
+};
+
+/// The replies sent to recent commands in reply mode, so further output edits them.
+#[derive(Debug, Default)]
+pub struct Replies {
+    order: VecDeque<MessageKey>,
+    replies: HashMap<MessageKey, Message>,
+}
+
+impl Replies {
+    const CAPACITY: usize = 256;
+
+    fn get(&self, key: &MessageKey) -> Option<Message> {
+        self.replies.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: MessageKey, reply: Message) {
+        if self.replies.insert(key, reply).is_some() {
+            return;
+        }
+
+        if self.order.len() >= Self::CAPACITY
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.replies.remove(&oldest);
+        }
+        self.order.push_back(key);
+    }
+
+    fn remove(&mut self, key: &MessageKey) -> Option<Message> {
+        self.order.retain(|k| k != key);
+        self.replies.remove(key)
+    }
+}
+
+/// A command message whose edits go through [`TomorinClient::respond`].
+pub struct Responder {
+    bot: TomorinClient,
+    m: Message,
+}
+
+impl MessageOps for Responder {
+    fn text(&self) -> &str {
+        self.m.text()
+    }
+
+    fn chat_id(&self) -> i64 {
+        self.m.chat().id()
+    }
+
+    fn edit(
+        &self,
+        new: impl Into<Outgoing> + Send,
+    ) -> impl Future<Output = Result<(), InvocationError>> + Send {
+        self.bot.respond(&self.m, new)
+    }
+
+    async fn get_reply(&self) -> Result<Option<Self>, InvocationError> {
+        Ok(self.bot.get_reply(&self.m).await?.map(|m| Responder {
+            bot: self.bot.clone(),
+            m,
+        }))
+    }
+
+    fn truncation(&self) -> Truncation {
+        *self.bot.truncation.lock().unwrap()
+    }
+
+    fn wrap_columns(&self) -> Option<usize> {
+        self.bot.conf.wrap_columns
+    }
+}
+
+impl TomorinClient {
+    /// Show `content` as the output of the command `m`, according to the reply mode:
+    /// either by editing the command itself, or by sending a reply to it and editing
+    /// that reply on further output.
+    pub async fn respond(
+        &self,
+        m: &Message,
+        content: impl Into<Outgoing>,
+    ) -> Result<(), InvocationError> {
+        let mut content = content.into();
+        let text = content.text.clone();
+        let full_output = content.full_output.take();
+        let content = InputMessage::from(content).silent(self.is_silent(m));
+        match self.conf.reply_mode {
+            ReplyMode::Edit => {
+                self.pace(m.chat().id(), "edit").await;
+                m.edit(content).await?;
+                self.mark_written(m, &text);
+            }
+            ReplyMode::Reply => {
+                let key = (m.chat().id(), m.id());
+                let sent = self.replies.lock().unwrap().get(&key);
+                match sent {
+                    Some(reply) => {
+                        self.pace(key.0, "edit").await;
+                        reply.edit(content).await?;
+                        self.mark_written(&reply, &text);
+                    }
+                    None => {
+                        self.pace(key.0, "send_message").await;
+                        let reply = m.reply(content).await?;
+                        self.mark_written(&reply, &text);
+                        self.replies.lock().unwrap().insert(key, reply);
+                    }
+                }
+            }
+        }
+        if let Some(output) = full_output {
+            self.long_outputs
+                .lock()
+                .unwrap()
+                .record((m.chat().id(), m.id()), &output);
+        }
+        Ok(())
+    }
+
+    /// Remove the textual output of `m`, e.g. once it was superseded by a media message.
+    ///
+    /// In edit mode that is the command message itself.
+    pub async fn discard_response(&self, m: &Message) -> Result<(), InvocationError> {
+        match self.conf.reply_mode {
+            ReplyMode::Edit => {
+                self.pace(m.chat().id(), "delete").await;
+                m.delete().await
+            }
+            ReplyMode::Reply => {
+                let reply = self
+                    .replies
+                    .lock()
+                    .unwrap()
+                    .remove(&(m.chat().id(), m.id()));
+                match reply {
+                    Some(reply) => {
+                        self.pace(m.chat().id(), "delete").await;
+                        reply.delete().await
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Whether the output of `m` is sent without notification.
+    pub fn is_silent(&self, m: &Message) -> bool {
+        self.conf.silent_output
+            || self
+                .quiet
+                .lock()
+                .unwrap()
+                .contains(&(m.chat().id(), m.id()))
+    }
+
+    /// Run `cmd` like any other command, but with its output sent silently.
+    pub async fn handle_quiet(&self, cmd: &str, m: &Message) -> Result<()> {
+        if cmd.is_empty() {
+            self.respond(m, "Usage: quiet <command>").await?;
+            return Ok(());
+        }
+
+        let key = (m.chat().id(), m.id());
+        self.quiet.lock().unwrap().insert(key);
+        let result = Box::pin(self.handle_cmd(cmd, m)).await;
+        self.quiet.lock().unwrap().remove(&key);
+        result
+    }
+
+    /// Show or switch which lines of long output are kept, until the next restart.
+    pub async fn handle_truncate(&self, args: &str, m: &Message) -> Result<()> {
+        let truncation = match args {
+            "" => *self.truncation.lock().unwrap(),
+            "head" => Truncation::Head,
+            "tail" => Truncation::Tail,
+            _ => {
+                self.respond(m, "Usage: truncate [head|tail]").await?;
+                return Ok(());
+            }
+        };
+        *self.truncation.lock().unwrap() = truncation;
+
+        let kept = match truncation {
+            Truncation::Head => "first",
+            Truncation::Tail => "last",
+        };
+        self.respond(m, format!("Long output keeps its {kept} lines"))
+            .await?;
+        Ok(())
+    }
+
+    /// Wrap `m` for the handlers written against [`MessageOps`].
+    pub fn responder(&self, m: &Message) -> Responder {
+        Responder {
+            bot: self.clone(),
+            m: m.clone(),
+        }
+    }
+}
+
+impl Inspect for Replies {
+    fn size(&self) -> usize {
+        self.replies.len()
+    }
+
+    /// The id of the reply sent to each command.
+    fn dump(&self) -> Value {
+        self.order
+            .iter()
+            .filter_map(|key| Some((key_name(*key), self.replies.get(key)?.id().into())))
+            .collect::<Map<_, _>>()
+            .into()
+    }
+}
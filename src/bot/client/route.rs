@@ -0,0 +1,131 @@
+use super::script::Language;
+
+/// The markers that decide which handler a message goes to.
+#[derive(Clone, Debug)]
+pub struct Triggers<'a> {
+    pub repeat: &'a str,
+    pub eval: &'a str,
+    pub notebook: &'a str,
+    pub scripts: &'a [(&'a str, Language)],
+    pub cmd_prefixes: &'a [&'a str],
+    pub help: &'a str,
+    pub status: &'a str,
+}
+
+impl Triggers<'static> {
+    pub const DEFAULT: Self = Self {
+        repeat: "+",
+        eval: "r#",
+        notebook: "nb#",
+        scripts: &[
+            ("py#", Language::Python),
+            ("js#", Language::JavaScript),
+            ("sh#", Language::Shell),
+        ],
+        cmd_prefixes: &[",", "，", ".", "。"],
+        help: "h#",
+        status: "s#",
+    };
+}
+
+/// The handler a message is routed to, with the text it should work on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoutedCommand<'t> {
+    Repeat,
+    Eval(&'t str),
+    Notebook(&'t str),
+    Script(Language, &'t str),
+    Cmd(&'t str),
+    Help,
+    Status,
+    None,
+}
+
+/// Decide which handler `text` is meant for.
+///
+/// The order matters: a repeat must match exactly, and eval and the other snippet
+/// triggers win over the command prefixes.
+pub fn route<'t>(text: &'t str, triggers: &Triggers) -> RoutedCommand<'t> {
+    if text == triggers.repeat {
+        return RoutedCommand::Repeat;
+    }
+
+    if text.starts_with(triggers.eval) {
+        return RoutedCommand::Eval(text.trim_start_matches(triggers.eval));
+    }
+
+    if let Some(code) = text.strip_prefix(triggers.notebook) {
+        return RoutedCommand::Notebook(code);
+    }
+
+    for &(trigger, language) in triggers.scripts {
+        if let Some(code) = text.strip_prefix(trigger) {
+            return RoutedCommand::Script(language, code);
+        }
+    }
+
+    for prefix in triggers.cmd_prefixes {
+        if text.starts_with(prefix) {
+            return RoutedCommand::Cmd(text.trim_start_matches(prefix));
+        }
+    }
+
+    if text.starts_with(triggers.help) {
+        return RoutedCommand::Help;
+    }
+
+    if text.starts_with(triggers.status) {
+        return RoutedCommand::Status;
+    }
+
+    RoutedCommand::None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        let cases = [
+            ("", RoutedCommand::None),
+            ("+", RoutedCommand::Repeat),
+            ("++", RoutedCommand::None),
+            ("+1", RoutedCommand::None),
+            ("r#", RoutedCommand::Eval("")),
+            ("r#1 + 1", RoutedCommand::Eval("1 + 1")),
+            ("r#,ls", RoutedCommand::Eval(",ls")),
+            ("nb#", RoutedCommand::Notebook("")),
+            ("nb#1 + 1", RoutedCommand::Notebook("1 + 1")),
+            (",nb#1", RoutedCommand::Cmd("nb#1")),
+            (
+                "py#print(1)",
+                RoutedCommand::Script(Language::Python, "print(1)"),
+            ),
+            (
+                "js#1 + 1",
+                RoutedCommand::Script(Language::JavaScript, "1 + 1"),
+            ),
+            (
+                "sh#echo hi",
+                RoutedCommand::Script(Language::Shell, "echo hi"),
+            ),
+            (",py#1", RoutedCommand::Cmd("py#1")),
+            (",ls -l", RoutedCommand::Cmd("ls -l")),
+            (",,ls", RoutedCommand::Cmd("ls")),
+            ("，ls", RoutedCommand::Cmd("ls")),
+            (".ls", RoutedCommand::Cmd("ls")),
+            ("。ls", RoutedCommand::Cmd("ls")),
+            (",", RoutedCommand::Cmd("")),
+            (",h#", RoutedCommand::Cmd("h#")),
+            ("h#", RoutedCommand::Help),
+            ("s#", RoutedCommand::Status),
+            ("hello, world", RoutedCommand::None),
+            (" ,ls", RoutedCommand::None),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(route(text, &Triggers::DEFAULT), expected, "{text:?}");
+        }
+    }
+}
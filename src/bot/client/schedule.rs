@@ -0,0 +1,121 @@
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use grammers_client::{InputMessage, types::Message};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Telegram refuses to schedule messages further ahead than this.
+const MAX_AHEAD: TimeDelta = TimeDelta::days(365);
+
+/// When to send a message scheduled at `arg`, seen from `now`: a duration like `90m`,
+/// a unix timestamp, `HH:MM` (the next such time of day) or `YYYY-MM-DDTHH:MM`.
+fn parse_schedule_time<Tz: TimeZone>(
+    arg: &str,
+    now: DateTime<Tz>,
+) -> Result<DateTime<Utc>, String> {
+    let at = if let Ok(duration) = humantime::parse_duration(arg) {
+        let delta = TimeDelta::from_std(duration).map_err(|_| format!("Too far ahead: {arg}"))?;
+        now.to_utc() + delta
+    } else if let Ok(ts) = arg.parse::<i64>() {
+        DateTime::from_timestamp(ts, 0).ok_or_else(|| format!("Timestamp out of range: {ts}"))?
+    } else if let Ok(time) = NaiveTime::parse_from_str(arg, "%H:%M") {
+        let tz = now.timezone();
+        let today = now.date_naive().and_time(time);
+        let mut at = tz
+            .from_local_datetime(&today)
+            .earliest()
+            .ok_or_else(|| format!("No such local time: {arg}"))?;
+        if at <= now {
+            at = tz
+                .from_local_datetime(&(today + TimeDelta::days(1)))
+                .earliest()
+                .ok_or_else(|| format!("No such local time: {arg}"))?;
+        }
+        at.to_utc()
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(arg, "%Y-%m-%dT%H:%M") {
+        now.timezone()
+            .from_local_datetime(&datetime)
+            .earliest()
+            .ok_or_else(|| format!("No such local time: {arg}"))?
+            .to_utc()
+    } else {
+        return Err(format!("Unknown time: {arg}"));
+    };
+
+    if at <= now.to_utc() {
+        return Err("The time is in the past".to_string());
+    }
+    if at - now.to_utc() > MAX_AHEAD {
+        return Err("Messages can be scheduled at most a year ahead".to_string());
+    }
+    Ok(at)
+}
+
+impl TomorinClient {
+    /// Schedule `text` in the current chat with Telegram's scheduled messages, which are
+    /// sent by the server even while the bot is down.
+    pub async fn handle_schedule(&self, args: &str, m: &Message) -> Result<()> {
+        let Some((time, text)) = args
+            .split_once(char::is_whitespace)
+            .map(|(time, text)| (time, text.trim()))
+            .filter(|(_, text)| !text.is_empty())
+        else {
+            self.respond(
+                m,
+                "Usage: schedule <90m | HH:MM | YYYY-MM-DDTHH:MM | unix-ts> <text>",
+            )
+            .await?;
+            return Ok(());
+        };
+        let at = match parse_schedule_time(time, Local::now()) {
+            Ok(at) => at,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+
+        let message = InputMessage::text(text).schedule_date(Some(at.into()));
+        self.pace(m.chat().id(), "send_message").await;
+        match self.client.send_message(m.chat(), message).await {
+            Ok(_) => {
+                let local = at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S %:z");
+                self.respond(m, format!("Scheduled for {local} ({})", at.timestamp()))
+                    .await?;
+            }
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to schedule: {e}"))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::FixedOffset;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_schedule_time() {
+        let tz = FixedOffset::east_opt(9 * 3600).unwrap();
+        // 2023-11-15 07:13:20 +09:00
+        let now = DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .with_timezone(&tz);
+        let ts = |s: &str| parse_schedule_time(s, now).map(|at| at.timestamp());
+
+        assert_eq!(ts("90m"), Ok(1_700_000_000 + 90 * 60));
+        assert_eq!(ts("1700003600"), Ok(1_700_003_600));
+        // Later today, and tomorrow for a time already past.
+        assert_eq!(ts("08:00"), Ok(1_700_002_800));
+        assert_eq!(ts("07:00"), Ok(1_700_002_800 - 3600 + 86400));
+        assert_eq!(ts("2023-11-16T07:13"), Ok(1_700_086_380));
+
+        assert!(ts("1600000000").is_err());
+        assert!(ts("400d").is_err());
+        assert!(ts("tomorrow").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use grammers_client::types::Message;
+use tokio::process::Command;
+
+use super::{TomorinClient, shellwords::split_args, snippet_msg};
+use crate::error::{BotError, Result};
+
+/// The languages besides Rust that snippets can be run in, with a local interpreter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Python,
+    JavaScript,
+    Shell,
+}
+
+impl Language {
+    /// The language tag of the code block the snippet is shown in.
+    fn block_tag(self) -> &'static str {
+        match self {
+            Self::Python => "Python",
+            Self::JavaScript => "JavaScript",
+            Self::Shell => "Shell",
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::Python => "snippet.py",
+            Self::JavaScript => "snippet.js",
+            Self::Shell => "snippet.sh",
+        }
+    }
+}
+
+/// The programs a shell snippet runs directly, for checking them against `cmd-denylist`.
+///
+/// Only a best effort: commands are told apart at line breaks, `;`, `|` and `&`, quoted or
+/// not, and a program reached indirectly, e.g. through `eval` or a variable, is missed.
+fn shell_programs(code: &str) -> Vec<String> {
+    code.split(['\n', ';', '|', '&'])
+        .filter_map(|command| split_args(command).ok())
+        .filter_map(|words| {
+            words
+                .into_iter()
+                .find(|word| !word.contains('=') || word.starts_with('='))
+        })
+        .collect()
+}
+
+/// Combine the captured streams of a finished snippet into its displayed result.
+fn script_result(output: &std::process::Output, capped: Option<usize>) -> String {
+    let mut result = String::from_utf8_lossy(&output.stdout).into_owned();
+    result.push_str(&String::from_utf8_lossy(&output.stderr));
+    if let Some(cap) = capped {
+        result.push_str(&format!("\n[output truncated at {cap} bytes]"));
+    } else if let Some(code) = output.status.code()
+        && code != 0
+    {
+        result.push_str(&format!("\nexit code {code}"));
+    }
+    if result.trim().is_empty() {
+        result = "(no output)".to_string();
+    }
+    result
+}
+
+impl TomorinClient {
+    fn interpreter(&self, language: Language) -> &str {
+        match language {
+            Language::Python => &self.conf.python_interpreter,
+            Language::JavaScript => &self.conf.js_interpreter,
+            Language::Shell => &self.conf.sh_interpreter,
+        }
+    }
+
+    /// Run `code` with the interpreter for `language` and show it with its output, like an eval.
+    pub async fn handle_script(&self, language: Language, code: &str, m: &Message) -> Result<()> {
+        use crate::eval::{TempDir, output_capped};
+
+        if code.trim().is_empty() {
+            self.respond(m, "No code given").await?;
+            return Ok(());
+        }
+        if !self.may_spawn(self.interpreter(language), m).await? {
+            return Ok(());
+        }
+        if language == Language::Shell {
+            for program in shell_programs(code) {
+                if !self.may_spawn(&program, m).await? {
+                    return Ok(());
+                }
+            }
+        }
+        self.respond(m, "少女祈祷中......").await?;
+
+        let dir = TempDir::new()?;
+        let source = dir.path().join(language.file_name());
+        tokio::fs::write(&source, code).await?;
+
+        let timeout = *self.conf.eval_local_timeout;
+        let mut command = Command::new(self.interpreter(language));
+        command.current_dir(dir.path()).arg(&source);
+        let cap = self.conf.output_max_bytes;
+        let result = match output_capped(&mut command, timeout, cap).await {
+            Ok(Some((output, capped))) => script_result(&output, capped.then_some(cap)),
+            Ok(None) => format!(
+                "error: timed out after {}",
+                humantime::format_duration(timeout)
+            ),
+            Err(e @ BotError::Spawn(_)) => format!("笨！\n{e}"),
+            Err(e) => return Err(e),
+        };
+
+        self.respond(m, snippet_msg(language.block_tag(), code, &result))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::{
+        os::unix::process::ExitStatusExt,
+        process::{ExitStatus, Output},
+    };
+
+    use super::*;
+
+    #[cfg(unix)]
+    fn output(stdout: &str, stderr: &str, code: i32) -> Output {
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.into(),
+            stderr: stderr.into(),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_script_result() {
+        assert_eq!(script_result(&output("2\n", "", 0), None), "2\n");
+        assert_eq!(
+            script_result(&output("", "NameError\n", 1), None),
+            "NameError\n\nexit code 1"
+        );
+        assert_eq!(script_result(&output("", "", 0), None), "(no output)");
+        assert_eq!(
+            script_result(&output("yyyy", "", 0), Some(4)),
+            "yyyy\n[output truncated at 4 bytes]"
+        );
+    }
+
+    #[test]
+    fn test_shell_programs() {
+        assert_eq!(
+            shell_programs("echo hi; ls -l | grep x && FOO=1 rm -rf /tmp/x\n\n  mkfs"),
+            ["echo", "ls", "grep", "rm", "mkfs"]
+        );
+        assert!(shell_programs("echo 'unclosed").is_empty());
+    }
+}
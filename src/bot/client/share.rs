@@ -0,0 +1,110 @@
+use grammers_client::{
+    grammers_tl_types::{enums::MessageEntity, types::MessageEntityTextUrl},
+    types::Message,
+};
+use reqwest::StatusCode;
+
+use super::{TomorinClient, fence::utf16_slice, message::Outgoing};
+use crate::error::{BotError, Result};
+
+/// Split an eval result message back into its code and, if present, its output.
+pub(super) fn eval_parts(
+    text: &str,
+    entities: &[MessageEntity],
+) -> Option<(String, Option<String>)> {
+    let pre = |language: &str| {
+        entities.iter().find_map(|e| match e {
+            MessageEntity::Pre(pre) if pre.language == language => {
+                utf16_slice(text, pre.offset, pre.length)
+            }
+            _ => None,
+        })
+    };
+
+    let code = pre("Rust")?;
+    let output = pre("Output").map(|output| output.trim().to_string());
+    Some((code, output))
+}
+
+impl TomorinClient {
+    pub async fn handle_share(&self, m: &Message) -> Result<()> {
+        use crate::eval::EvalClient;
+
+        let Some(reply) = self.get_reply(m).await? else {
+            self.respond(m, "Reply to an eval result to share it")
+                .await?;
+            return Ok(());
+        };
+        let Some((code, output)) = eval_parts(
+            reply.text(),
+            reply.fmt_entities().map(Vec::as_slice).unwrap_or_default(),
+        ) else {
+            self.respond(m, "Not an eval result").await?;
+            return Ok(());
+        };
+
+        self.respond(m, "少女祈祷中......").await?;
+        match EvalClient::intance()
+            .share_gist(&code, output.as_deref())
+            .await
+        {
+            Ok(url) => {
+                let text = "Shared as a gist";
+                let entity = MessageEntity::TextUrl(MessageEntityTextUrl {
+                    offset: 0,
+                    length: text.encode_utf16().count() as i32,
+                    url,
+                });
+                self.respond(m, Outgoing::text(text).fmt_entities(vec![entity]))
+                    .await?;
+            }
+            Err(BotError::Http(e))
+                if matches!(
+                    e.status(),
+                    Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+                ) =>
+            {
+                self.respond(
+                    m,
+                    "笨！\nThe playground refused to create the gist, try again later",
+                )
+                .await?;
+            }
+            Err(e @ (BotError::Http(_) | BotError::HttpTimeout | BotError::HttpRateLimited)) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grammers_client::grammers_tl_types::types::MessageEntityPre;
+
+    use super::*;
+
+    fn pre(offset: i32, length: i32, language: &str) -> MessageEntity {
+        MessageEntity::Pre(MessageEntityPre {
+            offset,
+            length,
+            language: language.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_eval_parts() {
+        let text = "1 + 1\n2";
+        assert_eq!(
+            eval_parts(text, &[pre(0, 5, "Rust"), pre(5, 2, "Output")]),
+            Some(("1 + 1".to_string(), Some("2".to_string())))
+        );
+        assert_eq!(
+            eval_parts(text, &[pre(0, 5, "Rust")]),
+            Some(("1 + 1".to_string(), None))
+        );
+        assert_eq!(eval_parts(text, &[pre(0, 7, "StdOut")]), None);
+        assert_eq!(eval_parts(text, &[]), None);
+    }
+}
@@ -0,0 +1,86 @@
+/// Split a shell command into its program and arguments like a POSIX shell would, without
+/// expanding anything: single quotes keep everything literal, double quotes keep all but
+/// `\"`, `\\`, `` \` `` and `\$`, and a backslash outside quotes escapes any character.
+pub fn split_args(cmd: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    // The argument being read, `None` between arguments, so that `''` is an empty one.
+    let mut arg: Option<String> = None;
+    let mut chars = cmd.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '`' | '$')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("Unclosed double quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("Unclosed double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => arg.get_or_insert_default().push(c),
+                None => return Err("Nothing to escape after the trailing backslash".to_string()),
+            },
+            c => arg.get_or_insert_default().push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(cmd: &str) -> Vec<String> {
+        split_args(cmd).unwrap()
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split("ls  -l\t/tmp "), ["ls", "-l", "/tmp"]);
+        assert_eq!(split("echo \"hello world\""), ["echo", "hello world"]);
+        assert_eq!(split("echo 'a \"b\" $c'"), ["echo", "a \"b\" $c"]);
+        assert_eq!(split("echo pre\"fix\"'ed'"), ["echo", "prefixed"]);
+        assert_eq!(split(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_split_args_escapes() {
+        assert_eq!(split(r#"echo "say \"hi\"""#), ["echo", r#"say "hi""#]);
+        assert_eq!(split(r#"echo "a\b" "c\\d""#), ["echo", r"a\b", r"c\d"]);
+        assert_eq!(split(r"echo hello\ world \'"), ["echo", "hello world", "'"]);
+        assert_eq!(split(r"echo '\n'"), ["echo", r"\n"]);
+    }
+
+    #[test]
+    fn test_split_args_empty() {
+        assert_eq!(
+            split("printf '%s|' '' \"\" x"),
+            ["printf", "%s|", "", "", "x"]
+        );
+        assert!(split_args("echo 'open").is_err());
+        assert!(split_args("echo \"open").is_err());
+        assert!(split_args("echo \\").is_err());
+    }
+}
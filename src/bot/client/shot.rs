@@ -0,0 +1,226 @@
+use std::io::Cursor;
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont, point};
+use grammers_client::{InputMessage, types::Message};
+use image::{ImageFormat, Rgb, RgbImage};
+use tokio::process::Command;
+use unicode_width::UnicodeWidthChar;
+
+use super::{TomorinClient, action::ChatAction, shellwords::split_args};
+use crate::conf::ReplyMode;
+use crate::error::{BotError, Result};
+use crate::eval::output_capped;
+
+/// Monospace fonts tried in order when `shot-font` is not configured.
+const FALLBACK_FONTS: [&str; 4] = [
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu-sans-mono-fonts/DejaVuSansMono.ttf",
+    "/System/Library/Fonts/Menlo.ttc",
+];
+
+const PADDING: u32 = 16;
+const MAX_LINES: usize = 200;
+const MAX_COLUMNS: usize = 200;
+const BACKGROUND: Rgb<u8> = Rgb([30, 30, 30]);
+const FOREGROUND: Rgb<u8> = Rgb([212, 212, 212]);
+
+/// Render `text` on a monospace grid, wide (CJK) characters taking two cells.
+fn render_text(text: &str, font: &FontVec, size: f32) -> RgbImage {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let cell_width = scaled.h_advance(font.glyph_id('M'));
+    let line_height = scaled.height() + scaled.line_gap();
+
+    let text = text.replace('\t', "    ");
+    let lines = text.lines().take(MAX_LINES).collect::<Vec<_>>();
+    let columns = lines
+        .iter()
+        .map(|line| {
+            line.chars()
+                .map(|c| c.width_cjk().unwrap_or(0))
+                .sum::<usize>()
+                .min(MAX_COLUMNS)
+        })
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let width = (columns as f32 * cell_width).ceil() as u32 + 2 * PADDING;
+    let height = (lines.len().max(1) as f32 * line_height).ceil() as u32 + 2 * PADDING;
+    let mut img = RgbImage::from_pixel(width, height, BACKGROUND);
+
+    for (row, line) in lines.iter().enumerate() {
+        let baseline = PADDING as f32 + row as f32 * line_height + scaled.ascent();
+        let mut column = 0;
+        for c in line.chars() {
+            let cells = c.width_cjk().unwrap_or(0);
+            if column + cells > MAX_COLUMNS {
+                break;
+            }
+
+            let mut glyph = scaled.scaled_glyph(c);
+            glyph.position = point(PADDING as f32 + column as f32 * cell_width, baseline);
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    let x = bounds.min.x as i64 + gx as i64;
+                    let y = bounds.min.y as i64 + gy as i64;
+                    if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                        return;
+                    }
+
+                    let px = img.get_pixel_mut(x as u32, y as u32);
+                    for (channel, fg) in px.0.iter_mut().zip(FOREGROUND.0) {
+                        let blended = *channel as f32 * (1.0 - coverage) + fg as f32 * coverage;
+                        *channel = blended.round() as u8;
+                    }
+                });
+            }
+            column += cells;
+        }
+    }
+
+    img
+}
+
+impl TomorinClient {
+    pub(super) fn load_shot_font(&self) -> Result<FontVec> {
+        let path = match &self.conf.shot_font {
+            Some(path) => path.clone(),
+            None => FALLBACK_FONTS
+                .iter()
+                .map(Into::into)
+                .find(|path: &std::path::PathBuf| path.exists())
+                .ok_or_else(|| {
+                    BotError::Config("No monospace font found, set `shot-font`".to_string())
+                })?,
+        };
+
+        let data = std::fs::read(&path)
+            .map_err(|e| BotError::Config(format!("Failed to read font {path:?}: {e}")))?;
+        FontVec::try_from_vec(data)
+            .map_err(|e| BotError::Config(format!("Invalid font {path:?}: {e}")))
+    }
+
+    /// Run `cmd` and send its output rendered as an image.
+    pub async fn handle_shot(&self, cmd: &str, m: &Message) -> Result<()> {
+        let words = match split_args(cmd) {
+            Ok(words) => words,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+        let Some((program, args)) = words.split_first() else {
+            self.respond(m, "Usage: shot <command>").await?;
+            return Ok(());
+        };
+        if !self.may_spawn(program, m).await? {
+            return Ok(());
+        }
+
+        let font = match self.load_shot_font() {
+            Ok(font) => font,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+
+        self.respond(m, "少女祈祷中......").await?;
+
+        let timeout = *self.conf.shot_timeout;
+        let cap = self.conf.output_max_bytes;
+        let mut command = Command::new(program);
+        command.args(args);
+        let (output, capped) = match output_capped(&mut command, timeout, cap).await {
+            Ok(Some(output)) => output,
+            Ok(None) => {
+                self.respond(
+                    m,
+                    format!(
+                        "笨！\n{program} timed out after {}",
+                        humantime::format_duration(timeout)
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+            Err(e @ BotError::Spawn(_)) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        if text.trim().is_empty() {
+            text = "(no output)".to_string();
+        }
+        // On top, as the image only keeps the first lines.
+        if capped {
+            text.insert_str(0, &format!("[output truncated at {cap} bytes]\n"));
+        }
+
+        let png = self.render_png(text, font).await?;
+        self.send_shot(m, &format!("❯ {cmd}"), png).await?;
+        self.discard_response(m).await?;
+
+        Ok(())
+    }
+
+    /// Render `text` as a PNG image with `font`, off the async runtime.
+    pub(super) async fn render_png(&self, text: String, font: FontVec) -> Result<Vec<u8>> {
+        let size = self.conf.shot_font_size as f32;
+        let png = tokio::task::spawn_blocking(move || {
+            let mut png = Vec::new();
+            render_text(&text, &font, size)
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            anyhow::Ok(png)
+        })
+        .await
+        .map_err(anyhow::Error::from)??;
+        Ok(png)
+    }
+
+    /// Send the rendered `png` with `caption` as the output of `m`, as a photo if it fits
+    /// the photo limits, else as a file.
+    pub(super) async fn send_shot(&self, m: &Message, caption: &str, png: Vec<u8>) -> Result<()> {
+        let len = png.len();
+        self.count_call("upload");
+        let uploaded = self
+            .with_action(
+                m,
+                ChatAction::UploadPhoto,
+                self.client
+                    .upload_stream(&mut Cursor::new(png), len, "shot.png".to_string()),
+            )
+            .await?;
+
+        // Very tall or wide renders exceed the photo dimension limits, send those as files.
+        if self
+            .send_output_media(m, InputMessage::text(caption).photo(uploaded.clone()))
+            .await
+            .is_err()
+        {
+            self.send_output_media(m, InputMessage::text(caption).document(uploaded))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send `msg` next to the output of `m`, according to the reply mode.
+    pub(super) async fn send_output_media(
+        &self,
+        m: &Message,
+        msg: InputMessage,
+    ) -> Result<Message, grammers_client::InvocationError> {
+        let msg = msg.silent(self.is_silent(m));
+        self.pace(m.chat().id(), "send_message").await;
+        match self.conf.reply_mode {
+            ReplyMode::Edit => m.respond(msg).await,
+            ReplyMode::Reply => m.reply(msg).await,
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use grammers_client::{
+    grammers_tl_types::{enums::MessageEntity, types::MessageEntityPre},
+    types::Message,
+};
+
+use super::{TomorinClient, message::Outgoing};
+use crate::error::Result;
+
+impl TomorinClient {
+    /// Show the active config, with secrets redacted.
+    pub async fn handle_showconf(&self, m: &Message) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.conf.to_redacted_json())
+            .map_err(anyhow::Error::from)?;
+        // Unlike command output, the config is sent whole rather than cut to its last lines.
+        let entity = MessageEntity::Pre(MessageEntityPre {
+            offset: 0,
+            length: json.encode_utf16().count() as i32,
+            language: "JSON".to_string(),
+        });
+        self.respond(m, Outgoing::text(json.as_str()).fmt_entities(vec![entity]))
+            .await?;
+        Ok(())
+    }
+}
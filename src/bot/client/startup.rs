@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use grammers_client::{InvocationError, types::Chat};
+
+use super::TomorinClient;
+
+/// A chat as written in the `startup-chat` config.
+#[derive(Debug, PartialEq, Eq)]
+enum ChatRef<'a> {
+    /// Saved Messages.
+    Me,
+    Username(&'a str),
+    /// A chat id, also accepted in the Bot API form with the `-` or `-100` prefix.
+    Id(i64),
+}
+
+/// Bot API ids of channels and supergroups are `-(CHANNEL_ID_OFFSET + id)`, that is `-100`
+/// followed by the channel id padded to ten digits.
+const CHANNEL_ID_OFFSET: u64 = 1_000_000_000_000;
+
+/// Parse a chat as written in the config, or `None` if it can't name one.
+fn parse_chat_ref(chat: &str) -> Option<ChatRef<'_>> {
+    let chat = chat.trim();
+    if chat == "me" || chat == "self" {
+        return Some(ChatRef::Me);
+    }
+    match chat.parse::<i64>() {
+        Ok(0) => None,
+        Ok(id) if id < 0 => {
+            let id = id.unsigned_abs();
+            let id = id
+                .checked_sub(CHANNEL_ID_OFFSET)
+                .filter(|id| *id > 0)
+                .unwrap_or(id);
+            i64::try_from(id).ok().map(ChatRef::Id)
+        }
+        Ok(id) => Some(ChatRef::Id(id)),
+        Err(_) => {
+            let username = chat.strip_prefix('@').unwrap_or(chat);
+            let valid = !username.is_empty()
+                && username
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_');
+            valid.then_some(ChatRef::Username(username))
+        }
+    }
+}
+
+/// The `login-banner` text confirming `username` logged in on `host`.
+fn login_banner(username: &str, host: &str) -> String {
+    format!(
+        "Logged in as {username}\nTomorin {} ({}) on {host}",
+        env!("CARGO_PKG_VERSION"),
+        env!("TOMORIN_GIT_HASH"),
+    )
+}
+
+impl TomorinClient {
+    async fn resolve_chat_ref(&self, chat: ChatRef<'_>) -> Result<Option<Chat>, InvocationError> {
+        match chat {
+            ChatRef::Me => Ok(Some(Chat::User(self.me.clone()))),
+            ChatRef::Username(username) => {
+                self.count_call("resolve_username");
+                self.client.resolve_username(username).await
+            }
+            ChatRef::Id(id) => self.find_dialog_chat(id).await,
+        }
+    }
+
+    /// Tell the configured `startup-chat` that the bot is online. Failures are only logged,
+    /// they must not keep the bot from starting.
+    pub async fn announce_startup(&self) {
+        let Some(target) = &self.conf.startup_chat else {
+            return;
+        };
+        let Some(chat_ref) = parse_chat_ref(target) else {
+            tracing::warn!("Startup chat {target} is neither me, a @username nor a chat id");
+            return;
+        };
+        let chat = match self.resolve_chat_ref(chat_ref).await {
+            Ok(Some(chat)) => chat,
+            Ok(None) => {
+                tracing::warn!("Startup chat {target} not found");
+                return;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to resolve startup chat {target}: {e}");
+                return;
+            }
+        };
+
+        let host = sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string());
+        let host_uptime = Duration::from_secs(sysinfo::System::uptime());
+        let text = format!(
+            "Tomorin {} ({}) is online on {host}
+Host uptime - {}
+Total uptime - {}",
+            env!("CARGO_PKG_VERSION"),
+            env!("TOMORIN_GIT_HASH"),
+            humantime::format_duration(host_uptime),
+            humantime::format_duration(Duration::from_secs(self.uptime.total.as_secs())),
+        );
+        self.pace(chat.id(), "send_message").await;
+        if let Err(e) = self.client.send_message(&chat, text).await {
+            tracing::warn!("Failed to post the startup status to {target}: {e}");
+        }
+    }
+}
+
+impl TomorinClient {
+    /// Confirm in Saved Messages that a login just succeeded, if `login-banner` is set.
+    /// Failures are only logged like for the startup status.
+    pub(super) async fn send_login_banner(&self) {
+        if !self.conf.login_banner {
+            return;
+        }
+        let host = sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string());
+        let text = login_banner(&self.username(), &host);
+        self.pace(self.me.id(), "send_message").await;
+        if let Err(e) = self
+            .client
+            .send_message(&Chat::User(self.me.clone()), text)
+            .await
+        {
+            tracing::warn!("Failed to send the login banner: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chat_ref() {
+        assert_eq!(parse_chat_ref("me"), Some(ChatRef::Me));
+        assert_eq!(
+            parse_chat_ref("@rust_lang"),
+            Some(ChatRef::Username("rust_lang"))
+        );
+        assert_eq!(
+            parse_chat_ref("rust_lang"),
+            Some(ChatRef::Username("rust_lang"))
+        );
+        assert_eq!(parse_chat_ref("123456"), Some(ChatRef::Id(123456)));
+        assert_eq!(
+            parse_chat_ref("-1001234567890"),
+            Some(ChatRef::Id(1234567890))
+        );
+        assert_eq!(parse_chat_ref("-1000000000005"), Some(ChatRef::Id(5)));
+        assert_eq!(parse_chat_ref("-4567"), Some(ChatRef::Id(4567)));
+        // Basic groups whose id merely starts with 100.
+        assert_eq!(parse_chat_ref("-1005"), Some(ChatRef::Id(1005)));
+        assert_eq!(parse_chat_ref("-100"), Some(ChatRef::Id(100)));
+        assert_eq!(parse_chat_ref("0"), None);
+        assert_eq!(parse_chat_ref("-"), None);
+        assert_eq!(parse_chat_ref("@"), None);
+    }
+
+    #[test]
+    fn test_login_banner() {
+        let banner = login_banner("@tomorin", "box");
+        assert!(banner.starts_with("Logged in as @tomorin\nTomorin "));
+        assert!(banner.ends_with(" on box"));
+    }
+}
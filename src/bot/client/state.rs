@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use grammers_client::types::Message;
+use serde_json::{Map, Value, json};
+
+use super::{TomorinClient, dedup::MessageKey};
+use crate::{conf::Truncation, error::Result};
+
+/// In-memory state shown by `,state`.
+pub(super) trait Inspect {
+    /// How many entries are kept, such as messages or chats.
+    fn size(&self) -> usize;
+
+    fn dump(&self) -> Value;
+
+    /// Forget every entry, returning `false` if this state can't be safely cleared.
+    fn clear(&mut self) -> bool {
+        false
+    }
+}
+
+/// `chat/message`, for messages used as JSON object keys.
+pub(super) fn key_name((chat, id): MessageKey) -> String {
+    format!("{chat}/{id}")
+}
+
+impl Inspect for HashSet<MessageKey> {
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    fn dump(&self) -> Value {
+        let mut keys: Vec<_> = self.iter().copied().collect();
+        keys.sort_unstable();
+        keys.into_iter().map(key_name).collect()
+    }
+}
+
+impl Inspect for Truncation {
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn dump(&self) -> Value {
+        json!(self)
+    }
+}
+
+/// The keys `,state` knows, in the order they are listed.
+const KEYS: [&str; 16] = [
+    "handled",
+    "owned",
+    "history",
+    "replies",
+    "api-stats",
+    "prefixes",
+    "quiet",
+    "truncation",
+    "watches",
+    "error-mute",
+    "outbound",
+    "notebooks",
+    "confirms",
+    "output-modes",
+    "long-outputs",
+    "eval-settings",
+];
+
+enum StateArgs<'a> {
+    Sizes,
+    Show(&'a str),
+    Clear(&'a str),
+}
+
+fn parse_state_args(args: &str) -> Option<StateArgs<'_>> {
+    match args.split_whitespace().collect::<Vec<_>>()[..] {
+        [] => Some(StateArgs::Sizes),
+        ["clear", key] => Some(StateArgs::Clear(key)),
+        [key] => Some(StateArgs::Show(key)),
+        _ => None,
+    }
+}
+
+impl TomorinClient {
+    /// Run `f` on the state called `key`, or return `None` if there is no such state.
+    fn with_state<R>(&self, key: &str, f: impl FnOnce(&mut dyn Inspect) -> R) -> Option<R> {
+        let result = match key {
+            "handled" => f(&mut *self.handled.lock().unwrap()),
+            "owned" => f(&mut *self.owned.lock().unwrap()),
+            "history" => f(&mut *self.history.lock().unwrap()),
+            "replies" => f(&mut *self.replies.lock().unwrap()),
+            "api-stats" => f(&mut *self.api_stats.lock().unwrap()),
+            "prefixes" => f(&mut *self.prefixes.lock().unwrap()),
+            "quiet" => f(&mut *self.quiet.lock().unwrap()),
+            "truncation" => f(&mut *self.truncation.lock().unwrap()),
+            "watches" => f(&mut *self.watches.lock().unwrap()),
+            "error-mute" => f(&mut *self.error_mute.lock().unwrap()),
+            "outbound" => f(&mut *self.outbound.lock().unwrap()),
+            "notebooks" => f(&mut *self.notebooks.lock().unwrap()),
+            "confirms" => f(&mut *self.confirms.lock().unwrap()),
+            "output-modes" => f(&mut *self.output_modes.lock().unwrap()),
+            "long-outputs" => f(&mut *self.long_outputs.lock().unwrap()),
+            "eval-settings" => f(&mut *self.eval_settings.lock().unwrap()),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// Show the size of every piece of in-memory state, show one as JSON, or clear one.
+    pub async fn handle_state(&self, args: &str, m: &Message) -> Result<()> {
+        let value = match parse_state_args(args) {
+            Some(StateArgs::Sizes) => KEYS
+                .iter()
+                .filter_map(|key| {
+                    Some((key.to_string(), self.with_state(key, |s| s.size())?.into()))
+                })
+                .collect::<Map<_, _>>()
+                .into(),
+            Some(StateArgs::Show(key)) => match self.with_state(key, |s| s.dump()) {
+                Some(value) => value,
+                None => return self.respond_unknown_state(key, m).await,
+            },
+            Some(StateArgs::Clear(key)) => {
+                let text = match self.with_state(key, |s| s.clear()) {
+                    Some(true) => format!("Cleared {key}"),
+                    Some(false) => format!("笨！\n{key} can't be cleared"),
+                    None => return self.respond_unknown_state(key, m).await,
+                };
+                self.respond(m, text).await?;
+                return Ok(());
+            }
+            None => {
+                self.respond(m, "Usage: state [<key> | clear <key>]")
+                    .await?;
+                return Ok(());
+            }
+        };
+        let json = serde_json::to_string_pretty(&value).map_err(anyhow::Error::from)?;
+        Self::edit_pre_msg(&self.responder(m), &json, "JSON").await
+    }
+
+    async fn respond_unknown_state(&self, key: &str, m: &Message) -> Result<()> {
+        self.respond(
+            m,
+            format!(
+                "笨！\nNo state called {key}, try one of: {}",
+                KEYS.join(", ")
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state_args() {
+        assert!(matches!(parse_state_args(""), Some(StateArgs::Sizes)));
+        assert!(matches!(
+            parse_state_args("history"),
+            Some(StateArgs::Show("history"))
+        ));
+        assert!(matches!(
+            parse_state_args("clear  history"),
+            Some(StateArgs::Clear("history"))
+        ));
+        assert!(parse_state_args("history confirms").is_none());
+    }
+
+    #[test]
+    fn test_quiet_dump() {
+        let quiet = HashSet::from([(2, 1), (1, 5), (1, 3)]);
+        assert_eq!(quiet.size(), 3);
+        assert_eq!(quiet.dump(), json!(["1/3", "1/5", "2/1"]));
+    }
+}
@@ -0,0 +1,119 @@
+use grammers_client::{
+    InvocationError, grammers_tl_types as tl,
+    types::{Media, Message},
+};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// The t.me link adding the set with `short_name`.
+fn set_link(short_name: &str, emojis: bool) -> String {
+    let kind = if emojis { "addemoji" } else { "addstickers" };
+    format!("https://t.me/{kind}/{short_name}")
+}
+
+fn render_set(set: &tl::types::StickerSet) -> String {
+    let kind = if set.emojis {
+        "emojis"
+    } else if set.masks {
+        "masks"
+    } else {
+        "stickers"
+    };
+    let mut text = format!(
+        "{}\n{} - {} {kind}\n{}",
+        set.title,
+        set.short_name,
+        set.count,
+        set_link(&set.short_name, set.emojis)
+    );
+    if set.official {
+        text.push_str("\n(official)");
+    }
+    text
+}
+
+impl TomorinClient {
+    /// Reply to a sticker to show the set it belongs to, with a link to add it.
+    pub async fn handle_stickerset(&self, m: &Message) -> Result<()> {
+        let Some(reply) = self.get_reply(m).await? else {
+            self.respond(m, "Reply to a sticker to show its set")
+                .await?;
+            return Ok(());
+        };
+        let Some(Media::Sticker(sticker)) = reply.media() else {
+            self.respond(m, "笨！\nThat is not a sticker").await?;
+            return Ok(());
+        };
+        let stickerset = sticker.raw_attrs.stickerset;
+        if let tl::enums::InputStickerSet::Empty = stickerset {
+            self.respond(m, "The sticker does not belong to a set")
+                .await?;
+            return Ok(());
+        }
+
+        let request = tl::functions::messages::GetStickerSet {
+            stickerset,
+            hash: 0,
+        };
+        let set = match self.invoke(&request).await {
+            Ok(tl::enums::messages::StickerSet::Set(set)) => set.set,
+            Ok(tl::enums::messages::StickerSet::NotModified) => {
+                self.respond(m, "笨！\nTelegram sent no sticker set")
+                    .await?;
+                return Ok(());
+            }
+            Err(InvocationError::Rpc(e)) if e.name == "STICKERSET_INVALID" => {
+                self.respond(m, "The sticker's set no longer exists")
+                    .await?;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let tl::enums::StickerSet::Set(set) = set;
+        Self::edit_pre_msg(&self.responder(m), &render_set(&set), "StickerSet").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_set() {
+        let set = tl::types::StickerSet {
+            archived: false,
+            official: false,
+            masks: false,
+            emojis: false,
+            text_color: false,
+            channel_emoji_status: false,
+            creator: false,
+            installed_date: None,
+            id: 1,
+            access_hash: 2,
+            title: "Tomorin".into(),
+            short_name: "tomorin_by_bot".into(),
+            thumbs: None,
+            thumb_dc_id: None,
+            thumb_version: None,
+            thumb_document_id: None,
+            count: 42,
+            hash: 0,
+        };
+        assert_eq!(
+            render_set(&set),
+            "Tomorin\ntomorin_by_bot - 42 stickers\nhttps://t.me/addstickers/tomorin_by_bot"
+        );
+
+        let set = tl::types::StickerSet {
+            emojis: true,
+            official: true,
+            ..set
+        };
+        assert_eq!(
+            render_set(&set),
+            "Tomorin\ntomorin_by_bot - 42 emojis\nhttps://t.me/addemoji/tomorin_by_bot\n(official)"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use grammers_client::types::Message;
+use reqwest::{Method, StatusCode, Url, header::LOCATION, redirect::Policy};
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Redirects followed before giving up.
+const MAX_REDIRECTS: usize = 10;
+/// How long each hop may take to answer.
+const HOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A URL visited and the status it answered with.
+#[derive(Debug)]
+struct Hop {
+    url: Url,
+    status: StatusCode,
+}
+
+/// Why following the redirects stopped.
+#[derive(Debug)]
+enum Stop {
+    /// The last hop was not a redirect.
+    Final,
+    TooManyRedirects,
+    Failed(String),
+}
+
+/// Parse the `,unshorten` argument, taking a URL without a scheme as https.
+fn parse_url(arg: &str) -> Option<Url> {
+    let arg = arg.trim();
+    if arg.is_empty() || arg.contains(char::is_whitespace) {
+        return None;
+    }
+    let url = if arg.contains("://") {
+        Url::parse(arg)
+    } else {
+        Url::parse(&format!("https://{arg}"))
+    };
+    url.ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+/// The URL a redirect from `url` leads to, as `location` may be relative to it.
+fn next_url(url: &Url, location: &str) -> Option<Url> {
+    url.join(location)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+fn render_chain(hops: &[Hop], stop: &Stop) -> String {
+    let mut text: String = hops
+        .iter()
+        .map(|hop| format!("{}  {}\n", hop.status.as_u16(), hop.url))
+        .collect();
+    let redirects = hops.len().saturating_sub(1);
+    match (stop, hops.last()) {
+        (Stop::Final, Some(last)) => {
+            let plural = if redirects == 1 { "" } else { "s" };
+            text.push_str(&format!(
+                "\nFinal URL after {redirects} redirect{plural}\n{}",
+                last.url
+            ));
+        }
+        (Stop::TooManyRedirects, _) => {
+            text.push_str(&format!("\nStopped after {MAX_REDIRECTS} redirects"));
+        }
+        (Stop::Failed(e), _) => text.push_str(&format!("\nFailed: {e}")),
+        (Stop::Final, None) => {}
+    }
+    text
+}
+
+/// Follow the redirects from `url` one hop at a time, never reading a body.
+async fn follow(client: &reqwest::Client, mut url: Url) -> (Vec<Hop>, Stop) {
+    let mut hops = Vec::new();
+    loop {
+        let mut resp = client.request(Method::HEAD, url.clone()).send().await;
+        // Some servers don't answer HEAD. A GET whose body is dropped unread works too.
+        if let Ok(head) = &resp
+            && matches!(
+                head.status(),
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            )
+        {
+            resp = client.get(url.clone()).send().await;
+        }
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) if e.is_timeout() => {
+                return (
+                    hops,
+                    Stop::Failed(format!("{url} timed out after {HOP_TIMEOUT:?}")),
+                );
+            }
+            Err(e) => return (hops, Stop::Failed(format!("{url}: {e}"))),
+        };
+        let status = resp.status();
+        let location = resp
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .map(str::to_string);
+        hops.push(Hop {
+            url: url.clone(),
+            status,
+        });
+        if !status.is_redirection() {
+            return (hops, Stop::Final);
+        }
+        let Some(next) = location
+            .as_deref()
+            .and_then(|location| next_url(&url, location))
+        else {
+            return (
+                hops,
+                Stop::Failed(format!("{status} without a usable Location")),
+            );
+        };
+        if hops.len() > MAX_REDIRECTS {
+            return (hops, Stop::TooManyRedirects);
+        }
+        url = next;
+    }
+}
+
+impl TomorinClient {
+    /// Show where a shortened URL leads and every redirect on the way, without opening it.
+    pub async fn handle_unshorten(&self, args: &str, m: &Message) -> Result<()> {
+        let Some(url) = parse_url(args) else {
+            self.respond(m, "Usage: unshorten <url>").await?;
+            return Ok(());
+        };
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .timeout(HOP_TIMEOUT)
+            .user_agent(
+                self.conf
+                    .user_agent
+                    .as_deref()
+                    .unwrap_or(concat!("tomorin/", env!("CARGO_PKG_VERSION"))),
+            )
+            .build();
+        let client = match client {
+            Ok(client) => client,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+
+        self.respond(m, "少女祈祷中......").await?;
+        let (hops, stop) = follow(&client, url).await;
+        Self::edit_pre_msg(&self.responder(m), &render_chain(&hops, &stop), "Redirects").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("bit.ly/abc").unwrap().as_str(),
+            "https://bit.ly/abc"
+        );
+        assert_eq!(
+            parse_url(" http://t.co/x ").unwrap().as_str(),
+            "http://t.co/x"
+        );
+        assert!(parse_url("").is_none());
+        assert!(parse_url("ftp://example.com").is_none());
+        assert!(parse_url("a b").is_none());
+    }
+
+    #[test]
+    fn test_next_url() {
+        let url = Url::parse("https://bit.ly/abc").unwrap();
+        assert_eq!(
+            next_url(&url, "/login?next=1").unwrap().as_str(),
+            "https://bit.ly/login?next=1"
+        );
+        assert_eq!(
+            next_url(&url, "https://example.com/").unwrap().as_str(),
+            "https://example.com/"
+        );
+        assert!(next_url(&url, "javascript:alert(1)").is_none());
+    }
+
+    #[test]
+    fn test_render_chain() {
+        let hop = |url: &str, status: u16| Hop {
+            url: Url::parse(url).unwrap(),
+            status: StatusCode::from_u16(status).unwrap(),
+        };
+        let hops = [
+            hop("https://bit.ly/abc", 301),
+            hop("https://example.com/", 200),
+        ];
+        assert_eq!(
+            render_chain(&hops, &Stop::Final),
+            "301  https://bit.ly/abc\n200  https://example.com/\n\nFinal URL after 1 redirect\nhttps://example.com/"
+        );
+        assert_eq!(
+            render_chain(&[], &Stop::Failed("timed out".into())),
+            "\nFailed: timed out"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    io,
+    path::Path,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use grammers_client::{InputMessage, types::Message};
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    time::{Instant, interval_at},
+};
+
+use super::{TomorinClient, action::ChatAction, monitor::format_size};
+use crate::conf::ReplyMode;
+use crate::error::Result;
+
+/// Counts the bytes read through it, for reporting progress while they are uploaded.
+struct Counted<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Counted<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        self.read.fetch_add(read, Ordering::Relaxed);
+        poll
+    }
+}
+
+fn progress_line(name: &str, done: u64, total: u64) -> String {
+    let percent = (done * 100).checked_div(total).unwrap_or(100);
+    format!(
+        "Uploading {name}: {percent}% ({} / {})",
+        format_size(done),
+        format_size(total)
+    )
+}
+
+impl TomorinClient {
+    /// Send a file from the server as a document, streamed from disk and reporting
+    /// the progress on the output tick.
+    pub async fn handle_upload(&self, path: &str, m: &Message) -> Result<()> {
+        if path.is_empty() {
+            self.respond(m, "Usage: upload <path>").await?;
+            return Ok(());
+        }
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to open {path}: {e}"))
+                    .await?;
+                return Ok(());
+            }
+        };
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            self.respond(m, format!("笨！\n{path} is not a file"))
+                .await?;
+            return Ok(());
+        }
+        let total = metadata.len();
+        let name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let read = Arc::new(AtomicU64::new(0));
+        let mut stream = Counted {
+            inner: file,
+            read: read.clone(),
+        };
+        self.respond(m, progress_line(&name, 0, total)).await?;
+
+        let mut ticker = interval_at(
+            Instant::now() + Duration::from_millis(self.conf.output_initial_delay_ms),
+            Duration::from_millis(self.conf.output_tick_ms.max(1)),
+        );
+        self.count_call("upload");
+        let upload = self.with_action(
+            m,
+            ChatAction::UploadDocument,
+            self.client
+                .upload_stream(&mut stream, total as usize, name.clone()),
+        );
+        let mut upload = std::pin::pin!(upload);
+        let uploaded = loop {
+            tokio::select! {
+                uploaded = upload.as_mut() => break uploaded,
+                _ = ticker.tick() => {
+                    let done = read.load(Ordering::Relaxed);
+                    // Progress is best effort, a failed edit must not abort the upload.
+                    if let Err(e) = self.respond(m, progress_line(&name, done, total)).await {
+                        tracing::warn!("Failed to report upload progress: {e}");
+                    }
+                }
+            }
+        };
+        let uploaded = match uploaded {
+            Ok(uploaded) => uploaded,
+            Err(e) => {
+                self.respond(m, format!("笨！\nFailed to upload {name}: {e}"))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let msg = InputMessage::text(&name)
+            .document(uploaded)
+            .silent(self.is_silent(m));
+        self.pace(m.chat().id(), "send_message").await;
+        match self.conf.reply_mode {
+            ReplyMode::Edit => m.respond(msg).await?,
+            ReplyMode::Reply => m.reply(msg).await?,
+        };
+        self.discard_response(m).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[test]
+    fn test_progress_line() {
+        assert_eq!(
+            progress_line("a.bin", 512 * 1024, 2 * 1024 * 1024),
+            "Uploading a.bin: 25% (512.0 KiB / 2.0 MiB)"
+        );
+        assert_eq!(
+            progress_line("empty", 0, 0),
+            "Uploading empty: 100% (0 B / 0 B)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_counted() {
+        let read = Arc::new(AtomicU64::new(0));
+        let mut counted = Counted {
+            inner: &b"hello world"[..],
+            read: read.clone(),
+        };
+        let mut buf = [0; 4];
+        counted.read_exact(&mut buf).await.unwrap();
+        assert_eq!(read.load(Ordering::Relaxed), 4);
+
+        let mut rest = Vec::new();
+        counted.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(read.load(Ordering::Relaxed), 11);
+    }
+}
@@ -0,0 +1,39 @@
+use std::{path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// Uptime carried across restarts, so status can report more than the current session.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PersistedUptime {
+    /// Unix timestamp of the very first start.
+    pub first_start: i64,
+    /// Runtime accumulated by all previous sessions.
+    pub total: Duration,
+}
+
+impl PersistedUptime {
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(uptime) => Some(uptime),
+                Err(e) => {
+                    tracing::warn!("ignoring malformed state file {path:?}: {e}");
+                    None
+                }
+            })
+            .unwrap_or_else(|| Self {
+                first_start: chrono::Utc::now().timestamp(),
+                total: Duration::ZERO,
+            })
+    }
+
+    /// Add the `session` runtime and write the result to `path`.
+    pub fn save(&self, path: &Path, session: Duration) -> std::io::Result<()> {
+        let uptime = Self {
+            first_start: self.first_start,
+            total: self.total + session,
+        };
+        std::fs::write(path, serde_json::to_string(&uptime)?)
+    }
+}
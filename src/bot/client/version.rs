@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use grammers_client::types::Message;
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Cargo features this build may have, and whether it has them.
+const FEATURES: [(&str, bool); 1] = [("alloc-stats", cfg!(feature = "alloc-stats"))];
+
+fn built_at() -> String {
+    env!("TOMORIN_BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map_or_else(
+            || "unknown".to_string(),
+            |dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        )
+}
+
+/// The features enabled in `features`, or `none`.
+fn enabled_features(features: &[(&str, bool)]) -> String {
+    let enabled: Vec<_> = features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    if enabled.is_empty() {
+        "none".to_string()
+    } else {
+        enabled.join(", ")
+    }
+}
+
+impl TomorinClient {
+    /// Show the versions and build details worth including in a bug report.
+    pub async fn handle_version(&self, m: &Message) -> Result<()> {
+        let info = format!(
+            "tomorin          {} ({})
+grammers-client  {}
+rustc            {}
+built            {}",
+            env!("CARGO_PKG_VERSION"),
+            env!("TOMORIN_GIT_HASH"),
+            env!("TOMORIN_GRAMMERS_VERSION"),
+            env!("TOMORIN_RUSTC_VERSION"),
+            built_at(),
+        );
+        Self::edit_pre_msg(&self.responder(m), &info, "Version").await
+    }
+
+    /// Show the build and the environment it runs in, as one snapshot for a bug report.
+    pub async fn handle_about(&self, m: &Message) -> Result<()> {
+        let os = sysinfo::System::long_os_version().unwrap_or_else(|| "unknown".to_string());
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        let uptime = Duration::from_secs(self.start_time.elapsed().as_secs());
+        let info = format!(
+            "tomorin          {} ({}, {profile})
+grammers-client  {}
+rustc            {}
+built            {}
+features         {}
+os               {os} ({}/{})
+uptime           {}",
+            env!("CARGO_PKG_VERSION"),
+            env!("TOMORIN_GIT_HASH"),
+            env!("TOMORIN_GRAMMERS_VERSION"),
+            env!("TOMORIN_RUSTC_VERSION"),
+            built_at(),
+            enabled_features(&FEATURES),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            humantime::format_duration(uptime),
+        );
+        Self::edit_pre_msg(&self.responder(m), &info, "About").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled_features() {
+        assert_eq!(enabled_features(&[("a", false)]), "none");
+        assert_eq!(
+            enabled_features(&[("a", true), ("b", false), ("c", true)]),
+            "a, c"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use grammers_client::types::Message;
+
+use super::{TomorinClient, action::ChatAction, check_code_len, share::eval_parts};
+use crate::{
+    error::{BotError, Result},
+    eval::EvalClient,
+};
+
+/// The outputs of snippets `a` and `b` stacked under labels, and whether they agree.
+fn render_vs(a: &str, b: &str) -> String {
+    let (a, b) = (a.trim(), b.trim());
+    let verdict = if a == b {
+        "Same output"
+    } else {
+        "Outputs differ"
+    };
+    format!("A (replied)\n{a}\n\nB\n{b}\n\n{verdict}")
+}
+
+impl TomorinClient {
+    /// Evaluate `code`, showing a failed request as its output so the other snippet
+    /// still gets compared.
+    async fn eval_side(&self, code: &str, m: &Message) -> Result<String> {
+        let settings = self.eval_settings(m.chat().id());
+        match EvalClient::intance().eval(code, settings).await {
+            Ok(resp) => Ok(resp),
+            Err(e @ (BotError::Http(_) | BotError::HttpTimeout | BotError::HttpRateLimited)) => {
+                Ok(format!("笨！\n{e}"))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run the code of the replied eval result and `code`, and show both outputs.
+    pub async fn handle_vs(&self, code: &str, m: &Message) -> Result<()> {
+        let code = code.trim();
+        let reply = match self.get_reply(m).await? {
+            Some(reply) if !code.is_empty() => reply,
+            _ => {
+                self.respond(m, "Usage: reply to an eval result with vs <code>")
+                    .await?;
+                return Ok(());
+            }
+        };
+        let Some((other, _)) = eval_parts(
+            reply.text(),
+            reply.fmt_entities().map(Vec::as_slice).unwrap_or_default(),
+        ) else {
+            self.respond(m, "Not an eval result").await?;
+            return Ok(());
+        };
+        if let Err(e) = check_code_len(code, self.conf.max_code_chars) {
+            self.respond(m, e).await?;
+            return Ok(());
+        }
+
+        self.respond(m, "少女祈祷中......").await?;
+        // One after the other, so both go through the eval throttle like any other eval.
+        let both = async {
+            let a = self.eval_side(&other, m).await?;
+            let b = self.eval_side(code, m).await?;
+            Ok::<_, BotError>((a, b))
+        };
+        let (a, b) = self.with_action(m, ChatAction::Typing, both).await?;
+        Self::edit_pre_msg(&self.responder(m), &render_vs(&a, &b), "Output").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_vs() {
+        assert_eq!(
+            render_vs("1\n", "2"),
+            "A (replied)\n1\n\nB\n2\n\nOutputs differ"
+        );
+        assert!(render_vs("()", "() ").ends_with("Same output"));
+    }
+}
@@ -0,0 +1,194 @@
+use std::{collections::HashMap, time::Duration};
+
+use grammers_client::types::Message;
+use serde_json::{Map, Value};
+use tokio::{process::Command, sync::watch};
+
+use super::{TomorinClient, shellwords::split_args, state::Inspect};
+use crate::error::{BotError, Result};
+
+/// Stop signals for the running watches, per chat.
+#[derive(Debug, Default)]
+pub struct Watches {
+    stops: HashMap<i64, watch::Sender<()>>,
+}
+
+impl Watches {
+    fn subscribe(&mut self, chat: i64) -> watch::Receiver<()> {
+        self.stops
+            .entry(chat)
+            .or_insert_with(|| watch::channel(()).0)
+            .subscribe()
+    }
+
+    /// Stop every watch in `chat`, returning how many there were.
+    fn stop(&mut self, chat: i64) -> usize {
+        match self.stops.remove(&chat) {
+            Some(stop) => {
+                let count = stop.receiver_count();
+                stop.send_replace(());
+                count
+            }
+            None => 0,
+        }
+    }
+
+    /// Stop every watch in every chat, returning how many there were.
+    pub(super) fn stop_all(&mut self) -> usize {
+        self.stops
+            .drain()
+            .map(|(_, stop)| {
+                let count = stop.receiver_count();
+                stop.send_replace(());
+                count
+            })
+            .sum()
+    }
+}
+
+/// Parse the `<interval> <command>` arguments of `watch`, the interval in seconds.
+fn parse_watch_args(args: &str) -> Option<(Duration, &str)> {
+    let (interval, cmd) = args.split_once(char::is_whitespace)?;
+    let interval = interval.strip_suffix('s').unwrap_or(interval);
+    let interval = Duration::from_secs(interval.parse().ok()?);
+    let cmd = cmd.trim();
+    (!cmd.is_empty()).then_some((interval, cmd))
+}
+
+impl TomorinClient {
+    const WATCH_MIN_INTERVAL: Duration = Duration::from_secs(2);
+
+    /// Re-run a command every interval and show its latest output, until `watch stop`.
+    pub async fn handle_watch(&self, args: &str, m: &Message) -> Result<()> {
+        if args == "stop" {
+            let stopped = self.watches.lock().unwrap().stop(m.chat().id());
+            self.respond(m, format!("Stopped {stopped} watch(es)"))
+                .await?;
+            return Ok(());
+        }
+
+        let Some((interval, cmd)) = parse_watch_args(args) else {
+            self.respond(m, "Usage: watch <seconds> <command> | watch stop")
+                .await?;
+            return Ok(());
+        };
+        if interval < Self::WATCH_MIN_INTERVAL {
+            self.respond(
+                m,
+                format!(
+                    "笨！\nThe interval must be at least {}s",
+                    Self::WATCH_MIN_INTERVAL.as_secs()
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        let words = match split_args(cmd) {
+            Ok(words) => words,
+            Err(e) => {
+                self.respond(m, format!("笨！\n{e}")).await?;
+                return Ok(());
+            }
+        };
+        let Some((program, args)) = words.split_first() else {
+            self.respond(m, "Usage: watch <seconds> <command> | watch stop")
+                .await?;
+            return Ok(());
+        };
+        if !self.may_spawn(program, m).await? {
+            return Ok(());
+        }
+
+        let mut stop = self.watches.lock().unwrap().subscribe(m.chat().id());
+        loop {
+            let mut command = Command::new(program);
+            command.args(args);
+            let cap = self.conf.output_max_bytes;
+            let output = match crate::eval::output_capped(&mut command, interval, cap).await {
+                Ok(Some((output, capped))) => {
+                    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                    text.push_str(&String::from_utf8_lossy(&output.stderr));
+                    if capped {
+                        text.push_str(&format!("\n[output truncated at {cap} bytes]"));
+                    }
+                    text
+                }
+                Ok(None) => "(timed out)".to_string(),
+                Err(e @ BotError::Spawn(_)) => {
+                    self.respond(m, format!("笨！\n{e}")).await?;
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+
+            let header = format!(
+                "Every {}s: {cmd}    {}",
+                interval.as_secs(),
+                chrono::Local::now().format("%H:%M:%S")
+            );
+            Self::edit_pre_msg(
+                &self.responder(m),
+                &format!("{header}\n\n{}", output.trim()),
+                "Watch",
+            )
+            .await?;
+
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = stop.changed() => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Inspect for Watches {
+    fn size(&self) -> usize {
+        self.stops.len()
+    }
+
+    /// The number of watches running in each chat.
+    fn dump(&self) -> Value {
+        self.stops
+            .iter()
+            .map(|(chat, stop)| (chat.to_string(), stop.receiver_count().into()))
+            .collect::<Map<_, _>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_watch_args() {
+        assert_eq!(
+            parse_watch_args("5 df -h"),
+            Some((Duration::from_secs(5), "df -h"))
+        );
+        assert_eq!(
+            parse_watch_args("10s uptime"),
+            Some((Duration::from_secs(10), "uptime"))
+        );
+        assert_eq!(parse_watch_args("5"), None);
+        assert_eq!(parse_watch_args("five uptime"), None);
+    }
+
+    #[tokio::test]
+    async fn test_watches_stop() {
+        let mut watches = Watches::default();
+        let mut first = watches.subscribe(1);
+        let _second = watches.subscribe(1);
+        let mut other = watches.subscribe(2);
+
+        assert_eq!(watches.stop(1), 2);
+        assert!(first.changed().await.is_ok());
+        assert!(!other.has_changed().unwrap());
+        assert_eq!(watches.stop(1), 0);
+
+        assert_eq!(watches.stop_all(), 1);
+        assert!(other.changed().await.is_ok());
+        assert_eq!(watches.stop_all(), 0);
+    }
+}
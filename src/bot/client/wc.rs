@@ -0,0 +1,76 @@
+use grammers_client::types::Message;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::TomorinClient;
+use crate::error::Result;
+
+/// Telegram measures its message length limit in UTF-16 code units.
+const MAX_MESSAGE_UTF16: usize = 4096;
+
+#[derive(Debug, PartialEq)]
+struct Counts {
+    lines: usize,
+    words: usize,
+    chars: usize,
+    graphemes: usize,
+    width: usize,
+    utf16: usize,
+}
+
+impl Counts {
+    fn of(text: &str) -> Self {
+        Self {
+            lines: text.lines().count(),
+            words: text.split_whitespace().count(),
+            chars: text.chars().count(),
+            graphemes: text.graphemes(true).count(),
+            width: text.width_cjk(),
+            utf16: text.encode_utf16().count(),
+        }
+    }
+}
+
+impl TomorinClient {
+    pub async fn handle_wc(&self, m: &Message) -> Result<()> {
+        let Some(reply) = self.get_reply(m).await? else {
+            self.respond(m, "Reply to a message to count it").await?;
+            return Ok(());
+        };
+
+        let Counts {
+            lines,
+            words,
+            chars,
+            graphemes,
+            width,
+            utf16,
+        } = Counts::of(reply.text());
+        let summary = format!(
+            "Lines {lines} · Words {words} · Chars {chars} · Graphemes {graphemes} · Width {width}
+UTF-16 {utf16}/{MAX_MESSAGE_UTF16}"
+        );
+        self.respond(m, summary).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        assert_eq!(
+            Counts::of("hello 世界\n👍🏽 ok"),
+            Counts {
+                lines: 2,
+                words: 4,
+                chars: 14,
+                graphemes: 13,
+                width: 16,
+                utf16: 16,
+            }
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+
+use unicode_width::UnicodeWidthChar;
+
+/// Break the lines of `text` wider than `columns`, wide (CJK) characters counting as two.
+///
+/// Lines are broken at any character, as output is not prose; a character wider than
+/// `columns` still gets a line of its own.
+pub fn wrap_lines(text: &str, columns: usize) -> Cow<'_, str> {
+    let fits = |line: &str| {
+        line.chars()
+            .map(|c| c.width_cjk().unwrap_or(0))
+            .sum::<usize>()
+            <= columns
+    };
+    if columns == 0 || text.lines().all(fits) {
+        return text.into();
+    }
+
+    let mut wrapped = String::with_capacity(text.len() + text.len() / columns);
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            wrapped.push('\n');
+        }
+        let mut width = 0;
+        for c in line.chars() {
+            let w = c.width_cjk().unwrap_or(0);
+            if width + w > columns && width > 0 {
+                wrapped.push('\n');
+                width = 0;
+            }
+            wrapped.push(c);
+            width += w;
+        }
+    }
+    wrapped.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_lines() {
+        assert!(matches!(wrap_lines("short\nlines", 10), Cow::Borrowed(_)));
+        assert_eq!(wrap_lines("abcdefgh\nij", 3), "abc\ndef\ngh\nij");
+        assert_eq!(wrap_lines("abcdef", 3), "abc\ndef");
+        assert_eq!(wrap_lines("abc", 0), "abc");
+        // Wide characters are not split across the boundary.
+        assert_eq!(wrap_lines("一二三四五", 5), "一二\n三四\n五");
+        assert_eq!(wrap_lines("a一", 2), "a\n一");
+        assert_eq!(wrap_lines("一", 1), "一");
+        assert_eq!(wrap_lines("abcd\n\nab", 2), "ab\ncd\n\nab");
+    }
+}
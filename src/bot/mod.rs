@@ -1,42 +1,172 @@
 mod client;
+mod watchdog;
 
-use client::TomorinClient;
-use futures_util::future::{Either, select};
+use client::{DrainRequest, TomorinClient};
 use std::{pin::pin, sync::Arc, time::Duration};
-use tokio::task;
+use tokio::{
+    sync::mpsc,
+    task::{self, JoinSet},
+};
+use watchdog::Watchdog;
 
-use super::conf::Conf;
+use super::{
+    conf::Conf,
+    error::BotError,
+    retry::{RetryPolicy, Verdict, retry},
+};
+
+/// Reconnecting after a stall is retried for a few minutes, then left to the next stall.
+const RECONNECT_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 5,
+    base_delay: Duration::from_secs(5),
+    max_delay: Duration::from_secs(60),
+    jitter: 0.5,
+};
+
+/// Resolve once the process is asked to stop: on Ctrl-C, or on SIGTERM as sent by
+/// systemd or Docker.
+async fn exit_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => tracing::info!("Received SIGTERM"),
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {e}");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// What woke the update loop up.
+enum Wake {
+    Update(Box<Result<grammers_client::Update, BotError>>),
+    Stalled,
+    Switched(Arc<TomorinClient>),
+    /// A handler finished, as task id.
+    Joined(task::Id),
+    Drain(Box<DrainRequest>),
+    Exit,
+}
+
+/// A `,drain` waiting for the handlers that were running to finish.
+struct Drain {
+    request: DrainRequest,
+    finished: usize,
+}
 
 pub struct UserBot {
     client: Arc<TomorinClient>,
+    watchdog: Arc<Watchdog>,
+    /// Clients logged into another account by `,account switch`.
+    switched: mpsc::UnboundedReceiver<Arc<TomorinClient>>,
+    drains: mpsc::UnboundedReceiver<DrainRequest>,
 }
 
 impl UserBot {
     pub async fn new(conf: Conf) -> anyhow::Result<Self> {
+        let watchdog = Arc::new(Watchdog::new(*conf.watchdog_stall_timeout));
+        let (switch, switched) = mpsc::unbounded_channel();
+        let (drain, drains) = mpsc::unbounded_channel();
         Ok(Self {
-            client: Arc::new(TomorinClient::new(conf).await?),
+            client: Arc::new(TomorinClient::new(conf, switch, drain).await?),
+            watchdog,
+            switched,
+            drains,
         })
     }
 
-    pub async fn run(self) -> anyhow::Result<()> {
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        self.client.announce_startup().await;
+        let timer = self.watchdog.spawn_timer();
+        let rss_sampler = TomorinClient::spawn_rss_sampler();
+        // Created once, so a signal arriving while an update is dispatched is not missed.
+        let mut exit = pin!(exit_signal());
+        // Handlers started while not draining, which a drain waits for.
+        let mut handlers = JoinSet::new();
+        let mut drain: Option<Drain> = None;
         loop {
-            let exit = pin!(async { tokio::signal::ctrl_c().await });
-            let upd = pin!(async { self.client.next_update().await });
+            if handlers.is_empty()
+                && let Some(Drain { request, finished }) = drain.take()
+            {
+                let client = self.client.clone();
+                task::spawn(async move { client.report_drained(&request.message, finished).await });
+            }
 
-            let update = match select(exit, upd).await {
-                Either::Left(_) => break,
-                Either::Right((u, _)) => u,
+            let wake = tokio::select! {
+                _ = exit.as_mut() => Wake::Exit,
+                u = self.client.next_update() => Wake::Update(Box::new(u)),
+                _ = self.watchdog.stalled() => Wake::Stalled,
+                Some(client) = self.switched.recv() => Wake::Switched(client),
+                Some(joined) = handlers.join_next_with_id() => {
+                    Wake::Joined(joined.map_or_else(|e| e.id(), |(id, ())| id))
+                }
+                Some(request) = self.drains.recv() => Wake::Drain(Box::new(request)),
             };
-
-            let Ok(update) = update else {
-                tracing::warn!("Failed to get update");
-                continue;
+            let update = match wake {
+                Wake::Update(update) => *update,
+                Wake::Stalled => {
+                    let client = &self.client;
+                    let reconnect = || client.reconnect();
+                    match retry(&RECONNECT_RETRY, "reconnect", reconnect, |_| Verdict::Retry).await
+                    {
+                        Ok(client) => self.client = Arc::new(client),
+                        Err(e) => tracing::error!("Failed to reconnect: {e}"),
+                    }
+                    continue;
+                }
+                Wake::Switched(client) => {
+                    self.client = client;
+                    self.watchdog.touch();
+                    continue;
+                }
+                Wake::Joined(id) => {
+                    if let Some(drain) = &mut drain
+                        && drain.request.task != Some(id)
+                    {
+                        drain.finished += 1;
+                    }
+                    continue;
+                }
+                Wake::Drain(request) => {
+                    drain = Some(Drain {
+                        request: *request,
+                        finished: 0,
+                    });
+                    continue;
+                }
+                Wake::Exit => break,
+            };
+            let update = match update {
+                Ok(update) => update,
+                Err(BotError::SessionRevoked(reason)) => self.client.session_revoked(&reason),
+                Err(_) => {
+                    tracing::warn!("Failed to get update");
+                    continue;
+                }
             };
+            self.watchdog.touch();
 
             let client = self.client.clone();
-            task::spawn(async move {
-                match client.update(update).await {
+            let watchdog = self.watchdog.clone();
+            let handle = async move {
+                let handled = client.update_isolated(update).await;
+                watchdog.touch();
+                match handled {
                     Ok(_) => {}
+                    Err(BotError::SessionRevoked(reason)) => client.session_revoked(&reason),
+                    Err(e) if client.errors_muted() => {
+                        tracing::debug!("Error handling update (muted): {e}");
+                    }
                     Err(e) => {
                         tracing::error!("Error handling update: {e}");
                         tracing::error!("Tomorin will retry after 60 secs");
@@ -44,9 +174,18 @@ impl UserBot {
                         tokio::time::sleep(Duration::from_secs(30)).await;
                     }
                 }
-            });
+            };
+            // Updates arriving while draining are only refused, which is not waited for.
+            if self.client.is_draining() {
+                task::spawn(handle);
+            } else {
+                handlers.spawn(handle);
+            }
         }
+        timer.abort();
+        rss_sampler.abort();
 
+        self.client.save_state();
         Ok(())
     }
 }
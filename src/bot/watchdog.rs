@@ -0,0 +1,83 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::{sync::Notify, task::JoinHandle};
+
+/// Detects a stalled update loop: no update received and no command finished for too long.
+#[derive(Debug)]
+pub struct Watchdog {
+    last_activity: Mutex<Instant>,
+    stall_timeout: Duration,
+    stalled: Notify,
+}
+
+impl Watchdog {
+    pub fn new(stall_timeout: Duration) -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            stall_timeout,
+            stalled: Notify::new(),
+        }
+    }
+
+    /// Record activity, postponing the next stall.
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    fn idle_at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(*self.last_activity.lock().unwrap())
+    }
+
+    fn is_stalled_at(&self, now: Instant) -> bool {
+        self.idle_at(now) >= self.stall_timeout
+    }
+
+    /// Resolve once the timer task spawned by [`Watchdog::spawn_timer`] detects a stall.
+    pub async fn stalled(&self) {
+        self.stalled.notified().await
+    }
+
+    /// Check for stalls periodically until the returned task is aborted.
+    pub fn spawn_timer(self: &Arc<Self>) -> JoinHandle<()> {
+        let watchdog = self.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval((watchdog.stall_timeout / 4).max(Duration::from_secs(1)));
+            loop {
+                interval.tick().await;
+                let now = Instant::now();
+                if watchdog.is_stalled_at(now) {
+                    tracing::warn!(
+                        "No activity for {}, forcing a reconnect",
+                        humantime::format_duration(Duration::from_secs(
+                            watchdog.idle_at(now).as_secs()
+                        ))
+                    );
+                    // Only warn once per stall.
+                    watchdog.touch();
+                    watchdog.stalled.notify_one();
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stalled_at() {
+        let watchdog = Watchdog::new(Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(!watchdog.is_stalled_at(now));
+        assert!(!watchdog.is_stalled_at(now + Duration::from_secs(30)));
+        assert!(watchdog.is_stalled_at(now + Duration::from_secs(61)));
+
+        watchdog.touch();
+        assert!(!watchdog.is_stalled_at(Instant::now() + Duration::from_secs(30)));
+    }
+}
@@ -1,12 +1,15 @@
 use std::{
     fs::{create_dir_all, write},
-    path::Path,
+    path::{Path, PathBuf},
     process,
+    time::Duration,
 };
 
 use miette::{IntoDiagnostic, miette};
+use serde::{Serialize, Serializer};
 
-#[derive(knuffel::Decode, Debug, PartialEq, Default)]
+#[derive(knuffel::Decode, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
 pub struct Conf {
     #[knuffel(child, unwrap(argument), default)]
     pub api_id: i32,
@@ -14,9 +17,307 @@ pub struct Conf {
     pub api_hash: String,
     #[knuffel(child, unwrap(argument), default)]
     pub phone: String,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_secs(1).into())]
+    #[serde(serialize_with = "display")]
+    pub eval_min_interval: humantime::Duration,
+    #[knuffel(child, unwrap(argument), default = 2)]
+    pub eval_retries: u32,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_millis(500).into())]
+    #[serde(serialize_with = "display")]
+    pub eval_retry_backoff: humantime::Duration,
+    #[knuffel(child)]
+    pub eval_prelude: Option<EvalPrelude>,
+    #[knuffel(child, unwrap(argument), default)]
+    pub eval_backend: EvalBackend,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_secs(10).into())]
+    #[serde(serialize_with = "display")]
+    pub eval_local_timeout: humantime::Duration,
+    #[knuffel(child, unwrap(argument), default = 50_000)]
+    pub max_code_chars: usize,
+    #[knuffel(child, unwrap(argument), default = "python3".into())]
+    pub python_interpreter: String,
+    #[knuffel(child, unwrap(argument), default = "node".into())]
+    pub js_interpreter: String,
+    #[knuffel(child, unwrap(argument), default = "sh".into())]
+    pub sh_interpreter: String,
+    #[knuffel(child, unwrap(argument))]
+    pub user_agent: Option<String>,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_secs(90).into())]
+    #[serde(serialize_with = "display")]
+    pub http_pool_idle_timeout: humantime::Duration,
+    #[knuffel(child, unwrap(argument), default = 8)]
+    pub http_pool_max_idle_per_host: usize,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_secs(60).into())]
+    #[serde(serialize_with = "display")]
+    pub http_tcp_keepalive: humantime::Duration,
+    #[knuffel(child, unwrap(argument, str))]
+    pub tls_root_cert: Option<PathBuf>,
+    /// Disables certificate verification entirely. Insecure, only meant for testing.
+    #[knuffel(child, unwrap(argument), default)]
+    pub danger_accept_invalid_certs: bool,
+    #[knuffel(child, unwrap(argument), default = 1000)]
+    pub output_tick_ms: u64,
+    #[knuffel(child, unwrap(argument), default = 800)]
+    pub output_initial_delay_ms: u64,
+    #[knuffel(child, unwrap(argument), default = 1024 * 1024)]
+    pub output_max_bytes: usize,
+    #[knuffel(child, unwrap(argument), default = true)]
+    pub output_overflow_kill: bool,
+    /// Programs no command may spawn, matched by file name.
+    #[knuffel(child, unwrap(arguments), default)]
+    pub cmd_denylist: Vec<String>,
+    #[knuffel(
+        child,
+        unwrap(arguments),
+        default = ["shutdown", "reboot", "poweroff"].map(String::from).into()
+    )]
+    pub confirm_commands: Vec<String>,
+    #[knuffel(child, unwrap(argument), default = 20)]
+    pub history_size: usize,
+    #[knuffel(child, unwrap(argument, str))]
+    pub shot_font: Option<PathBuf>,
+    #[knuffel(child, unwrap(argument), default = 18)]
+    pub shot_font_size: u32,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_secs(60).into())]
+    #[serde(serialize_with = "display")]
+    pub shot_timeout: humantime::Duration,
+    #[knuffel(child, unwrap(argument), default)]
+    pub auto_eval_fences: bool,
+    #[knuffel(child, unwrap(argument), default)]
+    pub enable_raw_tl: bool,
+    #[knuffel(child, unwrap(argument), default)]
+    pub reply_mode: ReplyMode,
+    #[knuffel(child, unwrap(argument), default)]
+    pub silent_output: bool,
+    #[knuffel(child, unwrap(argument), default)]
+    pub output_truncation: Truncation,
+    #[knuffel(child, unwrap(argument), default)]
+    pub output_mode: OutputMode,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_secs(30 * 60).into())]
+    #[serde(serialize_with = "display")]
+    pub watchdog_stall_timeout: humantime::Duration,
+    #[knuffel(child, unwrap(argument), default)]
+    pub delete_revoked_session: bool,
+    #[knuffel(child, unwrap(argument))]
+    pub startup_chat: Option<String>,
+    #[knuffel(child, unwrap(argument), default)]
+    pub login_banner: bool,
+    #[knuffel(child, unwrap(argument), default = 1800)]
+    pub outbound_global_rate: u32,
+    #[knuffel(child, unwrap(argument), default = 60)]
+    pub outbound_chat_rate: u32,
+    #[knuffel(child, unwrap(argument))]
+    pub wrap_columns: Option<usize>,
+    #[knuffel(children(name = "macro"))]
+    pub macros: Vec<Macro>,
+    #[knuffel(child, unwrap(argument, str), default = Duration::from_secs(5 * 60).into())]
+    #[serde(serialize_with = "display")]
+    pub macro_step_timeout: humantime::Duration,
+    #[knuffel(child, unwrap(argument, str))]
+    pub log_file: Option<PathBuf>,
+    #[knuffel(child, unwrap(argument), default = true)]
+    pub chat_actions: bool,
+    #[knuffel(child, unwrap(argument), default)]
+    pub eval_render: EvalRender,
+    #[knuffel(child, unwrap(argument), default)]
+    pub report_panics: bool,
+}
+
+/// How command output is shown.
+#[derive(knuffel::DecodeScalar, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReplyMode {
+    /// Replace the command message with the output.
+    #[default]
+    Edit,
+    /// Keep the command message and send the output as a reply to it.
+    Reply,
+}
+
+/// How command output too long to show in full is delivered.
+#[derive(knuffel::DecodeScalar, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputMode {
+    /// Only show the truncated output in the message.
+    #[default]
+    Inline,
+    /// Also send the full output as a text file.
+    File,
+    /// Also send the full output rendered as an image.
+    Image,
+}
+
+/// How evaluated code and its output are formatted.
+#[derive(knuffel::DecodeScalar, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvalRender {
+    /// Set `Pre` entities on the plain text.
+    #[default]
+    Pre,
+    /// Write markdown code fences and let them be parsed into entities.
+    Markdown,
+}
+
+/// Which lines of long command output are kept.
+#[derive(knuffel::DecodeScalar, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Truncation {
+    /// Keep the last lines.
+    #[default]
+    Tail,
+    /// Keep the first lines.
+    Head,
+}
+
+/// Where code is evaluated.
+#[derive(knuffel::DecodeScalar, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvalBackend {
+    /// The Rust playground at play.rust-lang.org.
+    #[default]
+    Playground,
+    /// The locally installed `rustc`, with a prelude limited to `std`.
+    Local,
+}
+
+/// Extra prelude for evaluated code, given inline or read from a file.
+#[derive(knuffel::Decode, Serialize, Debug, PartialEq)]
+pub struct EvalPrelude {
+    #[knuffel(argument)]
+    pub inline: Option<String>,
+    #[knuffel(property, str)]
+    pub file: Option<PathBuf>,
+}
+
+impl EvalPrelude {
+    /// The prelude source, with the file content following the inline part if both are set.
+    pub fn source(&self) -> std::io::Result<String> {
+        let mut source = self.inline.clone().unwrap_or_default();
+        if let Some(path) = &self.file {
+            if !source.is_empty() {
+                source.push('\n');
+            }
+            source.push_str(&std::fs::read_to_string(path)?);
+        }
+        Ok(source)
+    }
+}
+
+/// A named sequence of shell commands, run one after another by `,run <name>`.
+#[derive(knuffel::Decode, Serialize, Debug, PartialEq)]
+pub struct Macro {
+    #[knuffel(argument)]
+    pub name: String,
+    #[knuffel(children(name = "step"), unwrap(argument))]
+    pub steps: Vec<String>,
+}
+
+fn display<T: std::fmt::Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+impl Default for Conf {
+    fn default() -> Self {
+        // Every field has a default, so an empty document yields the default config.
+        knuffel::parse("default.kdl", "").expect("empty config should always parse")
+    }
+}
+
+/// Split a line of KDL into its `;`-separated nodes, leaving `;` in strings and comments.
+fn split_nodes(line: &str) -> Vec<&str> {
+    let mut nodes = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '/' if !in_string && chars.peek().is_some_and(|(_, next)| *next == '/') => break,
+            ';' if !in_string => {
+                nodes.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    nodes.push(&line[start..]);
+    nodes
 }
 
 impl Conf {
+    /// Where the config is read from, relative to the working directory.
+    pub const PATH: &str = "config.kdl";
+
+    /// Fields shown as [`Conf::REDACTED_VALUE`] by [`Conf::to_redacted_json`] and
+    /// [`Conf::redact_kdl`]. Add new secrets here.
+    const REDACTED: [&str; 2] = ["api-hash", "phone"];
+
+    /// What the values of the secrets are replaced with.
+    const REDACTED_VALUE: &str = "***";
+
+    /// The config as JSON with the secrets masked, safe to show in a chat.
+    pub fn to_redacted_json(&self) -> serde_json::Value {
+        let mut json = serde_json::to_value(self).expect("config should always serialize");
+        if let Some(fields) = json.as_object_mut() {
+            for field in Self::REDACTED {
+                if let Some(value) = fields.get_mut(field) {
+                    *value = Self::REDACTED_VALUE.into();
+                }
+            }
+        }
+        json
+    }
+
+    /// The config document `kdl` with the values of the secret nodes masked.
+    pub fn redact_kdl(kdl: &str) -> String {
+        kdl.lines()
+            .map(|line| {
+                let nodes: Vec<_> = split_nodes(line)
+                    .into_iter()
+                    .map(Self::redact_node)
+                    .collect();
+                format!("{}\n", nodes.join(";"))
+            })
+            .collect()
+    }
+
+    /// `node`, with the whitespace before it, masked if it is a secret.
+    fn redact_node(node: &str) -> String {
+        let trimmed = node.trim_start();
+        let indent = &node[..node.len() - trimmed.len()];
+        match Self::REDACTED
+            .iter()
+            .find(|name| trimmed.split_whitespace().next() == Some(name))
+        {
+            Some(name) => format!("{indent}{name} \"{}\"", Self::REDACTED_VALUE),
+            None => node.to_string(),
+        }
+    }
+
+    /// Check that `kdl` is a config [`Conf::load_or_create`] would accept, and not one
+    /// exported with its secrets redacted, which would break the next login.
+    pub fn validate(kdl: &str) -> Result<(), String> {
+        let conf = knuffel::parse::<Conf>(Self::PATH, kdl).map_err(|e| e.to_string())?;
+        let redacted: Vec<_> = [("api-hash", &conf.api_hash), ("phone", &conf.phone)]
+            .into_iter()
+            .filter(|(_, value)| *value == Self::REDACTED_VALUE)
+            .map(|(name, _)| name)
+            .collect();
+        if !redacted.is_empty() {
+            return Err(format!(
+                "{} still redacted, fill in the real values first",
+                redacted.join(" and ")
+            ));
+        }
+        Ok(())
+    }
+
     fn load(path: &Path) -> miette::Result<Self> {
         let contents = match std::fs::read_to_string(path).into_diagnostic() {
             Ok(contents) => contents,
@@ -39,11 +340,12 @@ impl Conf {
     }
 
     pub fn load_or_create() -> miette::Result<Self> {
-        const PATH: &str = "config.kdl";
-
-        let path = Path::new(PATH);
+        let path = Path::new(Self::PATH);
         if !path.exists() {
-            tracing::info!("config file {PATH} does not exist, creating default config");
+            tracing::info!(
+                "config file {} does not exist, creating default config",
+                Self::PATH
+            );
             create_dir_all(path.parent().unwrap()).into_diagnostic()?;
             write(path, include_str!("example.kdl")).into_diagnostic()?;
             process::exit(0);
@@ -63,10 +365,222 @@ mod tests {
             api-id 123456
             api-hash "test_api_hash"
             phone "1234567890"
+            eval-min-interval "2s 500ms"
+            eval-retries 5
+            eval-retry-backoff "1s"
+            eval-prelude "use std::fmt::Write;" file="/etc/tomorin/prelude.rs"
+            eval-backend "local"
+            eval-local-timeout "5s"
+            max-code-chars 1000
+            python-interpreter "/usr/bin/python3.12"
+            js-interpreter "deno"
+            sh-interpreter "bash"
+            user-agent "custom/1.0"
+            http-pool-idle-timeout "30s"
+            http-pool-max-idle-per-host 2
+            http-tcp-keepalive "15s"
+            tls-root-cert "/etc/ssl/corp.pem"
+            danger-accept-invalid-certs true
+            output-tick-ms 500
+            output-initial-delay-ms 200
+            output-max-bytes 4096
+            output-overflow-kill false
+            cmd-denylist "rm" "shutdown" "mkfs"
+            confirm-commands "dd"
+            history-size 5
+            shot-font "/usr/share/fonts/mono.ttf"
+            shot-font-size 24
+            shot-timeout "10s"
+            auto-eval-fences true
+            enable-raw-tl true
+            reply-mode "reply"
+            silent-output true
+            output-truncation "head"
+            output-mode "file"
+            watchdog-stall-timeout "10m"
+            delete-revoked-session true
+            startup-chat "me"
+            login-banner true
+            outbound-global-rate 600
+            outbound-chat-rate 0
+            wrap-columns 40
+            macro "deploy" {
+                step "git pull"
+                step "cargo build --release"
+            }
+            macro "empty"
+            macro-step-timeout "30s"
+            log-file "/var/log/tomorin.log"
+            chat-actions false
+            eval-render "markdown"
+            report-panics true
         "#;
         let conf: Conf = knuffel::parse("example.kdl", conf).unwrap();
         assert_eq!(conf.api_id, 123456);
         assert_eq!(conf.api_hash, "test_api_hash");
         assert_eq!(conf.phone, "1234567890");
+        assert_eq!(*conf.eval_min_interval, Duration::from_millis(2500));
+        assert_eq!(conf.eval_retries, 5);
+        assert_eq!(*conf.eval_retry_backoff, Duration::from_secs(1));
+        assert_eq!(
+            conf.eval_prelude,
+            Some(EvalPrelude {
+                inline: Some("use std::fmt::Write;".to_string()),
+                file: Some(PathBuf::from("/etc/tomorin/prelude.rs")),
+            })
+        );
+        assert_eq!(conf.eval_backend, EvalBackend::Local);
+        assert_eq!(*conf.eval_local_timeout, Duration::from_secs(5));
+        assert_eq!(conf.max_code_chars, 1000);
+        assert_eq!(conf.python_interpreter, "/usr/bin/python3.12");
+        assert_eq!(conf.js_interpreter, "deno");
+        assert_eq!(conf.sh_interpreter, "bash");
+        assert_eq!(conf.user_agent.as_deref(), Some("custom/1.0"));
+        assert_eq!(*conf.http_pool_idle_timeout, Duration::from_secs(30));
+        assert_eq!(conf.http_pool_max_idle_per_host, 2);
+        assert_eq!(*conf.http_tcp_keepalive, Duration::from_secs(15));
+        assert_eq!(conf.tls_root_cert, Some(PathBuf::from("/etc/ssl/corp.pem")));
+        assert!(conf.danger_accept_invalid_certs);
+        assert_eq!(conf.output_tick_ms, 500);
+        assert_eq!(conf.output_initial_delay_ms, 200);
+        assert_eq!(conf.output_max_bytes, 4096);
+        assert!(!conf.output_overflow_kill);
+        assert_eq!(conf.cmd_denylist, ["rm", "shutdown", "mkfs"]);
+        assert_eq!(conf.confirm_commands, ["dd"]);
+        assert_eq!(conf.history_size, 5);
+        assert_eq!(
+            conf.shot_font,
+            Some(PathBuf::from("/usr/share/fonts/mono.ttf"))
+        );
+        assert_eq!(conf.shot_font_size, 24);
+        assert_eq!(*conf.shot_timeout, Duration::from_secs(10));
+        assert!(conf.auto_eval_fences);
+        assert!(conf.enable_raw_tl);
+        assert_eq!(conf.reply_mode, ReplyMode::Reply);
+        assert!(conf.silent_output);
+        assert_eq!(conf.output_truncation, Truncation::Head);
+        assert_eq!(conf.output_mode, OutputMode::File);
+        assert_eq!(*conf.watchdog_stall_timeout, Duration::from_secs(600));
+        assert!(conf.delete_revoked_session);
+        assert_eq!(conf.startup_chat.as_deref(), Some("me"));
+        assert!(conf.login_banner);
+        assert_eq!(conf.outbound_global_rate, 600);
+        assert_eq!(conf.outbound_chat_rate, 0);
+        assert_eq!(conf.wrap_columns, Some(40));
+        assert_eq!(*conf.macro_step_timeout, Duration::from_secs(30));
+        assert_eq!(
+            conf.macros,
+            vec![
+                Macro {
+                    name: "deploy".into(),
+                    steps: vec!["git pull".into(), "cargo build --release".into()],
+                },
+                Macro {
+                    name: "empty".into(),
+                    steps: vec![],
+                },
+            ]
+        );
+        assert_eq!(
+            conf.log_file.as_deref(),
+            Some(Path::new("/var/log/tomorin.log"))
+        );
+        assert!(!conf.chat_actions);
+        assert_eq!(conf.eval_render, EvalRender::Markdown);
+        assert!(conf.report_panics);
+    }
+
+    #[test]
+    fn test_conf_redacted() {
+        let conf: Conf = knuffel::parse(
+            "example.kdl",
+            r#"
+            api-id 123456
+            api-hash "secret_hash"
+            phone "1234567890"
+            eval-min-interval "2s"
+            reply-mode "reply"
+        "#,
+        )
+        .unwrap();
+        let json = conf.to_redacted_json();
+        assert_eq!(json["api-hash"], "***");
+        assert_eq!(json["phone"], "***");
+        assert_eq!(json["api-id"], 123456);
+        assert_eq!(json["eval-min-interval"], "2s");
+        assert_eq!(json["reply-mode"], "reply");
+        assert!(!json.to_string().contains("secret_hash"));
+    }
+
+    #[test]
+    fn test_redact_kdl() {
+        let kdl = "api-id 123456\n  api-hash \"secret_hash\"\nphone \"1234567890\"\n// phone \"x\"\nphone-like 1";
+        assert_eq!(
+            Conf::redact_kdl(kdl),
+            "api-id 123456\n  api-hash \"***\"\nphone \"***\"\n// phone \"x\"\nphone-like 1\n"
+        );
+        assert_eq!(
+            Conf::redact_kdl("api-id 1; api-hash \"a;b\"; phone \"1\" // x; phone \"2\""),
+            "api-id 1; api-hash \"***\"; phone \"***\"\n"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Conf::validate("api-id 1\neval-retries 3").is_ok());
+        assert!(Conf::validate("eval-retries \"three\"").is_err());
+        assert!(Conf::validate("api-id {").is_err());
+        let exported = Conf::redact_kdl("api-hash \"secret\"\nphone \"1234\"");
+        assert!(Conf::validate(&exported).is_err());
+    }
+
+    #[test]
+    fn test_conf_default() {
+        let conf = Conf::default();
+        assert_eq!(*conf.eval_min_interval, Duration::from_secs(1));
+        assert_eq!(conf.eval_retries, 2);
+        assert_eq!(*conf.eval_retry_backoff, Duration::from_millis(500));
+        assert_eq!(conf.eval_prelude, None);
+        assert_eq!(conf.eval_backend, EvalBackend::Playground);
+        assert_eq!(*conf.eval_local_timeout, Duration::from_secs(10));
+        assert_eq!(conf.max_code_chars, 50_000);
+        assert_eq!(conf.python_interpreter, "python3");
+        assert_eq!(conf.js_interpreter, "node");
+        assert_eq!(conf.sh_interpreter, "sh");
+        assert_eq!(conf.user_agent, None);
+        assert_eq!(*conf.http_pool_idle_timeout, Duration::from_secs(90));
+        assert_eq!(conf.http_pool_max_idle_per_host, 8);
+        assert_eq!(*conf.http_tcp_keepalive, Duration::from_secs(60));
+        assert_eq!(conf.tls_root_cert, None);
+        assert!(!conf.danger_accept_invalid_certs);
+        assert_eq!(conf.output_tick_ms, 1000);
+        assert_eq!(conf.output_initial_delay_ms, 800);
+        assert_eq!(conf.output_max_bytes, 1024 * 1024);
+        assert!(conf.output_overflow_kill);
+        assert!(conf.cmd_denylist.is_empty());
+        assert_eq!(conf.confirm_commands, ["shutdown", "reboot", "poweroff"]);
+        assert_eq!(conf.history_size, 20);
+        assert_eq!(conf.shot_font, None);
+        assert_eq!(conf.shot_font_size, 18);
+        assert_eq!(*conf.shot_timeout, Duration::from_secs(60));
+        assert!(!conf.auto_eval_fences);
+        assert!(!conf.enable_raw_tl);
+        assert_eq!(conf.reply_mode, ReplyMode::Edit);
+        assert!(!conf.silent_output);
+        assert_eq!(conf.output_truncation, Truncation::Tail);
+        assert_eq!(conf.output_mode, OutputMode::Inline);
+        assert_eq!(*conf.watchdog_stall_timeout, Duration::from_secs(1800));
+        assert!(!conf.delete_revoked_session);
+        assert_eq!(conf.startup_chat, None);
+        assert!(!conf.login_banner);
+        assert_eq!(conf.outbound_global_rate, 1800);
+        assert_eq!(conf.outbound_chat_rate, 60);
+        assert_eq!(conf.wrap_columns, None);
+        assert!(conf.macros.is_empty());
+        assert_eq!(*conf.macro_step_timeout, Duration::from_secs(300));
+        assert_eq!(conf.log_file, None);
+        assert!(conf.chat_actions);
+        assert_eq!(conf.eval_render, EvalRender::Pre);
+        assert!(!conf.report_panics);
     }
 }
@@ -0,0 +1,65 @@
+use grammers_client::InvocationError;
+use reqwest::StatusCode;
+
+pub type Result<T, E = BotError> = std::result::Result<T, E>;
+
+/// Errors handlers can run into, split by cause so they can be reported appropriately.
+#[derive(Debug, thiserror::Error)]
+pub enum BotError {
+    #[error("Telegram asked to wait {0} seconds")]
+    FloodWait(u32),
+    #[error("Telegram error: {0}")]
+    Telegram(InvocationError),
+    #[error("Telegram session is no longer valid ({0}), log in again")]
+    SessionRevoked(String),
+    #[error("HTTP request timed out")]
+    HttpTimeout,
+    #[error("HTTP request was rate limited, try again later")]
+    HttpRateLimited,
+    #[error("HTTP request failed: {0}")]
+    Http(reqwest::Error),
+    #[error("Failed to spawn command: {0}")]
+    Spawn(std::io::Error),
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// RPC errors meaning the session was revoked or expired, so retrying is pointless.
+const SESSION_INVALID: [&str; 6] = [
+    "AUTH_KEY_UNREGISTERED",
+    "AUTH_KEY_INVALID",
+    "SESSION_REVOKED",
+    "SESSION_EXPIRED",
+    "USER_DEACTIVATED",
+    "USER_DEACTIVATED_BAN",
+];
+
+impl From<InvocationError> for BotError {
+    fn from(e: InvocationError) -> Self {
+        match &e {
+            InvocationError::Rpc(rpc) if rpc.name == "FLOOD_WAIT" => {
+                BotError::FloodWait(rpc.value.unwrap_or_default())
+            }
+            InvocationError::Rpc(rpc) if SESSION_INVALID.contains(&rpc.name.as_str()) => {
+                BotError::SessionRevoked(rpc.name.clone())
+            }
+            _ => BotError::Telegram(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for BotError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            BotError::HttpTimeout
+        } else if e.status() == Some(StatusCode::TOO_MANY_REQUESTS) {
+            BotError::HttpRateLimited
+        } else {
+            BotError::Http(e)
+        }
+    }
+}
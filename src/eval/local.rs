@@ -0,0 +1,307 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use tokio::{io::AsyncReadExt, process::Command};
+
+use super::types::{EvalSettings, Mode, Response};
+use crate::error::{BotError, Result};
+
+/// A scratch directory removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> std::io::Result<Self> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let name = format!(
+            "tomorin-eval-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            tracing::warn!("failed to remove {:?}: {e}", self.0);
+        }
+    }
+}
+
+/// Run `command`, killing it once `timeout` has passed.
+///
+/// Returns `None` on timeout.
+pub async fn output_within(
+    command: &mut Command,
+    timeout: Duration,
+) -> Result<Option<std::process::Output>> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(BotError::Spawn)?;
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(output) => Ok(Some(output?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Run `command` like [`output_within`], but stop reading once stdout and stderr together
+/// passed `cap` bytes and kill the command then, so a chatty one can't use up memory.
+///
+/// Returns the output cut to `cap` bytes and whether it was cut, or `None` on timeout.
+pub async fn output_capped(
+    command: &mut Command,
+    timeout: Duration,
+    cap: usize,
+) -> Result<Option<(std::process::Output, bool)>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(BotError::Spawn)?;
+    let (Some(mut out), Some(mut err)) = (child.stdout.take(), child.stderr.take()) else {
+        unreachable!("both streams are piped");
+    };
+
+    let run = async {
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let (mut out_buf, mut err_buf) = ([0; 4096], [0; 4096]);
+        let (mut out_done, mut err_done) = (false, false);
+        let mut capped = false;
+        while !(out_done && err_done) {
+            tokio::select! {
+                n = out.read(&mut out_buf), if !out_done => match n? {
+                    0 => out_done = true,
+                    n => stdout.extend_from_slice(&out_buf[..n]),
+                },
+                n = err.read(&mut err_buf), if !err_done => match n? {
+                    0 => err_done = true,
+                    n => stderr.extend_from_slice(&err_buf[..n]),
+                },
+            }
+            if stdout.len() + stderr.len() > cap {
+                capped = true;
+                child.start_kill()?;
+                break;
+            }
+        }
+        let status = child.wait().await?;
+        stdout.truncate(cap);
+        stderr.truncate(cap - stdout.len());
+        let output = std::process::Output {
+            status,
+            stdout,
+            stderr,
+        };
+        Ok::<_, std::io::Error>((output, capped))
+    };
+    match tokio::time::timeout(timeout, run).await {
+        Ok(output) => Ok(Some(output?)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn timed_out(stage: &str, timeout: Duration) -> Response {
+    Response {
+        stderr: format!(
+            "error: {stage} timed out after {}",
+            humantime::format_duration(timeout)
+        ),
+        stdout: String::new(),
+        success: false,
+    }
+}
+
+/// `stderr` of a step whose output was cut at `cap` bytes, saying so.
+fn stderr_of(output: &std::process::Output, capped: bool, cap: usize) -> String {
+    let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if capped {
+        stderr.push_str(&format!("\nerror: output truncated at {cap} bytes"));
+    }
+    stderr
+}
+
+/// Compile and run the complete program `code` with the local `rustc`, in a temporary
+/// directory that is cleaned up afterwards. `timeout` applies to each of both steps, and
+/// a step printing more than `cap` bytes is killed.
+pub async fn execute(
+    code: &str,
+    settings: EvalSettings,
+    timeout: Duration,
+    cap: usize,
+) -> Result<Response> {
+    let dir = TempDir::new()?;
+    let source = dir.0.join("main.rs");
+    let binary = dir.0.join("main");
+    tokio::fs::write(&source, code).await?;
+
+    let mut rustc = Command::new("rustc");
+    rustc
+        .current_dir(&dir.0)
+        .args(["--edition", settings.edition, "-o"])
+        .arg(&binary)
+        .arg(&source);
+    if settings.mode == Mode::Release {
+        rustc.arg("-O");
+    }
+    let Some((compiled, capped)) = output_capped(&mut rustc, timeout, cap).await? else {
+        return Ok(timed_out("compilation", timeout));
+    };
+    if !compiled.status.success() || capped {
+        return Ok(Response {
+            stderr: stderr_of(&compiled, capped, cap),
+            stdout: String::new(),
+            success: false,
+        });
+    }
+
+    let mut program = Command::new(&binary);
+    program.current_dir(&dir.0);
+    let Some((run, capped)) = output_capped(&mut program, timeout, cap).await? else {
+        return Ok(timed_out("execution", timeout));
+    };
+    // Like on the playground, the compiler's warnings come before the program's stderr.
+    let mut stderr = String::from_utf8_lossy(&compiled.stderr).into_owned();
+    stderr.push_str(&stderr_of(&run, capped, cap));
+    Ok(Response {
+        stderr,
+        stdout: String::from_utf8_lossy(&run.stdout).into_owned(),
+        success: run.status.success() && !capped,
+    })
+}
+
+/// Type-check `code` with the local `rustc` without generating or running a binary,
+/// like `cargo check` does.
+pub async fn check(code: &str, edition: &str, timeout: Duration, cap: usize) -> Result<Response> {
+    let dir = TempDir::new()?;
+    let source = dir.0.join("main.rs");
+    tokio::fs::write(&source, code).await?;
+
+    let mut rustc = Command::new("rustc");
+    rustc
+        .current_dir(&dir.0)
+        .args(["--edition", edition, "--emit=metadata"])
+        .arg(&source);
+    let Some((checked, capped)) = output_capped(&mut rustc, timeout, cap).await? else {
+        return Ok(timed_out("compilation", timeout));
+    };
+    Ok(Response {
+        stderr: stderr_of(&checked, capped, cap),
+        stdout: String::new(),
+        success: checked.status.success() && !capped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::run::{CodeOptions, generate_code_to_send};
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_capped() {
+        let mut yes = Command::new("yes");
+        let (output, capped) = output_capped(&mut yes, Duration::from_secs(5), 10)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(capped);
+        assert_eq!(output.stdout, b"y\ny\ny\ny\ny\n");
+
+        let mut echo = Command::new("echo");
+        echo.arg("hi");
+        let (output, capped) = output_capped(&mut echo, Duration::from_secs(5), 10)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!capped);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hi\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute() {
+        let options = CodeOptions {
+            std_only: true,
+            ..Default::default()
+        };
+        let code = generate_code_to_send("HashSet::from([1, 1, 2]).len()", &options);
+        let resp = execute(
+            &code,
+            EvalSettings::default(),
+            Duration::from_secs(60),
+            1 << 20,
+        )
+        .await
+        .unwrap();
+        assert!(resp.success, "{}", resp.stderr);
+        assert_eq!(resp.stdout, "2\n");
+
+        let resp = execute(
+            "fn main() { let x: u32 = \"\"; }",
+            EvalSettings::default(),
+            Duration::from_secs(60),
+            1 << 20,
+        )
+        .await
+        .unwrap();
+        assert!(!resp.success);
+        assert!(resp.stderr.contains("error[E0308]"));
+
+        // `async` is only a keyword from 2018 on.
+        let settings = EvalSettings {
+            edition: "2015",
+            ..Default::default()
+        };
+        let resp = execute(
+            "fn main() { let async = 1; }",
+            settings,
+            Duration::from_secs(60),
+            1 << 20,
+        )
+        .await
+        .unwrap();
+        assert!(resp.success, "{}", resp.stderr);
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let resp = check(
+            "fn main() { loop {} }",
+            "2024",
+            Duration::from_secs(60),
+            1 << 20,
+        )
+        .await
+        .unwrap();
+        assert!(resp.success, "{}", resp.stderr);
+
+        let resp = check(
+            "fn main() { let x: u32 = \"\"; }",
+            "2024",
+            Duration::from_secs(60),
+            1 << 20,
+        )
+        .await
+        .unwrap();
+        assert!(!resp.success);
+        assert!(resp.stderr.contains("error[E0308]"));
+    }
+}
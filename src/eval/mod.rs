@@ -1,49 +1,418 @@
 // Most code of this module is copied from https://github.com/upsuper/telegram-rustevalbot
 
-use std::sync::LazyLock;
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
-use anyhow::Ok;
+use futures_util::FutureExt;
+use tokio::{
+    sync::{Mutex, OnceCell},
+    time::Instant,
+};
 
+mod local;
 mod run;
 mod types;
 
 use run::*;
 use types::*;
 
+pub use local::{TempDir, output_capped, output_within};
+pub use run::defines_main;
+pub use types::{Channel, CrateVersion, EDITIONS, EvalInfo, EvalSettings, Mode};
+
+use crate::conf::{Conf, EvalBackend};
+use crate::error::{BotError, Result};
+use crate::retry::{RetryPolicy, Verdict, retry};
+
 const EVAL_URL: &str = "https://play.rust-lang.org/execute";
+const GIST_URL: &str = "https://play.rust-lang.org/meta/gist";
+const VERSIONS_URL: &str = "https://play.rust-lang.org/meta/versions";
+const CRATES_URL: &str = "https://play.rust-lang.org/meta/crates";
+const USER_AGENT: &str = concat!("tomorin/", env!("CARGO_PKG_VERSION"));
+/// Cap on the backoff between retries of playground requests.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// How long [`EvalClient::ping`] waits for an answer.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+static CLIENT: OnceLock<EvalClient> = OnceLock::new();
 
 #[derive(Clone)]
 pub struct EvalClient {
     client: reqwest::Client,
+    min_interval: Duration,
+    retry: RetryPolicy,
+    /// User prelude appended to the built-in one.
+    prelude: Arc<str>,
+    backend: EvalBackend,
+    local_timeout: Duration,
+    /// Output the local backend's steps may print before they are killed, in bytes.
+    local_output_cap: usize,
+    /// When the last request was let through, shared by every clone of the client.
+    last_call: Arc<Mutex<Instant>>,
+    /// What the backend offers, fetched once per session by [`EvalClient::info`].
+    info: Arc<OnceCell<EvalInfo>>,
 }
 
 impl EvalClient {
+    fn new(conf: &Conf) -> Result<Self> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(conf.user_agent.as_deref().unwrap_or(USER_AGENT))
+            .pool_idle_timeout(*conf.http_pool_idle_timeout)
+            .pool_max_idle_per_host(conf.http_pool_max_idle_per_host)
+            .tcp_keepalive(*conf.http_tcp_keepalive)
+            .gzip(true)
+            .brotli(true);
+
+        if let Some(path) = &conf.tls_root_cert {
+            let pem = std::fs::read(path).map_err(|e| {
+                BotError::Config(format!("Failed to read root certificate {path:?}: {e}"))
+            })?;
+            let cert = reqwest::Certificate::from_pem(&pem)
+                .map_err(|e| BotError::Config(format!("Invalid root certificate {path:?}: {e}")))?;
+            builder = builder.add_root_certificate(cert);
+        }
+
+        if conf.danger_accept_invalid_certs {
+            tracing::warn!("TLS certificate verification is disabled, this is insecure!");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        let client = builder.build()?;
+
+        let prelude = match &conf.eval_prelude {
+            Some(prelude) => prelude
+                .source()
+                .map_err(|e| BotError::Config(format!("Failed to read eval prelude: {e}")))?,
+            None => String::new(),
+        };
+        if prelude.contains("fn main") {
+            return Err(BotError::Config(
+                "The eval prelude must not define `fn main`".to_string(),
+            ));
+        }
+
+        let min_interval = *conf.eval_min_interval;
+        let last_call = Instant::now()
+            .checked_sub(min_interval)
+            .unwrap_or_else(Instant::now);
+
+        Ok(EvalClient {
+            client,
+            min_interval,
+            retry: RetryPolicy {
+                max_attempts: conf.eval_retries + 1,
+                base_delay: *conf.eval_retry_backoff,
+                max_delay: MAX_RETRY_DELAY,
+                jitter: 0.5,
+            },
+            prelude: prelude.into(),
+            backend: conf.eval_backend,
+            local_timeout: *conf.eval_local_timeout,
+            local_output_cap: conf.output_max_bytes,
+            last_call: Arc::new(Mutex::new(last_call)),
+            info: Default::default(),
+        })
+    }
+
+    /// Configure the shared client. Must be called before the first [`EvalClient::intance`]
+    /// to take effect, later calls are ignored.
+    pub fn init(conf: &Conf) -> Result<()> {
+        if CLIENT.set(Self::new(conf)?).is_err() {
+            tracing::warn!("EvalClient is already initialized");
+        }
+        Ok(())
+    }
+
     pub fn intance() -> Self {
-        static CLIENT: LazyLock<EvalClient> = LazyLock::new(|| EvalClient {
-            client: reqwest::Client::new(),
-        });
+        CLIENT
+            .get_or_init(|| Self::new(&Conf::default()).expect("default EvalClient should build"))
+            .clone()
+    }
+
+    /// Wait until the next request may be sent without throttling, without claiming it.
+    async fn throttle_ready(&self) {
+        let ready = *self.last_call.lock().await + self.min_interval;
+        tokio::time::sleep_until(ready).await;
+    }
+
+    /// Wait until at least `min_interval` has passed since the previous request,
+    /// regardless of which chat triggered it.
+    async fn throttle(&self) {
+        let mut last_call = self.last_call.lock().await;
+        tokio::time::sleep_until(*last_call + self.min_interval).await;
+        *last_call = Instant::now();
+    }
+
+    fn code_options(&self) -> CodeOptions<'_> {
+        CodeOptions {
+            user_prelude: &self.prelude,
+            bench: None,
+            std_only: self.backend == EvalBackend::Local,
+            warnings: false,
+        }
+    }
+
+    /// The complete program `code` is run as.
+    pub fn generate_code(&self, code: &str) -> String {
+        generate_code_to_send(&normalize_unicode_chars(code), &self.code_options())
+    }
+
+    /// Post `body` to `url`, retrying transient failures with exponential backoff.
+    ///
+    /// Client errors, rate limiting included, are returned right away.
+    async fn post_with_retry<T: serde::Serialize>(
+        &self,
+        url: &str,
+        body: &T,
+    ) -> Result<reqwest::Response> {
+        let send = || {
+            self.client
+                .post(url)
+                .json(body)
+                .send()
+                .map(|resp| resp.and_then(reqwest::Response::error_for_status))
+        };
+        let verdict = |e: &reqwest::Error| {
+            if is_transient(e) {
+                Verdict::Retry
+            } else {
+                Verdict::Fail
+            }
+        };
+        let what = format!("request to {url}");
+        Ok(retry(&self.retry, &what, send, verdict).await?)
+    }
+
+    pub async fn eval(&self, code: &str, settings: EvalSettings) -> Result<String> {
+        self.execute(self.generate_code(code), settings).await
+    }
+
+    /// Like [`EvalClient::eval`], but a successful result is followed by the first few
+    /// compiler warnings.
+    pub async fn eval_with_warnings(&self, code: &str, settings: EvalSettings) -> Result<String> {
+        const MAX_WARNINGS: usize = 3;
+
+        let options = CodeOptions {
+            warnings: true,
+            ..self.code_options()
+        };
+        let code = generate_code_to_send(&normalize_unicode_chars(code), &options);
+        let (resp, channel) = self.run(code, settings).await?;
+        let warnings = if resp.success {
+            first_warnings(&resp.stderr, MAX_WARNINGS).join("\n")
+        } else {
+            String::new()
+        };
+        let result = generate_result_from_response(resp, channel, false);
+        if warnings.is_empty() {
+            return Ok(result);
+        }
+        Ok(format!(
+            "{result}\n\n{}",
+            htmlescape::encode_minimal(&warnings)
+        ))
+    }
+
+    /// Run `code` `iterations` times in release mode and report its timings.
+    pub async fn bench(
+        &self,
+        code: &str,
+        iterations: u32,
+        settings: EvalSettings,
+    ) -> Result<String> {
+        let options = CodeOptions {
+            bench: Some(iterations),
+            ..self.code_options()
+        };
+        let code = generate_code_to_send(&normalize_unicode_chars(code), &options);
+        let settings = EvalSettings {
+            mode: Mode::Release,
+            ..settings
+        };
+        self.execute(code, settings).await
+    }
+
+    /// Compile `code` without running it and report whether it builds, or its first error.
+    pub async fn check(&self, code: &str, settings: EvalSettings) -> Result<String> {
+        let (resp, channel) = self.compile(self.generate_code(code), settings).await?;
+        if resp.success {
+            return Ok("Compiled successfully".to_string());
+        }
+        Ok(generate_result_from_response(resp, channel, false))
+    }
+
+    /// Like [`EvalClient::eval`], followed by an estimate of where the time went: compiling
+    /// is timed with a compile-only request first, running is the rest of the full run.
+    /// Time spent waiting for throttling is left out.
+    pub async fn eval_timed(&self, code: &str, settings: EvalSettings) -> Result<String> {
+        let code = self.generate_code(code);
+
+        self.throttle_ready().await;
+        let start = Instant::now();
+        let (resp, channel) = self.compile(code.clone(), settings).await?;
+        let compile = start.elapsed();
+        if !resp.success {
+            return Ok(generate_result_from_response(resp, channel, false));
+        }
+
+        self.throttle_ready().await;
+        let start = Instant::now();
+        let result = self.execute(code, settings).await?;
+        Ok(format!(
+            "{result}\n\n{}",
+            timing_line(compile, start.elapsed())
+        ))
+    }
+
+    /// Compile the complete program `code` without running it.
+    async fn compile(&self, code: String, settings: EvalSettings) -> Result<(Response, Channel)> {
+        if self.backend == EvalBackend::Local {
+            let resp = local::check(
+                &code,
+                settings.edition,
+                self.local_timeout,
+                self.local_output_cap,
+            )
+            .await?;
+            return Ok((resp, Channel::Stable));
+        }
+        // The playground only builds library crates.
+        let resp = self.post_request(code, settings, CrateType::Lib).await?;
+        Ok((resp, settings.channel))
+    }
+
+    /// Run `code` on the configured backend and format the result.
+    ///
+    /// The result arrives whole: the playground's HTTP API has no streaming endpoint,
+    /// only its undocumented WebSocket protocol reports progress, which isn't used here.
+    async fn execute(&self, code: String, settings: EvalSettings) -> Result<String> {
+        let (resp, channel) = self.run(code, settings).await?;
+        Ok(generate_result_from_response(resp, channel, false))
+    }
 
-        CLIENT.clone()
+    /// Run `code` on the configured backend, along with the channel it was compiled with.
+    async fn run(&self, code: String, settings: EvalSettings) -> Result<(Response, Channel)> {
+        if self.backend == EvalBackend::Local {
+            let resp =
+                local::execute(&code, settings, self.local_timeout, self.local_output_cap).await?;
+            return Ok((resp, Channel::Stable));
+        }
+
+        let resp = self.post_request(code, settings, CrateType::Bin).await?;
+        Ok((resp, settings.channel))
     }
 
-    pub async fn eval(&self, code: &str) -> anyhow::Result<String> {
-        let code = normalize_unicode_chars(code);
-        let code = generate_code_to_send(&code);
+    async fn post_request(
+        &self,
+        code: String,
+        settings: EvalSettings,
+        crate_type: CrateType,
+    ) -> Result<Response> {
+        self.throttle().await;
 
         let req = Request {
-            channel: Channel::Nightly,
-            edition: "2024",
-            mode: Mode::Debug,
-            crate_type: CrateType::Bin,
+            channel: settings.channel,
+            edition: settings.edition,
+            mode: settings.mode,
+            crate_type,
             tests: false,
             backtrace: false,
             code,
         };
 
-        let resp = self.client.post(EVAL_URL).json(&req).send().await?;
-        let resp = resp.error_for_status()?.json().await?;
-        Ok(generate_result_from_response(resp, Channel::Nightly, false))
+        let resp = self.post_with_retry(EVAL_URL, &req).await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Check that the backend answers, with a request that builds nothing: the playground's
+    /// toolchain versions, or `rustc --version` for the local backend. Not throttled or
+    /// retried. Returns what answered, or why nothing did.
+    pub async fn ping(&self) -> std::result::Result<String, String> {
+        if self.backend == EvalBackend::Local {
+            let mut rustc = tokio::process::Command::new("rustc");
+            rustc.arg("--version");
+            return match output_within(&mut rustc, PING_TIMEOUT).await {
+                Ok(Some(output)) if output.status.success() => {
+                    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+                }
+                Ok(Some(output)) => Err(format!("rustc failed with {}", output.status)),
+                Ok(None) => Err(format!("Timed out after {PING_TIMEOUT:?}")),
+                Err(e) => Err(e.to_string()),
+            };
+        }
+
+        let resp = self
+            .client
+            .get(VERSIONS_URL)
+            .timeout(PING_TIMEOUT)
+            .send()
+            .await;
+        match resp {
+            Ok(resp) if resp.status().is_success() => Ok(format!("HTTP {}", resp.status())),
+            Ok(resp) => Err(format!("HTTP {}", resp.status())),
+            Err(e) if e.is_timeout() => Err(format!("Timed out after {PING_TIMEOUT:?}")),
+            Err(e) => Err(e.to_string()),
+        }
     }
+
+    /// The toolchains and crates snippets are built with, fetched on the first call and
+    /// kept for the rest of the session. A failed fetch is tried again on the next call.
+    pub async fn info(&self) -> Result<EvalInfo> {
+        let info = self
+            .info
+            .get_or_try_init(|| async {
+                if self.backend == EvalBackend::Local {
+                    return Ok(EvalInfo {
+                        toolchains: vec![(
+                            "local",
+                            self.ping().await.map_err(|e| anyhow::anyhow!(e))?,
+                        )],
+                        crates: Vec::new(),
+                    });
+                }
+                let versions: VersionsResponse = self.get_json(VERSIONS_URL).await?;
+                let crates: CratesResponse = self.get_json(CRATES_URL).await?;
+                Ok::<_, BotError>(EvalInfo::from_playground(versions, crates))
+            })
+            .await?;
+        Ok(info.clone())
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let resp = self.client.get(url).send().await?;
+        Ok(resp.error_for_status()?.json().await?)
+    }
+
+    /// Publish `code`, wrapped the same way [`EvalClient::eval`] would run it, as a gist
+    /// through the playground and return the gist URL. `output` is kept as a trailing comment.
+    pub async fn share_gist(&self, code: &str, output: Option<&str>) -> Result<String> {
+        self.throttle().await;
+
+        // The gist is meant to be opened in the playground, which has the full prelude.
+        let options = CodeOptions {
+            std_only: false,
+            ..self.code_options()
+        };
+        let mut code = generate_code_to_send(&normalize_unicode_chars(code), &options);
+        if let Some(output) = output {
+            code.push_str("\n// Output:\n");
+            for line in output.lines() {
+                code.push_str(&format!("// {line}\n"));
+            }
+        }
+
+        let req = GistRequest { code };
+        let resp = self.client.post(GIST_URL).json(&req).send().await?;
+        let resp: GistResponse = resp.error_for_status()?.json().await?;
+        Ok(resp.url)
+    }
+}
+
+/// Whether a failed request may succeed when simply sent again.
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.status().is_some_and(|s| s.is_server_error())
 }
 
 #[tokio::test]
@@ -54,6 +423,6 @@ async fn test_eval() {
             println!("Hello, world!");
         }
     "#;
-    let result = client.eval(code).await.unwrap();
+    let result = client.eval(code, EvalSettings::default()).await.unwrap();
     println!("Eval result: {}", result);
 }
@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Duration};
 
 use htmlescape::{encode_attribute, encode_minimal};
 use once_cell::sync::Lazy;
@@ -35,8 +35,45 @@ pub fn normalize_unicode_chars(input: &str) -> Cow<'_, str> {
 
 const PRELUDE: &str = include_str!("prelude.res.rs");
 
-pub fn generate_code_to_send(code: &str) -> String {
-    if code.contains("fn main()") {
+/// How [`generate_code_to_send`] wraps a snippet.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CodeOptions<'a> {
+    /// Appended to the built-in prelude.
+    pub user_prelude: &'a str,
+    /// Run the snippet this many times and print a timing table instead of its value.
+    pub bench: Option<u32>,
+    /// Leave out the parts of the built-in prelude needing crates outside of `std`.
+    pub std_only: bool,
+    /// Keep the compiler warnings about the snippet, only silencing those the prelude causes.
+    pub warnings: bool,
+}
+
+/// The built-in prelude, optionally without the lines referring to third-party crates.
+fn builtin_prelude(std_only: bool) -> Cow<'static, str> {
+    if !std_only {
+        return PRELUDE.into();
+    }
+    PRELUDE
+        .lines()
+        .filter(|line| {
+            let external = line.starts_with("extern crate ")
+                || (line.starts_with("use ") && !line.starts_with("use std::"));
+            !external
+        })
+        .map(|line| format!("{line}\n"))
+        .collect::<String>()
+        .into()
+}
+
+/// Whether `code` defines its own `main`, whatever its signature, so it must be run as is.
+pub fn defines_main(code: &str) -> bool {
+    static RE_MAIN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\bfn\s+main\s*\(").unwrap());
+    RE_MAIN.is_match(code)
+}
+
+/// Wrap `code` into a complete program, as set by `options`.
+pub fn generate_code_to_send(code: &str, options: &CodeOptions) -> String {
+    if defines_main(code) {
         return code.to_string();
     }
     macro_rules! template {
@@ -46,7 +83,34 @@ pub fn generate_code_to_send(code: &str) -> String {
     }
     let (header, body) = extract_code_headers(code);
     tracing::debug!("extract: {:?} -> ({:?}, {:?})", code, header, body);
-    let code = if body.contains("println!") || body.contains("print!") {
+    let code = if let Some(iterations) = options.bench {
+        format!(
+            template! {
+                "let mut __times = Vec::with_capacity({iterations});",
+                "    for _ in 0..{iterations} {{",
+                "        let __start = std::time::Instant::now();",
+                "        let __result = {{",
+                "            {code}",
+                "        }};",
+                "        __times.push(__start.elapsed());",
+                "        std::hint::black_box(__result);",
+                "    }}",
+                "    let __min = __times.iter().min().unwrap();",
+                "    let __max = __times.iter().max().unwrap();",
+                "    let __avg = __times.iter().sum::<std::time::Duration>() / {iterations};",
+                "    println!(\"{{:<10}} {{:<12}} {{:<12}} {{}}\", \"iterations\", \"min\", \"avg\", \"max\");",
+                "    println!(",
+                "        \"{{:<10}} {{:<12}} {{:<12}} {{}}\",",
+                "        {iterations},",
+                "        format!(\"{{__min:?}}\"),",
+                "        format!(\"{{__avg:?}}\"),",
+                "        format!(\"{{__max:?}}\"),",
+                "    );",
+            },
+            code = body,
+            iterations = iterations,
+        )
+    } else if body.contains("println!") || body.contains("print!") {
         format!("{{\n{code}\n}};")
     } else {
         format!(
@@ -59,9 +123,14 @@ pub fn generate_code_to_send(code: &str) -> String {
             code = body
         )
     };
+    let allow = if options.warnings {
+        "unused_imports, unused_macros, dead_code"
+    } else {
+        "warnings"
+    };
     format!(
         template! {
-            "#![allow(warnings)]",
+            "#![allow({allow})]",
             "{header}",
             "{prelude}",
             "fn main() -> Result<(), Box<dyn std::error::Error>> {{",
@@ -69,8 +138,13 @@ pub fn generate_code_to_send(code: &str) -> String {
             "    Ok(())",
             "}}",
         },
+        allow = allow,
         header = header,
-        prelude = PRELUDE,
+        prelude = format!(
+            "{}{}",
+            builtin_prelude(options.std_only),
+            options.user_prelude.trim_end()
+        ),
         code = code,
     )
 }
@@ -146,6 +220,31 @@ pub fn truncate_output(output: &str, max_lines: usize, max_total_columns: usize)
     output.into()
 }
 
+/// The estimated split of a run taking `total`, given that compiling alone took `compile`.
+pub fn timing_line(compile: Duration, total: Duration) -> String {
+    let secs = |d: Duration| format!("{:.2}s", d.as_secs_f64());
+    format!(
+        "compile ≈ {}, run ≈ {} (total {})",
+        secs(compile),
+        secs(total.saturating_sub(compile)),
+        secs(total)
+    )
+}
+
+/// The first `max` compiler warnings in `stderr`, without the summary lines cargo adds.
+pub fn first_warnings(stderr: &str, max: usize) -> Vec<&str> {
+    stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            line.starts_with("warning:")
+                && !line.ends_with(" emitted")
+                && !line.contains(") generated ")
+        })
+        .take(max)
+        .collect()
+}
+
 pub fn generate_result_from_response(resp: Response, channel: Channel, is_private: bool) -> String {
     if resp.success {
         let output = resp.stdout.trim();
@@ -210,4 +309,282 @@ pub fn generate_result_from_response(resp: Response, channel: Channel, is_privat
     } else {
         "(nothing??)".to_string()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_code_to_send_expression() {
+        let code = generate_code_to_send("1 + 1", &CodeOptions::default());
+        assert!(code.starts_with("#![allow(warnings)]\n"));
+        assert!(code.contains(PRELUDE));
+        assert!(code.contains("println!(\"{:?}\", {\n        1 + 1\n    });"));
+        assert!(code.ends_with("    Ok(())\n}\n"));
+    }
+
+    #[test]
+    fn test_generate_code_to_send_print() {
+        let code = generate_code_to_send("println!(\"hi\")", &CodeOptions::default());
+        assert!(code.contains("{\nprintln!(\"hi\")\n};"));
+        assert!(!code.contains("{:?}"));
+    }
+
+    #[test]
+    fn test_generate_code_to_send_main() {
+        let code = "fn main() {\n    println!(\"hi\");\n}";
+        assert_eq!(generate_code_to_send(code, &CodeOptions::default()), code);
+    }
+
+    #[test]
+    fn test_defines_main() {
+        assert!(defines_main("fn main() {}"));
+        assert!(defines_main("fn main () {}"));
+        assert!(defines_main("fn  main(){}"));
+        assert!(defines_main("async fn main() {}"));
+        assert!(defines_main(
+            "#[tokio::main]\npub async fn main() -> io::Result<()> {}"
+        ));
+        assert!(defines_main(
+            "fn main() -> Result<(), Box<dyn Error>> {\n    Ok(())\n}"
+        ));
+        assert!(!defines_main("fn main_loop() {}"));
+        assert!(!defines_main("fn domain() {}"));
+        assert!(!defines_main("main()"));
+
+        let code = "fn main () -> Result<(), String> {\n    Ok(())\n}";
+        assert_eq!(generate_code_to_send(code, &CodeOptions::default()), code);
+    }
+
+    #[test]
+    fn test_generate_code_to_send_bench() {
+        let code = generate_code_to_send(
+            "#![feature(test)]\n(1..100).sum::<u32>()",
+            &CodeOptions {
+                bench: Some(50),
+                ..Default::default()
+            },
+        );
+        assert!(code.starts_with("#![allow(warnings)]\n#![feature(test)]\n"));
+        assert!(code.contains("for _ in 0..50 {"));
+        assert!(code.contains("let __result = {\n            (1..100).sum::<u32>()\n        };"));
+        assert!(code.contains("/ 50;"));
+        assert!(!code.contains("println!(\"{:?}\""));
+    }
+
+    #[test]
+    fn test_generate_code_to_send_user_prelude() {
+        let code = generate_code_to_send(
+            "1",
+            &CodeOptions {
+                user_prelude: "use std::fmt::Write;\n\n",
+                ..Default::default()
+            },
+        );
+        let prelude = format!("{PRELUDE}use std::fmt::Write;\nfn main()");
+        assert!(code.contains(&prelude));
+    }
+
+    #[test]
+    fn test_generate_code_to_send_std_only() {
+        let code = generate_code_to_send(
+            "1",
+            &CodeOptions {
+                std_only: true,
+                ..Default::default()
+            },
+        );
+        assert!(!code.contains("lazy_static"));
+        assert!(!code.contains("once_cell"));
+        assert!(!code.contains("serde"));
+        assert!(code.contains("use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};\n"));
+        assert!(code.contains("fn type_name_of_val"));
+    }
+
+    #[test]
+    fn test_generate_code_to_send_headers() {
+        fn header_before_prelude(code: &str, header: &str) {
+            let header_pos = code.find(header).unwrap();
+            let prelude_pos = code.find(PRELUDE).unwrap();
+            let main_pos = code.find("fn main()").unwrap();
+            assert!(header_pos < prelude_pos && prelude_pos < main_pos);
+        }
+
+        let code = generate_code_to_send("extern crate foo;\nfoo::bar()", &CodeOptions::default());
+        header_before_prelude(&code, "extern crate foo;");
+        assert!(code.contains("println!(\"{:?}\", {\n        foo::bar()\n    });"));
+
+        let code = generate_code_to_send("#![feature(never_type)]\n1", &CodeOptions::default());
+        header_before_prelude(&code, "#![feature(never_type)]");
+        assert!(code.contains("println!(\"{:?}\", {\n        1\n    });"));
+
+        let code = generate_code_to_send(
+            "#![feature(never_type)]\n#[macro_use] extern crate foo;\nbar!()",
+            &CodeOptions::default(),
+        );
+        header_before_prelude(
+            &code,
+            "#![feature(never_type)]\n#[macro_use] extern crate foo;",
+        );
+        assert!(code.contains("println!(\"{:?}\", {\n        bar!()\n    });"));
+    }
+
+    fn response(success: bool, stdout: &str, stderr: &str) -> Response {
+        Response {
+            stderr: stderr.to_string(),
+            stdout: stdout.to_string(),
+            success,
+        }
+    }
+
+    #[test]
+    fn test_result_success() {
+        let resp = response(true, "Some(<1>)\n", "   Compiling playground");
+        assert_eq!(
+            generate_result_from_response(resp, Channel::Stable, true),
+            "Some(&lt;1&gt;)"
+        );
+
+        let resp = response(true, " \n", "");
+        assert_eq!(
+            generate_result_from_response(resp, Channel::Stable, true),
+            "(no output)"
+        );
+
+        let resp = response(true, "1\n2\n3\n4\n", "");
+        assert_eq!(
+            generate_result_from_response(resp, Channel::Stable, false),
+            "1\n2\n3..."
+        );
+    }
+
+    #[test]
+    fn test_generate_code_to_send_warnings() {
+        let code = generate_code_to_send("1", &CodeOptions::default());
+        assert!(code.starts_with("#![allow(warnings)]\n"));
+
+        let code = generate_code_to_send(
+            "1",
+            &CodeOptions {
+                warnings: true,
+                ..Default::default()
+            },
+        );
+        assert!(code.starts_with("#![allow(unused_imports, unused_macros, dead_code)]\n"));
+    }
+
+    #[test]
+    fn test_first_warnings() {
+        let stderr = "   Compiling playground v0.0.1 (/playground)
+warning: unused variable: `x`
+ --> src/main.rs:3:9
+  |
+3 |     let x = 1;
+  |         ^ help: if this is intentional, prefix it with an underscore: `_x`
+warning: variable does not need to be mutable
+warning: unreachable statement
+warning: `playground` (bin \"playground\") generated 3 warnings
+    Finished dev [unoptimized + debuginfo] target(s) in 0.61s";
+        assert_eq!(
+            first_warnings(stderr, 2),
+            vec![
+                "warning: unused variable: `x`",
+                "warning: variable does not need to be mutable",
+            ]
+        );
+        assert_eq!(
+            first_warnings("warning: 1 warning emitted", 3),
+            Vec::<&str>::new()
+        );
+        assert_eq!(first_warnings("", 3), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_timing_line() {
+        assert_eq!(
+            timing_line(Duration::from_millis(1200), Duration::from_millis(1500)),
+            "compile ≈ 1.20s, run ≈ 0.30s (total 1.50s)"
+        );
+        // The check may take longer than the whole run when the build was cached.
+        assert_eq!(
+            timing_line(Duration::from_secs(2), Duration::from_secs(1)),
+            "compile ≈ 2.00s, run ≈ 0.00s (total 1.00s)"
+        );
+    }
+
+    #[test]
+    fn test_result_error_code() {
+        let stderr = "   Compiling playground v0.0.1 (/playground)
+warning: unused variable: `x`
+error[E0308]: mismatched types
+ --> src/main.rs:3:5";
+        for channel in [Channel::Stable, Channel::Nightly] {
+            let url = format!(
+                "https://doc.rust-lang.org/{}/error-index.html#E0308",
+                channel.as_str()
+            );
+            assert_eq!(
+                generate_result_from_response(response(false, "", stderr), channel, true),
+                format!(
+                    r#"error<a href="{}">[E0308]</a>: mismatched types"#,
+                    encode_attribute(&url)
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_result_code_and_issue() {
+        let stderr = "error[E0658]: use of unstable library feature `test` (see issue #50297)";
+        let url = encode_attribute("https://doc.rust-lang.org/beta/error-index.html#E0658");
+        assert_eq!(
+            generate_result_from_response(response(false, "", stderr), Channel::Beta, true),
+            format!(
+                r#"error<a href="{url}">[E0658]</a>: use of unstable library feature <code>test</code> (see issue <a href="https://github.com/rust-lang/rust/issues/50297">#50297</a>)"#
+            )
+        );
+
+        let stderr = "warning: expected `&str`, found `u8`\n";
+        assert_eq!(
+            generate_result_from_response(response(false, "", stderr), Channel::Stable, true),
+            "warning: expected <code>&amp;str</code>, found <code>u8</code>"
+        );
+
+        let resp = response(false, "", "   Compiling playground\n    Finished dev\n");
+        assert_eq!(
+            generate_result_from_response(resp, Channel::Stable, true),
+            "(nothing??)"
+        );
+    }
+
+    #[test]
+    fn test_truncate_output_ascii() {
+        assert_eq!(truncate_output("hello", 3, 10), "hello");
+        assert_eq!(truncate_output("hello", 3, 5), "hello");
+        assert_eq!(truncate_output("abcdefghij", 3, 5), "ab...");
+        assert_eq!(truncate_output("a\nb\nc\nd", 3, 80), "a\nb\nc...");
+        assert_eq!(truncate_output("a\nb\nc", 3, 80), "a\nb\nc");
+    }
+
+    #[test]
+    fn test_truncate_output_cjk() {
+        assert_eq!(truncate_output("你好世界", 3, 8), "你好世界");
+        assert_eq!(truncate_output("你好世界你好", 3, 7), "你...");
+        assert_eq!(truncate_output("你好世界你好", 3, 5), "...");
+        assert_eq!(truncate_output("ab你好cd", 3, 5), "a...");
+    }
+
+    #[test]
+    fn test_truncate_output_emoji() {
+        assert_eq!(truncate_output("ab😀😀😀", 3, 5), "a...");
+        assert_eq!(truncate_output("😀😀", 3, 4), "😀😀");
+    }
+
+    #[test]
+    fn test_truncate_output_mid_grapheme() {
+        // The backtrack stops on a char boundary, even if that splits a ZWJ sequence.
+        assert_eq!(truncate_output("👨\u{200d}👩xyz", 3, 6), "👨\u{200d}...");
+        assert_eq!(truncate_output("abcde\u{301}fg", 3, 6), "abc...");
+    }
+}
@@ -17,6 +17,8 @@ pub struct Request {
 #[serde(rename_all = "lowercase")]
 pub enum CrateType {
     Bin,
+    /// Only built, never run.
+    Lib,
 }
 
 #[derive(Debug, Deserialize)]
@@ -33,6 +35,21 @@ pub enum Mode {
     Release,
 }
 
+impl Mode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mode::Debug => "debug",
+            Mode::Release => "release",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [Mode::Debug, Mode::Release]
+            .into_iter()
+            .find(|mode| mode.as_str() == s)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Channel {
@@ -49,4 +66,95 @@ impl Channel {
             Channel::Nightly => "nightly",
         }
     }
-}
\ No newline at end of file
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [Channel::Stable, Channel::Beta, Channel::Nightly]
+            .into_iter()
+            .find(|channel| channel.as_str() == s)
+    }
+}
+
+/// Rust editions snippets can be built with.
+pub const EDITIONS: [&str; 4] = ["2015", "2018", "2021", "2024"];
+
+/// How snippets are built. The local backend always uses the installed toolchain, so
+/// only the mode and edition apply to it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct EvalSettings {
+    pub channel: Channel,
+    pub mode: Mode,
+    pub edition: &'static str,
+}
+
+impl Default for EvalSettings {
+    fn default() -> Self {
+        Self {
+            channel: Channel::Nightly,
+            mode: Mode::Debug,
+            edition: "2024",
+        }
+    }
+}
+
+/// The playground's `/meta/versions`, of which only the rustc versions are kept.
+#[derive(Debug, Deserialize)]
+pub struct VersionsResponse {
+    pub stable: ChannelVersion,
+    pub beta: ChannelVersion,
+    pub nightly: ChannelVersion,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelVersion {
+    pub rustc: ToolVersion,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToolVersion {
+    pub version: String,
+}
+
+/// The playground's `/meta/crates`.
+#[derive(Debug, Deserialize)]
+pub struct CratesResponse {
+    pub crates: Vec<CrateVersion>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CrateVersion {
+    pub name: String,
+    pub version: String,
+}
+
+/// What snippets can be built with: the toolchain of each channel and the crates to `use`.
+#[derive(Clone, Debug, Default)]
+pub struct EvalInfo {
+    /// Channel, or `local`, and its rustc version.
+    pub toolchains: Vec<(&'static str, String)>,
+    pub crates: Vec<CrateVersion>,
+}
+
+impl EvalInfo {
+    pub fn from_playground(versions: VersionsResponse, crates: CratesResponse) -> Self {
+        Self {
+            toolchains: vec![
+                (Channel::Stable.as_str(), versions.stable.rustc.version),
+                (Channel::Beta.as_str(), versions.beta.rustc.version),
+                (Channel::Nightly.as_str(), versions.nightly.rustc.version),
+            ],
+            crates: crates.crates,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GistRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GistResponse {
+    pub id: String,
+    pub url: String,
+    pub code: String,
+}
@@ -1,18 +1,28 @@
 #![feature(async_fn_traits)]
-#![feature(fn_traits, unboxed_closures)]
+#![feature(unboxed_closures)]
+mod alloc_stats;
 mod args;
 mod bot;
 mod conf;
+mod error;
 mod eval;
+mod retry;
 
 use args::Args;
 use clap::Parser;
 
 pub async fn run() -> anyhow::Result<()> {
-    Args::parse().init();
+    let log_file = Args::parse().init();
 
     let conf = conf::Conf::load_or_create()
         .map_err(|e| anyhow::anyhow!("Failed to load or create configuration: {e}"))?;
+    if let Some(path) = &conf.log_file {
+        log_file
+            .open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open log file {path:?}: {e}"))?;
+    }
+
+    eval::EvalClient::init(&conf)?;
 
     bot::UserBot::new(conf).await?.run().await
 }
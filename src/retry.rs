@@ -0,0 +1,150 @@
+//! Retrying failed operations with exponential backoff.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use grammers_client::InvocationError;
+
+/// How often and how long to wait before trying a failed operation again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further one.
+    pub base_delay: Duration,
+    /// Cap on the delay between attempts. Telegram asking to wait longer than this gives up.
+    pub max_delay: Duration,
+    /// Fraction of each delay, from 0 to 1, that is randomly cut off, so clients failing
+    /// together don't retry together.
+    pub jitter: f64,
+}
+
+/// What to do about a failed attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Give up and return the error.
+    Fail,
+    /// Retry after the policy's backoff.
+    Retry,
+    /// Retry after exactly this long, as the server asked.
+    RetryAfter(Duration),
+}
+
+impl RetryPolicy {
+    /// The delay before retry number `retry`, counted from 0, where `random` is in `[0, 1)`.
+    fn delay(&self, retry: u32, random: f64) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        backoff.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * random)
+    }
+}
+
+/// A number in `[0, 1)` that is good enough for spreading retries.
+fn random() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+/// Run `f` until it succeeds, `classify` rules out retrying its error or `policy` runs
+/// out of attempts. `what` names the operation in the log.
+pub async fn retry<T, E, Fut>(
+    policy: &RetryPolicy,
+    what: &str,
+    mut f: impl FnMut() -> Fut,
+    classify: impl Fn(&E) -> Verdict,
+) -> Result<T, E>
+where
+    E: Display,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        let e = match f().await {
+            Err(e) if attempt < policy.max_attempts => e,
+            result => return result,
+        };
+        let delay = match classify(&e) {
+            Verdict::Fail => return Err(e),
+            Verdict::Retry => policy.delay(attempt - 1, random()),
+            Verdict::RetryAfter(delay) if delay <= policy.max_delay => delay,
+            Verdict::RetryAfter(_) => return Err(e),
+        };
+        tracing::warn!(
+            "{what} failed: {e}, retry {attempt}/{} in {delay:?}",
+            policy.max_attempts - 1
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Retry Telegram calls only on flood waits, after the time Telegram asks for.
+pub fn flood_wait(e: &InvocationError) -> Verdict {
+    match e {
+        InvocationError::Rpc(rpc) if rpc.name == "FLOOD_WAIT" => {
+            Verdict::RetryAfter(Duration::from_secs(rpc.value.unwrap_or_default().into()))
+        }
+        _ => Verdict::Fail,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(300),
+        jitter: 0.5,
+    };
+
+    #[test]
+    fn test_delay() {
+        assert_eq!(POLICY.delay(0, 0.0), Duration::from_millis(100));
+        assert_eq!(POLICY.delay(1, 0.0), Duration::from_millis(200));
+        assert_eq!(POLICY.delay(2, 0.0), Duration::from_millis(300));
+        assert_eq!(POLICY.delay(40, 0.0), Duration::from_millis(300));
+        assert_eq!(POLICY.delay(1, 0.5), Duration::from_millis(150));
+        assert!((0.0..1.0).contains(&random()));
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let policy = RetryPolicy {
+            base_delay: Duration::ZERO,
+            ..POLICY
+        };
+        let calls = Cell::new(0);
+        let flaky = || {
+            calls.set(calls.get() + 1);
+            let n = calls.get();
+            async move { if n < 3 { Err(n) } else { Ok(n) } }
+        };
+
+        assert_eq!(retry(&policy, "op", flaky, |_| Verdict::Retry).await, Ok(3));
+
+        calls.set(0);
+        let twice = RetryPolicy {
+            max_attempts: 2,
+            ..policy
+        };
+        assert_eq!(retry(&twice, "op", flaky, |_| Verdict::Retry).await, Err(2));
+
+        calls.set(0);
+        assert_eq!(retry(&policy, "op", flaky, |_| Verdict::Fail).await, Err(1));
+
+        calls.set(0);
+        let too_long = |_: &i32| Verdict::RetryAfter(Duration::from_secs(1));
+        assert_eq!(retry(&policy, "op", flaky, too_long).await, Err(1));
+    }
+}